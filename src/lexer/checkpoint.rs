@@ -0,0 +1,39 @@
+// Import `VecDeque`, the buffer type of the peeked tokens saved in a checkpoint.
+use std::collections::VecDeque;
+
+// Import the `Token` and `LexerError` types making up the saved lookahead buffer.
+use super::error::LexerError;
+use super::token::Token;
+
+// --- Checkpoint Definition ---
+// A `Checkpoint` is a saved lexer state, returned by `Lexer::checkpoint` and handed back to
+// `Lexer::restore` to make the lexer continue from that point again. This is what a
+// backtracking parser needs: save a checkpoint, try one production, and restore the checkpoint
+// if it fails. The tokens lexed after restoring are exactly the ones lexed the first time.
+//
+// The state is everything scanning depends on or reports: the position, the line tracking,
+// the skip counters, the last token, the lookahead buffer of `peek_n` and the resource limit
+// bookkeeping. The fields are private; a checkpoint can only be restored.
+//
+// `#[derive(...)]`:
+// - `Clone`: Allows restoring the same checkpoint into a copy, or keeping several around.
+// - `Debug`: Allows checkpoints to be printed with `{:?}` for debugging.
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    pub(crate) position: usize,
+    pub(crate) line: usize,
+    pub(crate) line_start: usize,
+    pub(crate) skipped_bytes: usize,
+    pub(crate) comment_bytes: usize,
+    // `warning_count`: How many warnings had been collected; later ones are dropped on restore.
+    pub(crate) warning_count: usize,
+    pub(crate) last_token: Option<Token>,
+    pub(crate) token_count: usize,
+    pub(crate) limit_exceeded: bool,
+    // `peeked`: The lookahead buffer, whose entries hold checkpoints of their own (taken with
+    // an empty buffer, see `Lexer::peek_n`).
+    pub(crate) peeked: VecDeque<(Result<Token, LexerError>, Checkpoint)>,
+    pub(crate) max_line_length: usize,
+    pub(crate) after_identifier: bool,
+    pub(crate) shebang_len: usize,
+}
@@ -2,7 +2,11 @@
 
 // Import the `Regex` type from the `regex` crate.
 // This type is used to define and match regular expressions against text.
-use regex::Regex;
+// `CaptureLocations` holds where the groups of a match are, and can be reused between matches.
+use regex::{CaptureLocations, Regex};
+
+// Import `RefCell`, which lets the thread-local capture locations of `word_match` be reused.
+use std::cell::RefCell;
 
 // Import the `lazy_static` macro from the `lazy_static` crate.
 // This macro is used to ensure that complex static variables (like compiled regexes)
@@ -10,41 +14,112 @@ use regex::Regex;
 // This is more efficient than recompiling regexes every time they are needed.
 use lazy_static::lazy_static;
 
-// Import the `Token` enum from the sibling module `token.rs` within the `lexer` module.
+// Import `ParseIntError` and its `IntErrorKind`, which tells why a constant failed to parse
+// (overflow or a bad digit).
+use std::num::{IntErrorKind, ParseIntError};
+
+// Import `VecDeque`, the ring buffer holding the tokens lexed ahead by `peek_n`.
+use std::collections::VecDeque;
+
+// Import the `LexerError` struct and its `LexerErrorKind` from the sibling module `error.rs`.
 // `super::` refers to the parent module of the current file (`core.rs`), which is `lexer` (defined by `lexer/mod.rs`).
-// So, `super::token` refers to `lexer::token`.
-use super::error::LexerError;
+// So, `super::error` refers to `lexer::error`.
+use super::error::{ErrorContext, LexerError, LexerErrorKind, ResourceKind};
+
+// Import the `Token` enum from the sibling module `token.rs`.
+// `TokenRef` is the borrowed counterpart of `Token`, which the lexer produces internally.
+use super::token::{Token, TokenRef};
+
+// Import the `Span` struct from the sibling module `span.rs`.
+// Spans record the byte range of the source text each token was produced from.
+// `SourcePiece` is the item type of the lossless tokenization.
+use super::span::{SourcePiece, Span};
+
+// Import the column-counting and line-break helpers shared with `LineIndex`, so that the
+// lines and columns the lexer reports in errors always agree with `LineIndex::visual_col`
+// (and, with the default tab width of 1, with `LineIndex::line_col`).
+use super::line_index::{LineIndex, ends_line_at, line_break_len, visual_width};
+
+// Import the interner types used by `tokenize_interned`.
+use super::interner::{InternedToken, StringInterner};
+
+// Import the `Rule` struct from the sibling module `rule.rs`, for user-registered token patterns.
+use super::rule::Rule;
 
-// Import the `LexerError` enum from the sibling module `error.rs`.
-use super::token::Token;
+// Import the `LexerWarning` enum from the sibling module `warning.rs`, and the edit-distance
+// check used to detect keyword typos.
+use super::warning::{LexerWarning, is_one_edit_apart};
+
+// Import the `Diagnostic` enum, which combines errors and warnings.
+use super::diagnostic::Diagnostic;
+
+// Import `group_by_line`, which `tokens_by_line` builds on.
+use super::lines::group_by_line;
+
+// Import the `Checkpoint` struct, the saved state `checkpoint` and `restore` work with.
+use super::checkpoint::Checkpoint;
+
+// Import the `Tokens` iterator from the sibling module `iter.rs`.
+use super::iter::Tokens;
+
+// Import the `LexerOptions` struct from the sibling module `options.rs`.
+// It holds the configuration (comment handling, leniency, keywords, ...) a `Lexer` runs with.
+// `RecoveryPolicy` is the setting that decides how `tokenize_all_recovering` resumes after errors.
+use super::options::{LexerOptions, RecoveryPolicy};
 
 // --- Regular Expression Definitions ---
 // The `lazy_static!` block is used to define static `Regex` instances.
 // Compiling regexes can be somewhat expensive, so doing it once at program
 // startup (or first use) and storing them statically improves performance.
 lazy_static! {
-    // Regex for matching Identifiers.
+    // Regex for matching Identifiers and (Integer) Constants in one go.
+    // These are by far the most common tokens, so instead of trying one regex for identifiers
+    // and another one for constants, a single alternation is matched once and the named
+    // capture group that took part in the match says which of the two was found (see
+    // `word_match`).
     // - `\A`: Anchors the match to the beginning of the string slice being tested. This is crucial
     //   because we always want to match tokens from the current lexer position.
-    // - `[a-zA-Z_]`: Matches a single ASCII letter (uppercase or lowercase) or an underscore.
-    //   This defines the allowed starting characters for an identifier.
-    // - `\w*`: Matches zero or more "word" characters. In many regex engines (including Rust's default),
-    //   `\w` is equivalent to `[a-zA-Z0-9_]`. So, this part matches subsequent letters, digits, or underscores.
-    // - `\b`: Matches a "word boundary". This ensures that the identifier doesn't immediately blend
-    //   into another word character. For example, `int` in `int main` is a match because space is a boundary,
-    //   but `int` in `intFoo` would match `intFoo` entirely (if `Foo` were valid word chars).
-    //   More importantly, for something like `return2`, `return` would match because `2` forms a boundary.
-    //   For `123bar`, `123` (if it were an identifier) would fail to match `123` alone if `CONSTANT_RE` didn't also use `\b`.
+    // - `(?P<identifier>[a-zA-Z_]\w*)`: An identifier, in the group named `identifier`.
+    //   - `[a-zA-Z_]`: Matches a single ASCII letter (uppercase or lowercase) or an underscore.
+    //     This defines the allowed starting characters for an identifier.
+    //   - `\w*`: Matches zero or more "word" characters. In many regex engines (including Rust's default),
+    //     `\w` is equivalent to `[a-zA-Z0-9_]`. So, this part matches subsequent letters, digits, or underscores.
+    // - `(?P<constant>[0-9]+)`: An integer constant, in the group named `constant`: one or more
+    //   ASCII digits (0 through 9).
+    //   The two branches never compete, since one starts with a digit and the other never does.
+    // - `\b`: Matches a "word boundary" after either branch. This ensures that the token doesn't
+    //   immediately blend into another word character. For an identifier the greedy `\w*` has
+    //   already consumed every word character, so e.g. `return2` is one identifier. For a
+    //   constant it prevents `123` from matching in `123foo`: the digits must be properly
+    //   terminated, and `123foo` falls through to the suffix error below.
     // `.unwrap()`: `Regex::new` returns a `Result`. We `.unwrap()` here because if these fundamental
     //   regexes are invalid, the program cannot function, so panicking is acceptable at startup.
-    static ref IDENTIFIER_RE: Regex = Regex::new(r"\A[a-zA-Z_]\w*\b").unwrap();
+    static ref WORD_RE: Regex =
+        Regex::new(r"\A(?:(?P<identifier>[a-zA-Z_]\w*)|(?P<constant>[0-9]+))\b").unwrap();
+
+    // Regex for matching integer constants with digit separators (see
+    // `LexerOptions::digit_separators`), e.g. `1_000`.
+    // - `(?:_[0-9]+)*`: Any number of groups of an underscore followed by digits, so every
+    //   underscore sits between two digits.
+    static ref SEPARATED_CONSTANT_RE: Regex = Regex::new(r"\A[0-9]+(?:_[0-9]+)*\b").unwrap();
+
+    // Regex for recognizing digits run together with identifier characters, e.g. `123abc`
+    // or `0x`. `WORD_RE` rejects these (thanks to `\b`); this regex is only used after
+    // nothing else matched, to report them with a clear error instead of an unexpected `1`.
+    // - `([0-9]+)`: The digits (capture group 1).
+    // - `([^\W0-9]\w*)`: The suffix (capture group 2): a word character that is not a digit
+    //   (so the digits are never split up), followed by any further word characters.
+    static ref NUMBER_WITH_SUFFIX_RE: Regex = Regex::new(r"\A([0-9]+)([^\W0-9]\w*)").unwrap();
 
-    // Regex for matching (Integer) Constants.
-    // - `\A`: Anchors to the beginning of the slice.
-    // - `[0-9]+`: Matches one or more ASCII digits (0 through 9).
-    // - `\b`: Matches a word boundary. This prevents `123` from matching in `123foo` if `foo` starts
-    //   with a word character, ensuring the constant is properly terminated.
-    static ref CONSTANT_RE: Regex = Regex::new(r"\A[0-9]+\b").unwrap();
+    // Regex for matching floating-point constants.
+    // - `[0-9]+\.[0-9]*`: Digits, a dot and optional further digits, e.g. `3.14` or `5.`.
+    // - `\.[0-9]+`: A dot followed by digits, e.g. `.5`.
+    // - `\w*`: Any identifier characters run together with the number, e.g. the `f` of `1.5f`.
+    //   They are matched so that the whole text is one (invalid) token rather than a float
+    //   followed by an identifier; `make_builtin_token` reports them as a suffix error.
+    // Whether a leading `.` starts a float or is a `Dot` also depends on the previous token
+    // (see `BuiltinRule::match_len`).
+    static ref FLOAT_RE: Regex = Regex::new(r"\A(?:[0-9]+\.[0-9]*|\.[0-9]+)\w*").unwrap();
 
     // Regexes for simple punctuation tokens. These are very straightforward.
     // They match the literal character at the beginning of the slice.
@@ -57,18 +132,50 @@ lazy_static! {
     // `;`: Semicolon does not have a special regex meaning here, so it doesn't strictly need escaping,
     //   but escaping non-alphanumeric characters consistently is not harmful.
     static ref SEMICOLON_RE: Regex = Regex::new(r"\A;").unwrap();
+    // `/`: The division operator. A slash only becomes a token when it doesn't start a
+    //   comment, e.g. `//` lexes as two slashes when `//` is not a line-comment prefix.
+    static ref SLASH_RE: Regex = Regex::new(r"\A/").unwrap();
+    // `.`: A dot must be escaped, since an unescaped `.` matches any character.
+    static ref DOT_RE: Regex = Regex::new(r"\A\.").unwrap();
+
+    // Regexes for string and character literals.
+    // - `(?:u8|u|U|L)?`: A string literal's optional encoding prefix, as in `L"wide"`.
+    // - `"` / `'`: The opening and closing quote.
+    // - `[^"\\\r\n]`: Any character except the closing quote, a backslash or a line break.
+    // - `\\[^\r\n]`: A backslash and the character it escapes (which may be a quote).
+    // A literal may not span lines, so a line break before the closing quote means no match
+    // (reported as `UnterminatedString`/`UnterminatedChar` by `match_token`).
+    static ref STRING_RE: Regex =
+        Regex::new(r#"\A(?:u8|u|U|L)?"(?:[^"\\\r\n]|\\[^\r\n])*""#).unwrap();
+    // Regex for preprocessor directives like `#include <stdio.h>` or `#define N 10`.
+    // - `#`: The directive starts with a hash.
+    // - `\\(?:\r\n|\r|\n)`: A backslash-newline continuation, which extends the directive onto
+    //   the next line. It is tried first, so a backslash at the end of a line never ends it.
+    // - `[^\r\n]`: Any other character up to the end of the line.
+    // Whether a `#` starts a directive also depends on where it is (see `match_token`).
+    static ref DIRECTIVE_RE: Regex = Regex::new(r"\A#(?:\\(?:\r\n|\r|\n)|[^\r\n])*").unwrap();
+
+    // Regex for a directive introduced by the `??=` trigraph instead of `#` (see
+    // `LexerOptions::translate_trigraphs`); otherwise the same as `DIRECTIVE_RE`.
+    static ref TRIGRAPH_DIRECTIVE_RE: Regex =
+        Regex::new(r"\A\?\?=(?:\\(?:\r\n|\r|\n)|[^\r\n])*").unwrap();
+
+    static ref CHAR_RE: Regex = Regex::new(r"\A'(?:[^'\\\r\n]|\\[^\r\n])*'").unwrap();
 
     // Regexes for skipping non-token parts of the input.
     // - Whitespace:
     //   - `\A`: Anchor.
-    //   - `\s+`: Matches one or more whitespace characters (spaces, tabs, newlines, etc.).
-    static ref WHITESPACE_RE: Regex = Regex::new(r"\A\s+").unwrap();
+    //   - `[\s&&[^\x{A0}]]+`: Matches one or more whitespace characters (spaces, tabs,
+    //     newlines, etc.), except the non-breaking space (U+00A0). That one is an unexpected
+    //     character, so that the lookalike hint can point it out (see `LexerError::hint`):
+    //     it usually comes from pasted text and breaks other C tools that read the file.
+    static ref WHITESPACE_RE: Regex = Regex::new(r"\A[\s&&[^\x{A0}]]+").unwrap();
 
-    // - Single-line comments:
-    //   - `\A//`: Matches the literal `//` at the beginning of the slice.
-    //   - `.*`: Matches any character (except newline, by default for `.`) zero or more times.
-    //     This consumes the rest of the line after `//`.
-    static ref SINGLE_LINE_COMMENTS_RE: Regex = Regex::new(r"\A//.*").unwrap();
+    // - Whitespace other than line breaks (used when newlines are emitted as tokens):
+    //   - `[^\S\r\n\x{A0}]+`: Matches one or more characters that are whitespace (`\S`
+    //     negated) but are neither `\r` nor `\n`, i.e. spaces, tabs, form feeds, etc. Like
+    //     above, the non-breaking space is excluded.
+    static ref INLINE_WHITESPACE_RE: Regex = Regex::new(r"\A[^\S\r\n\x{A0}]+").unwrap();
 
     // - Multi-line comments:
     //   - `\A`: Anchor.
@@ -88,23 +195,292 @@ lazy_static! {
 // --- Keyword Definitions ---
 // A static array that maps string representations of keywords to their corresponding `Token` enum variants.
 // This is used after an identifier is matched to check if it's actually a keyword.
-// - `[(&str, Token); 3]`: Defines an array of 3 elements. Each element is a tuple `(&str, Token)`.
+// - `[(&str, Token); 10]`: Defines an array of 10 elements. Each element is a tuple `(&str, Token)`.
 //   - `&str`: A string slice representing the keyword text (e.g., "int").
 //   - `Token`: The corresponding `Token` enum variant (e.g., `Token::KwInt`).
 // The `Token` variants here (like `Token::KwInt`) are `clone()`d from their definitions because
 // `Token` itself derives `Clone`. This ensures that the `KEYWORDS` array owns its `Token` values.
-const KEYWORDS: [(&str, Token); 3] = [
+const KEYWORDS: [(&str, Token); 10] = [
     ("int", Token::KwInt),
     ("void", Token::KwVoid),
     ("return", Token::KwReturn),
+    ("sizeof", Token::KwSizeof),
+    ("typedef", Token::KwTypedef),
+    ("struct", Token::KwStruct),
+    ("union", Token::KwUnion),
+    ("enum", Token::KwEnum),
+    ("static", Token::KwStatic),
+    ("extern", Token::KwExtern),
+];
+
+// --- Trigraphs ---
+// The nine trigraphs of C, old spellings of characters missing from some keyboards and
+// character sets: `??` followed by the character in the first column stands for the one in
+// the second. Only recognized with `LexerOptions::translate_trigraphs`.
+const TRIGRAPHS: [(char, char); 9] = [
+    ('=', '#'),
+    ('/', '\\'),
+    ('\'', '^'),
+    ('(', '['),
+    (')', ']'),
+    ('!', '|'),
+    ('<', '{'),
+    ('>', '}'),
+    ('-', '~'),
 ];
 
+// `TRIGRAPH_LEN` is the length in bytes of every trigraph.
+const TRIGRAPH_LEN: usize = 3;
+
+// `trigraph_at` returns the character the trigraph at the start of `input` stands for, or
+// `None` if `input` does not start with one. `???` is not a trigraph (though `???<` ends in
+// one, as the last three characters are `??<`).
+fn trigraph_at(input: &str) -> Option<char> {
+    let third = input.strip_prefix("??")?.chars().next()?;
+    TRIGRAPHS
+        .iter()
+        .find(|(spelling, _)| *spelling == third)
+        .map(|(_, translated)| *translated)
+}
+
+// `default_keywords` converts the static `KEYWORDS` table into the owned form stored in
+// `LexerOptions::keywords`. Options own their table so that users can supply their own keywords.
+pub(crate) fn default_keywords() -> Vec<(String, Token)> {
+    KEYWORDS
+        .iter()
+        .map(|(text, token)| (text.to_string(), token.clone()))
+        .collect()
+}
+
+// --- Built-in Token Rules ---
+// `BuiltinRule` names each of the lexer's own token patterns. During matching they are
+// treated just like user-registered `Rule`s: each one that matches is a candidate, and the
+// longest match wins (see `Lexer::longest_match`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BuiltinRule {
+    Newline,       // A line break; only a token when `LexerOptions::emit_newlines` is set.
+    Comment, // A single-line or `/* */` comment; only a token when `LexerOptions::keep_comments` is set.
+    OpenParen, // `(`
+    CloseParen, // `)`
+    OpenBrace, // `{`
+    CloseBrace, // `}`
+    Semicolon, // `;`
+    Slash,   // `/`
+    Dot,     // `.`
+    StringLiteral, // A `"..."` string literal, with an optional encoding prefix (`L"..."`).
+    RawString, // A raw string literal, `R"delim(...)delim"`.
+    CharLiteral, // A `'...'` character literal.
+    Word,    // An identifier or an integer constant, matched together (see `WORD_RE`).
+    Identifier, // An identifier, which is promoted to a keyword token if it is in the keyword table.
+    Constant,   // An integer constant.
+    Float,      // A floating-point constant.
+}
+
+// `BUILTIN_RULES` lists every built-in rule in priority order. When two rules match the
+// same number of bytes, the one listed first wins. None of the built-in patterns can match
+// the same text (a comment and `Slash` can both start with `/`, but the comment is always
+// longer; likewise `Dot` and `Constant` against `Float`), so for the built-in grammar the
+// order only documents intent.
+// Identifiers and constants are tried together as `Word`, which reports whichever of the two
+// it found; `Identifier` and `Constant` themselves are not listed.
+const BUILTIN_RULES: [BuiltinRule; 14] = [
+    BuiltinRule::Newline,
+    BuiltinRule::Comment,
+    BuiltinRule::OpenParen,
+    BuiltinRule::CloseParen,
+    BuiltinRule::OpenBrace,
+    BuiltinRule::CloseBrace,
+    BuiltinRule::Semicolon,
+    BuiltinRule::Slash,
+    BuiltinRule::Dot,
+    BuiltinRule::RawString,
+    BuiltinRule::StringLiteral,
+    BuiltinRule::CharLiteral,
+    BuiltinRule::Word,
+    BuiltinRule::Float,
+];
+
+impl BuiltinRule {
+    // `match_len` returns the length (in bytes) of this rule's match at the start of `input`,
+    // together with the rule that matched, or `None` if it does not match there (or is
+    // disabled by `options`). The rule is `self`, except for `Word`, which reports whether it
+    // found an `Identifier` or a `Constant`.
+    // `after_identifier` tells whether the previous token was an identifier: a `.` there is
+    // member access, so `a.5` is `a`, `Dot`, `5` rather than `a` followed by the float `.5`.
+    fn match_len(
+        self,
+        input: &str,
+        options: &LexerOptions,
+        after_identifier: bool,
+    ) -> Option<(usize, BuiltinRule)> {
+        let regex: &Regex = match self {
+            BuiltinRule::Newline if !options.emit_newlines => return None,
+            // A single line break: `\r\n` (Windows), `\n` (Unix) or a lone `\r` (old Mac).
+            BuiltinRule::Newline => return line_break_len(input).map(|len| (len, self)),
+            BuiltinRule::Comment if !options.keep_comments => return None,
+            BuiltinRule::Comment => {
+                return line_comment_len(input, &options.line_comment_prefixes)
+                    .or_else(|| MULTI_LINE_COMMENTS_RE.find(input).map(|mat| mat.end()))
+                    .map(|len| (len, self));
+            }
+            // With digit separators, `1_000` is not a word (`\b` fails after the `1`), so a
+            // constant with separators is tried when `WORD_RE` finds nothing.
+            BuiltinRule::Word if options.digit_separators => {
+                return word_match(input).or_else(|| {
+                    SEPARATED_CONSTANT_RE
+                        .find(input)
+                        .map(|mat| (mat.end(), BuiltinRule::Constant))
+                });
+            }
+            BuiltinRule::Word => return word_match(input),
+            BuiltinRule::RawString => return raw_string_len(input).map(|len| (len, self)),
+            BuiltinRule::Constant if options.digit_separators => &SEPARATED_CONSTANT_RE,
+            // Asked for on their own, `Identifier` and `Constant` only match their half of `Word`.
+            BuiltinRule::Identifier | BuiltinRule::Constant => {
+                return word_match(input).filter(|&(_, rule)| rule == self);
+            }
+            BuiltinRule::OpenParen => &OPEN_PAREN_RE,
+            BuiltinRule::CloseParen => &CLOSE_PAREN_RE,
+            BuiltinRule::OpenBrace => &OPEN_BRACE_RE,
+            BuiltinRule::CloseBrace => &CLOSE_BRACE_RE,
+            BuiltinRule::Semicolon => &SEMICOLON_RE,
+            BuiltinRule::Slash => &SLASH_RE,
+            BuiltinRule::Dot => &DOT_RE,
+            BuiltinRule::Float if after_identifier && input.starts_with('.') => return None,
+            BuiltinRule::Float => &FLOAT_RE,
+            BuiltinRule::StringLiteral => &STRING_RE,
+            BuiltinRule::CharLiteral => &CHAR_RE,
+        };
+        regex.find(input).map(|mat| (mat.end(), self))
+    }
+}
+
+// `WORD_LOCATIONS` is where `word_match` records the capture groups of `WORD_RE`. It is
+// allocated once per thread and reused for every match, since `Regex::captures` would allocate
+// a fresh set of locations each time it is called, i.e. at every token.
+thread_local! {
+    static WORD_LOCATIONS: RefCell<CaptureLocations> = RefCell::new(WORD_RE.capture_locations());
+}
+
+// `word_match` matches `WORD_RE` once at the start of `input` and dispatches on the capture
+// group that took part in the match: it returns the length of the match together with
+// `BuiltinRule::Identifier` or `BuiltinRule::Constant`, or `None` if neither matches.
+// Keyword promotion happens later, when the identifier's token is built.
+fn word_match(input: &str) -> Option<(usize, BuiltinRule)> {
+    WORD_LOCATIONS.with_borrow_mut(|locations| {
+        let mat = WORD_RE.captures_read(locations, input)?;
+        // Group 1 is `identifier` (group 0 is the whole match, group 2 is `constant`).
+        let rule = if locations.get(1).is_some() {
+            BuiltinRule::Identifier
+        } else {
+            BuiltinRule::Constant
+        };
+        Some((mat.end(), rule))
+    })
+}
+
+// `line_comment_len` returns the length (in bytes) of the single-line comment at the start
+// of `input`, or `None` if `input` doesn't start with one of `prefixes`.
+// A comment is its prefix followed by the rest of the line, up to (but not including) the
+// first `\r` or `\n`, so that every kind of line break ends it. The first matching prefix
+// wins; empty prefixes are ignored, since they would turn every line into a comment.
+fn line_comment_len(input: &str, prefixes: &[String]) -> Option<usize> {
+    let prefix = prefixes
+        .iter()
+        .find(|prefix| !prefix.is_empty() && input.starts_with(prefix.as_str()))?;
+    let rest = &input[prefix.len()..];
+    Some(prefix.len() + rest.find(['\r', '\n']).unwrap_or(rest.len()))
+}
+
+// `parse_constant` parses the text of an integer constant as an `i32`, ignoring any `_` digit
+// separators (only matched with `LexerOptions::digit_separators`). The text is only copied
+// when it has separators to remove.
+fn parse_constant(text: &str) -> Result<i32, ParseIntError> {
+    if text.contains('_') {
+        text.replace('_', "").parse()
+    } else {
+        text.parse()
+    }
+}
+
+// `integer_error_kind` returns the kind of error for the constant `text` (in base `radix`),
+// which failed to parse with `error`: an `IntegerOverflow` if the value is too large to fit in
+// an `i32`, with the range it had to fit in, and an `InvalidInteger` if the text is malformed.
+fn integer_error_kind(text: &str, radix: u32, error: &ParseIntError) -> LexerErrorKind {
+    match error.kind() {
+        IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => LexerErrorKind::IntegerOverflow {
+            value: text.to_string(),
+            radix,
+            bits: i32::BITS,
+            max: i32::MAX.into(),
+        },
+        _ => LexerErrorKind::InvalidInteger {
+            value: text.to_string(),
+            radix,
+        },
+    }
+}
+
+// `RAW_STRING_MAX_DELIMITER` is the maximum length of a raw string's delimiter, as in C++.
+pub(crate) const RAW_STRING_MAX_DELIMITER: usize = 16;
+
+// `raw_string_delimiter` returns the delimiter of the raw string literal starting at the start
+// of `input`, or `None` if `input` doesn't start one. A raw string (as in C++) is opened by
+// `R"`, an optional delimiter and `(`, e.g. `R"(` or `R"xyz(`. The delimiter is at most
+// `RAW_STRING_MAX_DELIMITER` characters, none of them a space, a parenthesis, a backslash or a
+// control character (such as a tab or a line break); anything else after `R"` means there is
+// no raw string, and the `R` is lexed as an identifier.
+// Only the first `RAW_STRING_MAX_DELIMITER + 1` bytes after `R"` are searched for the `(`, so
+// an `R"` followed by a long run of text without one is rejected without scanning it all.
+fn raw_string_delimiter(input: &str) -> Option<&str> {
+    let rest = input.strip_prefix("R\"")?;
+    let len = rest
+        .bytes()
+        .take(RAW_STRING_MAX_DELIMITER + 1)
+        .position(|byte| byte == b'(')?;
+    // `(` is ASCII, so `len` is a character boundary.
+    let delimiter = &rest[..len];
+    let valid = !delimiter
+        .chars()
+        .any(|c| matches!(c, ' ' | '(' | ')' | '\\') || c.is_control());
+    valid.then_some(delimiter)
+}
+
+// `raw_string_len` returns the length (in bytes) of the raw string literal at the start of
+// `input`, or `None` if there is none or it is never closed. The body runs verbatim (line
+// breaks and backslashes included) up to the first `)`, delimiter and `"`, so with the
+// delimiter `xyz`, `R"xyz()")xyz"` is one literal with the body `)"`.
+fn raw_string_len(input: &str) -> Option<usize> {
+    let delimiter = raw_string_delimiter(input)?;
+    let opener = "R\"(".len() + delimiter.len();
+    let closer = format!("){}\"", delimiter);
+    let body = input[opener..].find(&closer)?;
+    Some(opener + body + closer.len())
+}
+
+// `Candidate` records which rule produced a match: a built-in rule, or the user rule at
+// the given index in `LexerOptions::rules`.
+#[derive(Clone, Copy, Debug)]
+enum Candidate {
+    Builtin(BuiltinRule),
+    User(usize),
+}
+
 // --- Lexer Struct Definition ---
 // The `Lexer` struct is the main structure responsible for the tokenization process.
 // It holds the state needed to scan through the input source code.
 // - `'a`: This is a lifetime parameter. It indicates that the `Lexer` struct holds a reference
 //   (`input: &'a str`) that lives for at least as long as the lifetime `'a`. This means
 //   the `Lexer` instance cannot outlive the input string it is borrowing.
+//
+// `#[derive(Clone)]`: A clone is an independent lexer at the same point of the same input, so
+// a speculative parser can clone the lexer, try a path on the clone, and simply drop it if
+// the path fails; the original is not affected by anything the clone does, and vice versa.
+// The input itself is borrowed, not copied, but the rest of the state is: the options
+// (keyword table, user rules, ...), the warnings and the lookahead buffer. `Lexer` is not
+// `Copy`, since those own heap data. To go back and forth on a single lexer without copying
+// its options, use `checkpoint` and `restore` instead.
+#[derive(Clone)]
 pub struct Lexer<'a> {
     // `input`: A string slice (`&'a str`) representing the source code to be tokenized.
     // It's a reference to the original input string, meaning the lexer doesn't own the string data itself.
@@ -112,7 +488,67 @@ pub struct Lexer<'a> {
 
     // `position`: A `usize` representing the current byte offset (index) within the `input` string.
     // This tracks how much of the input has been processed (consumed into tokens or skipped).
+    // Invariant: `position` is always a character boundary of `input` (and at most its length).
+    // Every token and trivia pattern matches whole characters, so `advance` only ever moves it
+    // to another boundary; that is what makes slicing the input at `position` safe everywhere.
     position: usize,
+
+    // `options`: The configuration this lexer runs with (see `LexerOptions`).
+    options: LexerOptions,
+
+    // `line`: The 1-based line number that `position` currently lies on.
+    // It is updated incrementally by `advance` as input is consumed, so reporting the
+    // location of an error never requires re-scanning the input from the start.
+    line: usize,
+
+    // `line_start`: The byte offset at which the current line begins.
+    // The column of `position` is derived from the text between `line_start` and `position`.
+    line_start: usize,
+
+    // `skipped_bytes`: The total number of bytes skipped as trivia (whitespace and comments).
+    skipped_bytes: usize,
+
+    // `comment_bytes`: The number of skipped bytes that belonged to comments.
+    // This is always a part of `skipped_bytes`.
+    comment_bytes: usize,
+
+    // `warnings`: The warnings collected while tokenizing, in input order.
+    // They can be inspected with `warnings`, and are handed out (and cleared) by
+    // `tokenize_with_warnings` and `tokenize_all_with_diagnostics`.
+    warnings: Vec<LexerWarning>,
+
+    // `last_token`: The most recent token successfully produced, if any (see `last_token`).
+    // Errors leave it unchanged.
+    last_token: Option<Token>,
+
+    // `token_count`: The number of tokens produced so far, checked against
+    // `LexerOptions::max_tokens`.
+    token_count: usize,
+
+    // `limit_exceeded`: Set once a `LexerErrorKind::ResourceLimitExceeded` has been reported.
+    // From then on the lexer produces nothing more, as if it had reached the end of the input.
+    limit_exceeded: bool,
+
+    // `peeked`: The tokens lexed ahead by `peek_n` but not yet returned by `next_token`,
+    // oldest first, each with a `Checkpoint` of the lexer's state just before it was lexed.
+    // The first entry's checkpoint is therefore the state after the last token consumed,
+    // which is what `position` reports and what `unpeek` returns to. A `VecDeque` is a ring
+    // buffer, so tokens are added at the back and taken from the front cheaply.
+    peeked: VecDeque<(Result<Token, LexerError>, Checkpoint)>,
+
+    // `max_line_length`: The length, in characters, of the longest line completed so far
+    // (see `max_line_length`). Updated by `advance` whenever it steps over a line break.
+    max_line_length: usize,
+
+    // `after_identifier`: Whether the most recent token was an identifier, which makes a
+    // following `.` a `Dot` even before a digit (see `BuiltinRule::match_len`). Unlike
+    // `last_token`, it is also kept up to date by the borrowing methods.
+    after_identifier: bool,
+
+    // `shebang_len`: The length of the shebang line skipped by `skip_shebang`, or 0 if there
+    // was none. While the lexer is still right after it, `tokenize_lossless` covers it as
+    // trivia from offset 0. `seek` clears it, as the lexer then starts elsewhere.
+    shebang_len: usize,
 }
 
 // --- Lexer Implementation ---
@@ -124,10 +560,320 @@ impl<'a> Lexer<'a> {
     // - `input: &'a str`: Takes a string slice (with lifetime 'a) as the source code.
     // - `-> Self`: The return type `Self` is an alias for `Lexer<'a>` within this impl block.
     pub fn new(input: &'a str) -> Self {
+        // `new` is a shortcut for a lexer with the default configuration.
+        Lexer::with_options(input, LexerOptions::default())
+    }
+
+    // `new_named` works like `new`, but records `name` (usually the path of the file `input`
+    // was read from) as the file name reported in errors (see `LexerOptions::file_name`).
+    pub fn new_named(name: impl Into<String>, input: &'a str) -> Self {
+        let options = LexerOptions {
+            file_name: Some(name.into()),
+            ..LexerOptions::default()
+        };
+        Lexer::with_options(input, options)
+    }
+
+    // `new_at` works like `new`, but lexing starts at byte `offset` of `input` instead of at
+    // its beginning, e.g. to re-lex the tail of a file after a partial failure. The rest of
+    // the input is still known to the lexer, so spans, positions and error locations are
+    // relative to the whole `input`, exactly as in a lex from the start: the token at byte
+    // 40 is reported at byte 40, on its real line and column. Lexing from `offset` must make
+    // sense, i.e. `offset` should not be inside a token, comment or literal.
+    // `offset` must be a character boundary of `input` (its length included); otherwise no
+    // lexer is created and a `LexerErrorKind::InvalidRange` error (for the range from `offset`
+    // to the end of the input) is returned instead. `skipped_bytes` only counts what is
+    // skipped from `offset` on.
+    // To start over at offset 0 on the same input later, use `rewind`.
+    pub fn new_at(input: &'a str, offset: usize) -> Result<Self, LexerError> {
+        let mut lexer = Lexer::new(input);
+        if !input.is_char_boundary(offset) {
+            return Err(lexer.error_located(
+                LexerErrorKind::InvalidRange {
+                    range: Span::new(offset, input.len()),
+                },
+                Span::new(offset, offset),
+            ));
+        }
+        if offset > 0 {
+            lexer.seek(offset);
+            lexer.skipped_bytes = 0;
+            lexer.comment_bytes = 0;
+        }
+        Ok(lexer)
+    }
+
+    // `with_options` creates a `Lexer` with an explicit configuration.
+    // `LexerBuilder::build` is usually the more convenient way to call this.
+    pub fn with_options(input: &'a str, options: LexerOptions) -> Self {
         // Initialize and return a new `Lexer` instance.
         // - `input`: The provided input string slice is stored.
         // - `position`: The current parsing position is initialized to `0` (the beginning of the input).
-        Lexer { input, position: 0 }
+        // - `options`: The configuration is stored for use during scanning.
+        // - `line` / `line_start`: Scanning starts on line 1, which begins at offset 0.
+        let mut lexer = Lexer {
+            input,
+            position: 0,
+            options,
+            line: 1,
+            line_start: 0,
+            skipped_bytes: 0,
+            comment_bytes: 0,
+            warnings: Vec::new(),
+            last_token: None,
+            token_count: 0,
+            limit_exceeded: false,
+            peeked: VecDeque::new(),
+            max_line_length: 0,
+            after_identifier: false,
+            shebang_len: 0,
+        };
+        lexer.skip_shebang();
+        lexer
+    }
+
+    // `reset` makes the lexer start over on a new `input`, keeping its options (and rules).
+    // This lets one lexer be reused for many small snippets. All progress is cleared: the
+    // position, line tracking, skip counters and collected warnings, exactly as if the lexer
+    // had just been created for `input`.
+    pub fn reset(&mut self, input: &'a str) {
+        self.input = input;
+        self.rewind();
+    }
+
+    // `rewind` makes the lexer start over on the same input, e.g. to scan it a second time.
+    // Like `reset`, it clears all progress.
+    pub fn rewind(&mut self) {
+        self.position = 0;
+        self.line = 1;
+        self.line_start = 0;
+        self.skipped_bytes = 0;
+        self.comment_bytes = 0;
+        self.warnings.clear();
+        self.last_token = None;
+        self.token_count = 0;
+        self.limit_exceeded = false;
+        self.max_line_length = 0;
+        self.after_identifier = false;
+        self.peeked.clear();
+        self.shebang_len = 0;
+        self.skip_shebang();
+    }
+
+    // `skip_shebang` skips a leading `#!` line (e.g. `#!/usr/bin/env obv`), so that
+    // scripts can be lexed without the `#` being reported as an unexpected character.
+    // This only applies to the very first line and is done once, before any token is matched;
+    // a `#!` anywhere else in the input is lexed (and rejected) like any other text.
+    // The line break ending the shebang line is left in place and handled as whitespace.
+    fn skip_shebang(&mut self) {
+        if self.position == 0 && self.input.starts_with("#!") {
+            // Every kind of line break (`\n`, `\r\n`, `\r`) starts with one of these two bytes.
+            let len = self.input.find(['\n', '\r']).unwrap_or(self.input.len());
+            self.advance(len);
+            self.skipped_bytes += len;
+            self.shebang_len = len;
+        }
+    }
+
+    // `add_rule` registers an extra token pattern on this lexer (see `Rule`).
+    // Rules are tried in insertion order; whether they run before or after the built-in
+    // tokens is controlled by `LexerOptions::user_rules_first`.
+    pub fn add_rule(&mut self, rule: Rule) {
+        self.options.rules.push(rule);
+    }
+
+    // `skipped_bytes` returns how many bytes of the input have been skipped so far as
+    // whitespace or comments. After `tokenize_all` completes, this covers the whole input,
+    // so `input.len() - skipped_bytes()` is the number of bytes that ended up in tokens.
+    // Comments kept as tokens (see `LexerBuilder::keep_comments`) are not skipped and
+    // therefore not counted.
+    pub fn skipped_bytes(&self) -> usize {
+        self.skipped_bytes
+    }
+
+    // `comment_bytes` returns how many of the skipped bytes belonged to comments
+    // (both `// ...` and `/* ... */`, delimiters included).
+    pub fn comment_bytes(&self) -> usize {
+        self.comment_bytes
+    }
+
+    // `position` returns the byte offset the lexer has reached, i.e. where the next call will
+    // start looking for a token. Trivia is skipped lazily: right after a token is produced,
+    // the position is just past that token, and any whitespace or comments that follow are
+    // only skipped when the next token is requested. After an error the position is left at
+    // the start of the offending text (see `match_token`). Tokens that were only peeked at
+    // (see `peek_n`) are not counted: the position is just past the last token consumed.
+    pub fn position(&self) -> usize {
+        match self.peeked.front() {
+            Some((_, before)) => before.position,
+            None => self.position,
+        }
+    }
+
+    // `is_at_end` returns `true` once the whole input has been consumed. Because trivia is
+    // skipped lazily, this can still be `false` when only whitespace or comments remain;
+    // requesting another token then skips them and returns `None`, after which it is `true`.
+    pub fn is_at_end(&self) -> bool {
+        self.position() >= self.input.len()
+    }
+
+    // `is_blank` returns `true` if the rest of the input (from `position`) holds no tokens,
+    // only whitespace and comments, i.e. if `tokenize_all` would return an empty list; e.g.
+    // for `"   // hi\n/* x */"`, but not for `"int"`. This is handy to skip empty source files.
+    // With `keep_comments` or `emit_newlines`, comments or line breaks are tokens, so input
+    // containing them is not blank. Something that cannot be lexed (like `$` or an
+    // unterminated `/*`) is not blank either.
+    // The lexer looks ahead by skipping the trivia, but then puts everything back as it was
+    // (position, line tracking, skip counters and warnings), so it can be used as before.
+    pub fn is_blank(&mut self) -> bool {
+        // Tokens (or errors) that were peeked at are still ahead, so the rest is not blank.
+        if !self.peeked.is_empty() {
+            return false;
+        }
+        let checkpoint = self.checkpoint();
+        self.skip_whitespaces_and_comments();
+        let blank = self.position >= self.input.len();
+        self.restore(&checkpoint);
+        blank
+    }
+
+    // `checkpoint` saves the lexer's current state, so that `restore` can return to it later,
+    // e.g. for a parser that tries one production and backtracks if it fails (see
+    // `Checkpoint`). Taking a checkpoint does not change the lexer.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            position: self.position,
+            line: self.line,
+            line_start: self.line_start,
+            skipped_bytes: self.skipped_bytes,
+            comment_bytes: self.comment_bytes,
+            warning_count: self.warnings.len(),
+            last_token: self.last_token.clone(),
+            token_count: self.token_count,
+            limit_exceeded: self.limit_exceeded,
+            peeked: self.peeked.clone(),
+            max_line_length: self.max_line_length,
+            after_identifier: self.after_identifier,
+            shebang_len: self.shebang_len,
+        }
+    }
+
+    // `restore` puts the lexer back into the state saved by `checkpoint`, so it produces
+    // exactly the same tokens (and errors) from there as it did the first time. Warnings
+    // collected since the checkpoint are dropped, as they will be collected again.
+    // The checkpoint must come from this lexer, and from before any `reset` (which changes
+    // the input the saved offsets refer to); the options and rules are not part of it.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) {
+        debug_assert!(self.input.is_char_boundary(checkpoint.position));
+        self.position = checkpoint.position;
+        self.line = checkpoint.line;
+        self.line_start = checkpoint.line_start;
+        self.skipped_bytes = checkpoint.skipped_bytes;
+        self.comment_bytes = checkpoint.comment_bytes;
+        self.warnings.truncate(checkpoint.warning_count);
+        self.last_token = checkpoint.last_token.clone();
+        self.token_count = checkpoint.token_count;
+        self.limit_exceeded = checkpoint.limit_exceeded;
+        self.peeked = checkpoint.peeked.clone();
+        self.max_line_length = checkpoint.max_line_length;
+        self.after_identifier = checkpoint.after_identifier;
+        self.shebang_len = checkpoint.shebang_len;
+    }
+
+    // `max_line_length` returns the length, in characters (Unicode scalar values, so a tab or
+    // an `é` counts as one), of the longest line scanned so far, line break excluded. It is
+    // tracked while scanning, at no extra pass over the input, e.g. for a style linter's
+    // line-length check. Once the whole input has been lexed (e.g. after `tokenize_all`
+    // succeeds), it covers every line, including a last line without a trailing newline.
+    // Before that it is approximate: it only covers the input up to the current position.
+    pub fn max_line_length(&self) -> usize {
+        let current = self.input[self.line_start..self.position].chars().count();
+        self.max_line_length.max(current)
+    }
+
+    // `remaining` returns the part of the input not consumed yet (starting at `position`),
+    // including any trivia that hasn't been skipped yet. It borrows from the input, not from
+    // the lexer, e.g. to hand the rest of a line to another parser after an error.
+    pub fn remaining(&self) -> &'a str {
+        &self.input[self.position()..]
+    }
+
+    // `advance` moves `self.position` forward by `len` bytes.
+    // Every change to the position goes through this method so that the line tracking
+    // (`line` and `line_start`) always stays in sync with the text that was consumed.
+    // Line breaks are recognized by `ends_line_at`, so `\n`, `\r\n` and a lone `\r` each
+    // count as exactly one line, even when a `\r\n` pair is consumed in two separate steps.
+    // The length of every line completed this way is checked against `max_line_length`.
+    fn advance(&mut self, len: usize) {
+        debug_assert!(
+            self.input.is_char_boundary(self.position + len),
+            "advanced to offset {} inside a character or past the input",
+            self.position + len
+        );
+        for index in self.position..self.position + len {
+            if ends_line_at(self.input, index) {
+                let line = self.input[self.line_start..=index].trim_end_matches(['\r', '\n']);
+                self.max_line_length = self.max_line_length.max(line.chars().count());
+                self.line += 1;
+                self.line_start = index + 1;
+            }
+        }
+        self.position += len;
+    }
+
+    // `advance_over` works like `advance`, but first checks the text being stepped over for
+    // suspicious content that is only visible from the inside. Currently that is a block
+    // comment containing another `/*` (see `LexerWarning::NestedCommentOpener`): the lexer steps
+    // to each inner `/*` in turn, so that the warning gets its location like any other.
+    fn advance_over(&mut self, len: usize) {
+        let input = self.input;
+        let text = &input[self.position..self.position + len];
+        if text.starts_with("/*") && text.len() >= 4 {
+            let end = self.position + len;
+            let body = &text[2..text.len() - 2];
+            let body_start = self.position + 2;
+            for (offset, _) in body.match_indices("/*") {
+                self.advance(body_start + offset - self.position);
+                let (line, column) = self.current_location();
+                self.warnings.push(LexerWarning::NestedCommentOpener {
+                    pos: self.position,
+                    end: self.position + 2,
+                    line,
+                    column,
+                    file: self.options.file_name.clone(),
+                });
+            }
+            self.advance(end - self.position);
+        } else {
+            self.advance(len);
+        }
+    }
+
+    // `seek` moves `self.position` to `offset`, which may be before or after the current
+    // position, and recomputes the line tracking for the new position from the start of the
+    // input. `offset` must be a character boundary within the input. The token before
+    // `offset` is not known, so `last_token` and `after_identifier` are cleared, and tokens
+    // peeked at from the old position are discarded.
+    fn seek(&mut self, offset: usize) {
+        debug_assert!(self.input.is_char_boundary(offset));
+        self.position = 0;
+        self.line = 1;
+        self.line_start = 0;
+        self.last_token = None;
+        self.after_identifier = false;
+        self.peeked.clear();
+        self.shebang_len = 0;
+        self.advance(offset);
+    }
+
+    // `current_location` returns the `(line, column)` of `self.position`, as reported in
+    // errors: 1-based unless `LexerOptions::numbering` says otherwise.
+    // Columns count characters (Unicode scalar values), not bytes, so a multi-byte UTF-8
+    // character such as `é` occupies a single column.
+    fn current_location(&self) -> (usize, usize) {
+        let line_text = &self.input[self.line_start..self.position];
+        let column = visual_width(line_text, self.options.tab_width) + 1;
+        self.options.numbering.apply((self.line, column))
     }
 
     // `skip_whitespace_and_comments` is a helper method responsible for advancing
@@ -161,10 +907,18 @@ impl<'a> Lexer<'a> {
             // `WHITESPACE_RE.find(current_slice)` attempts to find a match for the whitespace regex
             // at the beginning of `current_slice`.
             // `if let Some(mat) = ...`: If a match is found (`mat` will be a `regex::Match` object).
-            if let Some(mat) = WHITESPACE_RE.find(current_slice) {
+            // When newlines are emitted as tokens, only the whitespace *between* line breaks
+            // may be skipped, so a narrower regex is used.
+            let whitespace_re: &Regex = if self.options.emit_newlines {
+                &INLINE_WHITESPACE_RE
+            } else {
+                &WHITESPACE_RE
+            };
+            if let Some(mat) = whitespace_re.find(current_slice) {
                 // `mat.end()`: Returns the length (in bytes) of the matched whitespace.
                 // Advance `self.position` by this length to move past the skipped whitespace.
-                self.position += mat.end();
+                self.advance(mat.end());
+                self.skipped_bytes += mat.end();
                 // Set the flag indicating that something was skipped.
                 skipped_something = true;
                 // `continue`: Skip the rest of the current loop iteration and start the next one.
@@ -172,11 +926,20 @@ impl<'a> Lexer<'a> {
                 continue;
             }
 
+            // When comments are kept, they are tokens rather than trivia, so stop here and
+            // let `next_token_internal` emit them.
+            if self.options.keep_comments {
+                break;
+            }
+
             // --- Try to match and skip SINGLE-LINE COMMENTS ---
             // If whitespace wasn't found, try matching a single-line comment.
-            if let Some(mat) = SINGLE_LINE_COMMENTS_RE.find(current_slice) {
+            if let Some(len) = line_comment_len(current_slice, &self.options.line_comment_prefixes)
+            {
                 // Advance `self.position` past the entire matched single-line comment.
-                self.position += mat.end();
+                self.advance(len);
+                self.skipped_bytes += len;
+                self.comment_bytes += len;
                 // Continue to the next loop iteration to check for more skippables.
                 skipped_something = true;
                 continue;
@@ -188,10 +951,12 @@ impl<'a> Lexer<'a> {
                 // NOTE: (on MULTI_LINE_COMMENT_RE) `(?s)/\*.*?\*/`
                 // The `(?s)` flag allows `.` to match newlines. `.*?` is non-greedy.
                 // This regex handles simple, non-nested block comments.
-                // If an unterminated comment `/* ... EOF` occurs, this regex (because of `.*?`)
-                // might consume until the end of the file if `*/` is never found.
-                // TODO: Add a check for unterminated multiline comment
-                self.position += mat.end();
+                // An unterminated comment `/* ... EOF` doesn't match at all; it is left in
+                // place and reported as `LexerErrorKind::UnterminatedComment` by `match_token`.
+                // `advance_over` warns about any `/*` inside the comment.
+                self.advance_over(mat.end());
+                self.skipped_bytes += mat.end();
+                self.comment_bytes += mat.end();
                 skipped_something = true;
                 continue;
             }
@@ -214,160 +979,744 @@ impl<'a> Lexer<'a> {
     //   - `Result<Token, LexerError>`: If `Some`, this `Result` indicates success or failure:
     //     - `Ok(Token)`: A token was successfully recognized.
     //     - `Err(LexerError)`: An error occurred during tokenization.
-    fn next_token_internal(&mut self) -> Option<Result<Token, LexerError>> {
+    pub(crate) fn next_token_internal(&mut self) -> Option<Result<Token, LexerError>> {
+        // The span is not needed here, so only the token is kept.
+        self.next_spanned_token_internal()
+            .map(|result| result.map(|(token, _span)| token))
+    }
+
+    // `next_token` returns the next token, one at a time, for callers (like a hand-written
+    // parser) that pull tokens on demand rather than iterating. It returns `None` at the end
+    // of the input, and keeps returning `None` if called again.
+    // What happens after it returns an error is part of its contract:
+    // - By default, the lexer stays at the start of the offending text (after any whitespace
+    //   and comments before it), so calling `next_token` again returns the same error again,
+    //   forever. The caller decides what to do, e.g. stop, or `reset` the lexer.
+    // - With `LexerOptions::resume_after_error`, the lexer instead steps past the offending
+    //   text before returning the error (as `tokenize_all_recovering` does, following
+    //   `LexerOptions::recovery`), so the next call continues with the rest of the input.
+    // Either way, a `LexerErrorKind::ResourceLimitExceeded` is final: every later call returns
+    // `None`.
+    // Tokens looked at with `peek` or `peek_n` are returned first, in order.
+    pub fn next_token(&mut self) -> Option<Result<Token, LexerError>> {
+        if let Some((result, _)) = self.peeked.pop_front() {
+            if let Ok(token) = &result {
+                self.last_token = Some(token.clone());
+            }
+            return Some(result);
+        }
+        self.next_unpeeked_token()
+    }
+
+    // `peek` returns the token the next call to `next_token` will return, without consuming
+    // it, or `None` at the end of the input. It is the same as `peek_n(0)`.
+    pub fn peek(&mut self) -> Option<&Result<Token, LexerError>> {
+        self.peek_n(0)
+    }
+
+    // `peek_n` returns the token `n` places ahead without consuming anything: `peek_n(0)` is
+    // the token the next call to `next_token` returns, `peek_n(1)` the one after it, and so
+    // on (like `Iterator::nth`). It returns `None` if the input ends before that token.
+    // The tokens up to the requested one are lexed ahead into a buffer, which `next_token`
+    // drains before lexing any further, so every token is only lexed once however often it is
+    // peeked at. Errors are buffered like tokens, following `next_token`'s contract: by
+    // default, everything after an error is that same error again.
+    // Peeking does not count as consuming: `position`, `remaining`, `is_at_end` and
+    // `last_token` keep describing the tokens returned by `next_token`. The other methods
+    // that lex (`tokenize_all`, `tokens`, `tokenize_all_spanned`, ...) start with the peeked
+    // tokens too: they put the lexer back to where it was before them (see `unpeek`) and
+    // lex them again. `reset` and `rewind` discard them.
+    pub fn peek_n(&mut self, n: usize) -> Option<&Result<Token, LexerError>> {
+        if self.peeked.len() <= n {
+            // The buffer is taken out while lexing ahead, so the checkpoint saved before each
+            // token does not copy it.
+            let mut peeked = std::mem::take(&mut self.peeked);
+            let last_token = self.last_token.clone();
+            while peeked.len() <= n {
+                let before = self.checkpoint();
+                match self.next_unpeeked_token() {
+                    Some(result) => peeked.push_back((result, before)),
+                    None => break,
+                }
+            }
+            self.last_token = last_token;
+            self.peeked = peeked;
+        }
+        self.peeked.get(n).map(|(result, _)| result)
+    }
+
+    // `unpeek` discards the tokens lexed ahead by `peek_n` and puts the lexer back into the
+    // state it had before lexing them, i.e. just after the last token `next_token` returned.
+    // The skip counters, warnings and resource limit bookkeeping are rolled back with it, so
+    // lexing the tokens again counts them once. Methods that lex on their own call this first.
+    pub(crate) fn unpeek(&mut self) {
+        if let Some((_, before)) = self.peeked.pop_front() {
+            self.restore(&before);
+        }
+    }
+
+    // `next_unpeeked_token` lexes the token `next_token` returns when nothing is buffered,
+    // stepping past an error afterwards if `LexerOptions::resume_after_error` asks for it.
+    fn next_unpeeked_token(&mut self) -> Option<Result<Token, LexerError>> {
+        let result = self.next_token_internal();
+        if self.options.resume_after_error
+            && let Some(Err(error)) = &result
+        {
+            self.recover_from(error);
+        }
+        result
+    }
+
+    // `next_spanned_token_internal` works like `next_token_internal`, but each token is
+    // paired with the `Span` of source text it was recognized from.
+    // Every token produced here is remembered as the `last_token`.
+    fn next_spanned_token_internal(&mut self) -> Option<Result<(Token, Span), LexerError>> {
+        let result = self
+            .next_spanned_token_ref()
+            .map(|result| result.map(|(token, span)| (token.into_owned(), span)));
+        if let Some(Ok((token, _))) = &result {
+            self.last_token = Some(token.clone());
+        }
+        result
+    }
+
+    // `last_token` returns the most recent token the lexer produced, or `None` if it has not
+    // produced any yet. Errors do not change it, so a parser reporting an error can say what
+    // came before it, e.g. "unexpected `$` after `main`". Tokens produced by
+    // `tokenize_all_borrowed` and `tokenize_lossless`, which never build owned `Token`s, are
+    // not recorded.
+    pub fn last_token(&self) -> Option<&Token> {
+        self.last_token.as_ref()
+    }
+
+    // `next_spanned_token_ref` is where tokens are actually produced. It yields `TokenRef`s,
+    // which borrow their text from the input; the owned `Token` API converts them afterwards,
+    // while `tokenize_all_borrowed` uses them as they are.
+    // Every step is checked against the resource limits (see `check_limits`).
+    fn next_spanned_token_ref(&mut self) -> Option<Result<(TokenRef<'a>, Span), LexerError>> {
+        if self.limit_exceeded {
+            return None;
+        }
+        let result = self.next_unlimited_token_ref();
+        if let Some(Ok((token, _))) = &result {
+            self.after_identifier = matches!(
+                token,
+                TokenRef::Identifier(_) | TokenRef::Other(Token::Identifier(_))
+            );
+        }
+        self.check_limits(result)
+    }
+
+    // `next_unlimited_token_ref` produces the next token like `next_spanned_token_ref`, but
+    // without enforcing the resource limits.
+    fn next_unlimited_token_ref(&mut self) -> Option<Result<(TokenRef<'a>, Span), LexerError>> {
+        // In lenient mode, unexpected characters (including disallowed control characters) are
+        // stepped over and scanning resumes right after them, so keep scanning until a token,
+        // another error, or the end of input.
+        // With `error_tokens`, they are turned into a `TokenRef::Error` instead.
+        loop {
+            let result = self.scan_token();
+            // The text reported by an `UnexpectedCharacter` or `DisallowedControlCharacter`
+            // error: usually one character, or the three of a trigraph (see
+            // `LexerOptions::translate_trigraphs`).
+            let unexpected = match &result {
+                Some(Err(error)) => match error.kind() {
+                    LexerErrorKind::UnexpectedCharacter { .. }
+                    | LexerErrorKind::DisallowedControlCharacter { .. } => Some(error.span()),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match unexpected {
+                Some(span) if self.options.error_tokens => {
+                    let pos = span.start;
+                    self.advance(span.end - self.position);
+                    // Extend the error token over the following characters that can't start
+                    // a token either, so that e.g. `@@@` is one error token, not three.
+                    // A translated trigraph is stepped over as a whole, so `@??(` is not split
+                    // into `@??` and a `(`.
+                    while let Some(next) = self.input[self.position..].chars().next()
+                        && self.cannot_start_anything(&self.input[self.position..])
+                    {
+                        let rest = &self.input[self.position..];
+                        if self.options.translate_trigraphs && trigraph_at(rest).is_some() {
+                            self.advance(TRIGRAPH_LEN);
+                        } else {
+                            self.advance(next.len_utf8());
+                        }
+                    }
+                    let input = self.input;
+                    let text = &input[pos..self.position];
+                    return Some(Ok((TokenRef::Error(text), Span::new(pos, self.position))));
+                }
+                Some(span) if self.options.lenient => {
+                    self.advance(span.end - self.position);
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    // `check_limits` passes on the `result` of a scanning step, unless the step took the lexer
+    // over `LexerOptions::max_input_bytes` (it read past that offset, whatever it found there)
+    // or produced a token over `LexerOptions::max_tokens`. Then the result is replaced by a
+    // `LexerErrorKind::ResourceLimitExceeded`, and the lexer stops.
+    fn check_limits(
+        &mut self,
+        result: Option<Result<(TokenRef<'a>, Span), LexerError>>,
+    ) -> Option<Result<(TokenRef<'a>, Span), LexerError>> {
+        if let Some(max) = self.options.max_input_bytes
+            && self.position > max
+        {
+            // The limit may fall inside a multi-byte character; the error starts at that character.
+            let mut pos = max;
+            while !self.input.is_char_boundary(pos) {
+                pos -= 1;
+            }
+            let end = self.position;
+            return Some(Err(self.resource_limit_error(
+                ResourceKind::InputBytes,
+                max,
+                Span::new(pos, end),
+            )));
+        }
+        if let Some(Ok((_, span))) = &result {
+            if self.options.max_tokens == Some(self.token_count) {
+                let span = *span;
+                return Some(Err(self.resource_limit_error(
+                    ResourceKind::Tokens,
+                    self.token_count,
+                    span,
+                )));
+            }
+            self.token_count += 1;
+        }
+        result
+    }
+
+    // `resource_limit_error` creates the `LexerErrorKind::ResourceLimitExceeded` for exceeding the
+    // `limit` on `kind` with the text at `span`, and stops the lexer (see `limit_exceeded`).
+    fn resource_limit_error(&mut self, kind: ResourceKind, limit: usize, span: Span) -> LexerError {
+        self.limit_exceeded = true;
+        self.error_located(LexerErrorKind::ResourceLimitExceeded { kind, limit }, span)
+    }
+
+    // `error_located` works like `error_at`, but finds the line and column of `span` itself,
+    // for errors reported away from the lexer's position (where `current_location` does not
+    // apply). An offset past the end of the input is located at the end.
+    fn error_located(&self, kind: LexerErrorKind, span: Span) -> LexerError {
+        let (line, column) =
+            LineIndex::with_tab_width(self.input, self.options.tab_width).visual_col(span.start);
+        let location = self
+            .options
+            .numbering
+            .apply((line as usize, column as usize));
+        self.error_at(kind, span, location)
+    }
+
+    // `error_at` creates a `LexerError` of the given `kind` for the text at `span`, whose start
+    // is at `location` (a `(line, column)` pair, numbered as configured). The error gets the
+    // lexer's file name and the source line `span` starts on as its context.
+    fn error_at(&self, kind: LexerErrorKind, span: Span, location: (usize, usize)) -> LexerError {
+        LexerError::new(
+            kind,
+            span,
+            location,
+            self.options.file_name.clone(),
+            ErrorContext::new(self.input, span.start),
+        )
+    }
+
+    // `cannot_start_anything` returns `true` if nothing the lexer knows starts at the beginning
+    // of `rest`: no whitespace, no comment, no token, and no (malformed) number. This is the
+    // condition under which `match_token` reports an `UnexpectedCharacter`. A non-breaking
+    // space is not skipped as whitespace (see `WHITESPACE_RE`), so it is such a character.
+    fn cannot_start_anything(&self, rest: &str) -> bool {
+        !rest.starts_with(|c: char| c.is_whitespace() && c != '\u{A0}')
+            && !rest.starts_with(['"', '\''])
+            && line_comment_len(rest, &self.options.line_comment_prefixes).is_none()
+            && !MULTI_LINE_COMMENTS_RE.is_match(rest)
+            && !NUMBER_WITH_SUFFIX_RE.is_match(rest)
+            && !self.starts_trigraph_token(rest)
+            && self.longest_match(rest).is_none()
+    }
+
+    // `starts_trigraph_token` returns `true` if `rest` starts with a trigraph that is lexed as
+    // a token (`??<` or `??>`) and trigraphs are translated.
+    fn starts_trigraph_token(&self, rest: &str) -> bool {
+        self.options.translate_trigraphs && matches!(trigraph_at(rest), Some('{' | '}'))
+    }
+
+    // `scan_token` performs a single scanning step: skip trivia, then recognize one token
+    // (or report why none could be recognized) at the current position.
+    fn scan_token(&mut self) -> Option<Result<(TokenRef<'a>, Span), LexerError>> {
         // --- Phase 1: Skip leading whitespace and comments ---
-        // This loop ensures that `self.position` is advanced past any skippable
+        // This ensures that `self.position` is advanced past any skippable
         // characters before attempting to recognize an actual token.
-        loop {
-            // Call the helper method to skip whitespace and comments.
-            // The boolean result of `skip_whitespace_and_comments` is ignored here (`let _ = ...`)
-            // as we only care that the position is updated.
-            let _ = self.skip_whitespaces_and_comments();
-
-            // After attempting to skip, check if we've reached the end of the input.
-            if self.position >= self.input.len() {
-                // If `self.position` is at or beyond the input length, it means all remaining
-                // characters were skippable, or the input was empty to begin with.
-                // Return `None` to signal the end of token stream.
-                return None;
-            }
-            // If control reaches here which means that `self.position` points to a
-            // potential start of a token, so the control breaks out of the skipping loop.
-            break;
+        // The boolean result of `skip_whitespace_and_comments` is ignored here (`let _ = ...`)
+        // as we only care that the position is updated.
+        let _ = self.skip_whitespaces_and_comments();
+
+        // After attempting to skip, check if we've reached the end of the input.
+        if self.position >= self.input.len() {
+            // If `self.position` is at or beyond the input length, it means all remaining
+            // characters were skippable, or the input was empty to begin with.
+            // Return `None` to signal the end of token stream.
+            return None;
         }
+        // If control reaches here, `self.position` points to a potential start of a token.
 
-        // --- Phase 2: Attempt to match known token patterns ---
+        // The span of a token starts here, *after* the skipped trivia, and ends wherever
+        // `match_token` leaves the position, so it covers exactly the token's own text.
+        let start = self.position;
+        Some(
+            self.match_token()
+                .map(|token| (token, Span::new(start, self.position))),
+        )
+    }
+
+    // `match_token` recognizes a single token starting exactly at `self.position`, which
+    // must not be at the end of the input. On success, the position is advanced past the token;
+    // on error, it is left where it was, at the start of the offending text.
+    fn match_token(&mut self) -> Result<TokenRef<'a>, LexerError> {
         // `current_slice`: Get the part of the input string from the current `self.position`.
         // All regex matches will be attempted against the beginning of this slice.
-        let current_slice = &self.input[self.position..];
+        // Copying `self.input` out first makes the slice borrow from the input (`'a`) rather
+        // than from `self`, so tokens can keep pointing into it.
+        let input = self.input;
+        let current_slice = &input[self.position..];
+        // `scan_token`, the only caller, returns `None` at the end of the input instead.
+        debug_assert!(
+            !current_slice.is_empty(),
+            "match_token called at the end of the input"
+        );
 
         // `start_pos_of_token`: Store the current position. This is useful for error reporting,
         // as it indicates where the problematic (or successful) token began.
         let start_position_of_the_token = self.position;
 
-        // The order of these `if let Some(mat) = ...` blocks can be important,
-        // especially if some token patterns could ambiguously match the same prefix.
-        // Typically, longer or more specific matches (like keywords, which are handled
-        // after general identifiers here) or frequently occurring simple tokens
-        // might be checked first. For this set of tokens, the order is relatively robust.
-
-        // --- 2.1: Match Punctuation Tokens ---
-        // These are usually single-character tokens with fixed representations.
-        if let Some(mat) = OPEN_PAREN_RE.find(current_slice) {
-            self.position += mat.end(); // Advance position by the length of the matched token.
-            return Some(Ok(Token::OpenParen)); // Return the recognized token.
-        }
-
-        if let Some(mat) = CLOSE_PAREN_RE.find(current_slice) {
-            self.position += mat.end();
-            return Some(Ok(Token::CloseParen));
-        }
+        // `line` / `column`: The human-readable location of the token start, reported in errors.
+        let (line, column) = self.current_location();
 
-        if let Some(mat) = OPEN_BRACE_RE.find(current_slice) {
-            self.position += mat.end();
-            return Some(Ok(Token::OpenBrace));
+        // A `/*` that was not skipped (or, with `keep_comments`, matched) as a comment has no
+        // closing `*/`. Report it here, pointing at the opener; otherwise the `/` would be
+        // lexed as a `Slash` followed by a baffling "Unexpected character '*'".
+        if current_slice.starts_with("/*") && !MULTI_LINE_COMMENTS_RE.is_match(current_slice) {
+            return Err(self.error_at(
+                LexerErrorKind::UnterminatedComment,
+                Span::new(start_position_of_the_token, input.len()),
+                (line, column),
+            ));
         }
 
-        if let Some(mat) = CLOSE_BRACE_RE.find(current_slice) {
-            self.position += mat.end();
-            return Some(Ok(Token::CloseBrace));
+        // Likewise, a raw string whose closing `)delimiter"` never comes is reported from its
+        // `R"`, rather than lexing the `R` as an identifier followed by an unterminated string.
+        // It may span lines, so the error covers the rest of the input.
+        if raw_string_delimiter(current_slice).is_some() && raw_string_len(current_slice).is_none()
+        {
+            return Err(self.error_at(
+                LexerErrorKind::UnterminatedString,
+                Span::new(start_position_of_the_token, input.len()),
+                (line, column),
+            ));
         }
 
-        if let Some(mat) = SEMICOLON_RE.find(current_slice) {
-            self.position += mat.end();
-            return Some(Ok(Token::Semicolon));
+        // A `#` that is the first non-whitespace character on its line starts a preprocessor
+        // directive, which runs to the end of the line (including continuation lines). It takes
+        // precedence over every rule; a `#` anywhere else is not special.
+        if current_slice.starts_with('#')
+            && input[self.line_start..self.position]
+                .chars()
+                .all(char::is_whitespace)
+            && let Some(mat) = DIRECTIVE_RE.find(current_slice)
+        {
+            let text = &current_slice[..mat.end()];
+            self.advance(text.len());
+            return Ok(TokenRef::Directive(text));
         }
 
-        // --- 2.2: Match Identifiers (which could also be Keywords) ---
-        // Treating keywords like other identifiers.
-        // First, finding the end of the token. Then, if it looks like an identifier,
-        // check whether it matches any keywords."
-        if let Some(mat) = IDENTIFIER_RE.find(current_slice) {
-            let val = mat.as_str(); // Get the matched string slice (e.g., "main", "myVar").
-            self.position += mat.end(); // Advance position.
-
-            // Now, check if this identifier is one of the predefined keywords.
-            // Iterate over the `KEYWORDS` array (defined earlier).
-            for (keyword_str, token_variant) in KEYWORDS.iter() {
-                // Compare the matched identifier string (`val`) with the keyword string (`keyword_str`).
-                // `*keyword_str` dereferences `&str` to `str` for comparison with `val` (which is `str`).
-                if *keyword_str == val {
-                    // If it's a keyword, return the corresponding keyword `Token` variant.
-                    // `token_variant.clone()` is used because `token_variant` is a reference
-                    // from the `KEYWORDS` array, and we need an owned `Token` value.
-                    // (As `Token` derives `Clone`).
-                    return Some(Ok(token_variant.clone()));
+        // With `translate_trigraphs`, a trigraph stands for the character it translates to:
+        // `??<` and `??>` are braces, `??=` starts a directive like `#` does (its text is kept
+        // as written), and the others stand for characters that are no token here, reported
+        // as that character (e.g. `??(` as an unexpected `[`) with a span covering all three.
+        if self.options.translate_trigraphs
+            && let Some(translated) = trigraph_at(current_slice)
+        {
+            let starts_line = input[self.line_start..self.position]
+                .chars()
+                .all(char::is_whitespace);
+            let token = match translated {
+                '{' => Token::OpenBrace,
+                '}' => Token::CloseBrace,
+                '#' if starts_line => {
+                    let len = TRIGRAPH_DIRECTIVE_RE
+                        .find(current_slice)
+                        .map_or(TRIGRAPH_LEN, |mat| mat.end());
+                    self.advance(len);
+                    return Ok(TokenRef::Directive(&current_slice[..len]));
                 }
-            }
-            // If the matched string is not found in the `KEYWORDS` array,
-            // then it's a regular user-defined identifier.
-            // `val.to_string()` converts the `&str` slice into an owned `String`
-            // to be stored in the `Token::Identifier` variant.
-            return Some(Ok(Token::Identifier(val.to_string())));
+                _ => {
+                    return Err(self.error_at(
+                        LexerErrorKind::UnexpectedCharacter { char: translated },
+                        Span::new(
+                            start_position_of_the_token,
+                            start_position_of_the_token + TRIGRAPH_LEN,
+                        ),
+                        (line, column),
+                    ));
+                }
+            };
+            self.advance(TRIGRAPH_LEN);
+            return Ok(TokenRef::Other(token));
         }
 
-        // --- 2.3: Match Integer Constants ---
-        if let Some(mat) = CONSTANT_RE.find(current_slice) {
-            let val_str = mat.as_str(); // Get the matched string of digits (e.g., "123").
-            self.position += mat.end(); // Advance position.
-
-            // Attempt to parse the matched string of digits into an `i32` integer.
-            // `value_str.parse::<i32>()` returns a `Result<i32, ParseIntError>`.
-            match val_str.parse::<i32>() {
-                // If parsing is successful (`Ok(val)`), return a `Token::Constant`.
-                Ok(val) => return Some(Ok(Token::Constant(val))),
-                // If parsing fails (e.g., the number is too large to fit in an `i32`),
-                // it's an error.
-                Err(_) => {
-                    // Return an `InvalidInteger` lexer error.
-                    // Store the original string value and its starting position.
-                    return Some(Err(LexerError::InvalidInteger {
-                        value: val_str.to_string(),
-                        pos: start_position_of_the_token,
-                    }));
+        // --- Phase 2: Attempt to match known token patterns ---
+        // Every enabled rule (built-in or user-registered) is tried at the current position and
+        // the one with the *longest* match wins. This makes the result independent of the order
+        // in which rules are checked: e.g. with user rules for both `<` and `<<`, the input `<<`
+        // is always one `<<` token rather than two `<` tokens.
+        // When several rules match the same number of bytes, the tie is broken by priority
+        // (see `longest_match`), which is how keywords-as-identifiers and user overrides work.
+        if let Some((len, candidate)) = self.longest_match(current_slice) {
+            let text = &current_slice[..len];
+            let result = match candidate {
+                // A user rule builds its token from the matched text with its `make` function.
+                Candidate::User(index) => {
+                    Ok(TokenRef::Other((self.options.rules[index].make)(text)))
                 }
+                Candidate::Builtin(rule) => {
+                    self.make_builtin_token(rule, text, start_position_of_the_token, line, column)
+                }
+            };
+            // Advance position by the length of the matched token, warning about a `/*` inside
+            // a kept block comment (see `advance_over`). Text that was matched but rejected (an
+            // out-of-range integer, an over-long identifier, ...) is not stepped over.
+            if result.is_ok() {
+                self.advance_over(len);
             }
+            return result;
         }
 
         // --- Phase 3: Handle Unrecognized Input (Error Reporting) ---
         // If execution reaches this point, it means that after skipping whitespace/comments,
         // the `current_slice` did not match any of the defined token regexes (punctuation,
-        // identifier, constant).
+        // identifier, constant) or any user rule.
 
-        // The `IDENTIFIER_RE` and `CONSTANT_RE` use `\b` (word boundary).
-        // - "123bar": `CONSTANT_RE` won't match "123" because `b` is not a boundary.
-        //             `IDENTIFIER_RE` won't match because it starts with '1'.
+        // The `WORD_RE` uses `\b` (word boundary).
+        // - "123bar": Its constant branch won't match "123" because `b` is not a boundary.
+        //             Its identifier branch won't match because it starts with '1'.
         //             So, "123bar" will fall through to this error handling section.
         //             `current_slice` will be "123bar...".
         // - "$": This character doesn't start any known token. `current_slice` will be "$...".
 
-        // Check if `current_slice` is not empty. (It shouldn't be if we passed the EOF check earlier).
-        if !current_slice.is_empty() {
-            // Try to get the first character of the problematic slice.
-            // `chars().next()` correctly handles multi-byte UTF-8 characters.
-            if let Some(first_char) = current_slice.chars().next() {
-                // An unexpected character was found.
-                // Return an `UnexpectedCharacter` error, providing the character and its position.
-                // NOTE: We are NOT advancing `self.position` here. If `tokenize_all` stops on
-                // the first error (which it does), the lexer stops at the exact error point.
-                // If error recovery was implemented, we might advance `self.position` here
-                // by `first_char.len_utf8()` to try and continue lexing.
+        // A quote that didn't start a string or character literal has no closing quote on its
+        // line. The error points at the opening quote and covers the rest of the line, which
+        // is where the literal was meant to be; lexing does not continue onto the next line.
+        if let Some(quote) = current_slice
+            .chars()
+            .next()
+            .filter(|c| matches!(c, '"' | '\''))
+        {
+            let end = start_position_of_the_token
+                + current_slice
+                    .find(['\r', '\n'])
+                    .unwrap_or(current_slice.len());
+            let kind = if quote == '"' {
+                LexerErrorKind::UnterminatedString
+            } else {
+                LexerErrorKind::UnterminatedChar
+            };
+            return Err(self.error_at(
+                kind,
+                Span::new(start_position_of_the_token, end),
+                (line, column),
+            ));
+        }
+
+        // Digits immediately followed by identifier characters (like "123bar" or "0x") are
+        // reported as one malformed numeric literal covering the whole run.
+        if let Some(mat) = NUMBER_WITH_SUFFIX_RE.find(current_slice) {
+            return Err(self.error_at(
+                LexerErrorKind::InvalidNumericLiteral {
+                    text: mat.as_str().to_string(),
+                },
+                Span::new(
+                    start_position_of_the_token,
+                    start_position_of_the_token + mat.end(),
+                ),
+                (line, column),
+            ));
+        }
+
+        // Try to get the first character of the problematic slice.
+        // `chars().next()` correctly handles multi-byte UTF-8 characters.
+        // It is never `None`: the slice is not empty (asserted above), and a non-empty `&str`
+        // always starts with a character. Anything the rules above reject is therefore
+        // reported as one of the two character errors below, and the `NoMatch` fallback is
+        // unreachable. It is kept as a defensive answer that does not panic.
+        let Some(first_char) = current_slice.chars().next() else {
+            return Err(self.error_at(
+                LexerErrorKind::NoMatch,
+                Span::new(start_position_of_the_token, start_position_of_the_token),
+                (line, column),
+            ));
+        };
 
-                // self.position += first_char.len_utf8();
-                return Some(Err(LexerError::UnexpectedCharacter {
-                    char: first_char,
-                    pos: start_position_of_the_token,
-                }));
+        // Control characters (other than whitespace like `\t` and `\n`, which was
+        // skipped) are never valid, and printing them raw can mangle a terminal, so
+        // they get an error of their own that shows them as a code point.
+        let span = Span::new(
+            start_position_of_the_token,
+            start_position_of_the_token + first_char.len_utf8(),
+        );
+        if first_char.is_control() {
+            return Err(self.error_at(
+                LexerErrorKind::DisallowedControlCharacter { char: first_char },
+                span,
+                (line, column),
+            ));
+        }
+        // An unexpected character was found.
+        // Return an `UnexpectedCharacter` error, providing the character and its position.
+        // NOTE: We are NOT advancing `self.position` here. If `tokenize_all` stops on
+        // the first error (which it does), the lexer stops at the exact error point.
+        // In lenient mode, `next_unlimited_token_ref` advances past the character
+        // by `first_char.len_utf8()` and continues lexing instead.
+        Err(self.error_at(
+            LexerErrorKind::UnexpectedCharacter { char: first_char },
+            span,
+            (line, column),
+        ))
+    }
+
+    // `longest_match` tries every enabled rule against the start of `current_slice` and returns
+    // the length of the longest match together with the rule that produced it.
+    // Rules are considered in priority order and a later rule only replaces the current best
+    // match if it is strictly longer, so ties go to the rule with the higher priority:
+    // - Built-in rules are ranked in the order of `BUILTIN_RULES`.
+    // - User rules are ranked in insertion order, either all above the built-in rules
+    //   (`user_rules_first`) or all below them (the default).
+    fn longest_match(&self, current_slice: &str) -> Option<(usize, Candidate)> {
+        let builtin_matches = BUILTIN_RULES.iter().filter_map(|&rule| {
+            rule.match_len(current_slice, &self.options, self.after_identifier)
+                .map(|(len, rule)| (len, Candidate::Builtin(rule)))
+        });
+        let user_matches = self
+            .options
+            .rules
+            .iter()
+            .enumerate()
+            .filter_map(|(index, rule)| {
+                rule.match_len(current_slice)
+                    .map(|len| (len, Candidate::User(index)))
+            });
+
+        let mut best: Option<(usize, Candidate)> = None;
+        let mut consider = |(len, candidate): (usize, Candidate)| {
+            if best.is_none_or(|(best_len, _)| len > best_len) {
+                best = Some((len, candidate));
             }
+        };
+        if self.options.user_rules_first {
+            user_matches.for_each(&mut consider);
+            builtin_matches.for_each(&mut consider);
+        } else {
+            builtin_matches.for_each(&mut consider);
+            user_matches.for_each(&mut consider);
         }
+        best
+    }
+
+    // `make_builtin_token` turns the text matched by a built-in rule into its `Token`.
+    // `start_position_of_the_token`, `line` and `column` locate the text for error reporting.
+    fn make_builtin_token(
+        &mut self,
+        rule: BuiltinRule,
+        text: &'a str,
+        start_position_of_the_token: usize,
+        line: usize,
+        column: usize,
+    ) -> Result<TokenRef<'a>, LexerError> {
+        match rule {
+            BuiltinRule::Newline => Ok(TokenRef::Other(Token::Newline)),
+            BuiltinRule::Comment => Ok(TokenRef::Comment(text)),
+            BuiltinRule::OpenParen => Ok(TokenRef::Other(Token::OpenParen)),
+            BuiltinRule::CloseParen => Ok(TokenRef::Other(Token::CloseParen)),
+            BuiltinRule::OpenBrace => Ok(TokenRef::Other(Token::OpenBrace)),
+            BuiltinRule::CloseBrace => Ok(TokenRef::Other(Token::CloseBrace)),
+            BuiltinRule::Semicolon => Ok(TokenRef::Other(Token::Semicolon)),
+            BuiltinRule::Slash => Ok(TokenRef::Other(Token::Slash)),
+            BuiltinRule::Dot => Ok(TokenRef::Other(Token::Dot)),
+            // `Word` never gets here, since `match_len` reports the `Identifier` or `Constant`
+            // it found instead. It is handled anyway, by classifying the text again, as a
+            // defensive answer that does not panic.
+            BuiltinRule::Word => match word_match(text) {
+                Some((_, rule)) => {
+                    self.make_builtin_token(rule, text, start_position_of_the_token, line, column)
+                }
+                None => Err(self.error_at(
+                    LexerErrorKind::NoMatch,
+                    Span::new(start_position_of_the_token, start_position_of_the_token),
+                    (line, column),
+                )),
+            },
+            BuiltinRule::StringLiteral => match self.options.max_string_len {
+                Some(max) if text.len() > max => Err(self.error_at(
+                    LexerErrorKind::StringTooLong {
+                        len: text.len(),
+                        max,
+                    },
+                    Span::new(
+                        start_position_of_the_token,
+                        start_position_of_the_token + text.len(),
+                    ),
+                    (line, column),
+                )),
+                _ => {
+                    // The prefix, if any, is everything before the opening quote.
+                    let quote = text.find('"').unwrap_or(0);
+                    Ok(TokenRef::StringLiteral {
+                        value: &text[quote..],
+                        prefix: (quote > 0).then(|| &text[..quote]),
+                    })
+                }
+            },
+            BuiltinRule::CharLiteral => Ok(TokenRef::CharLiteral(text)),
+            // A raw string is subject to the same length limit as other string literals. Its
+            // token holds the body only, exactly as written (nothing in it is an escape).
+            BuiltinRule::RawString => match self.options.max_string_len {
+                Some(max) if text.len() > max => Err(self.error_at(
+                    LexerErrorKind::StringTooLong {
+                        len: text.len(),
+                        max,
+                    },
+                    Span::new(
+                        start_position_of_the_token,
+                        start_position_of_the_token + text.len(),
+                    ),
+                    (line, column),
+                )),
+                _ => {
+                    let delimiter = raw_string_delimiter(text).unwrap_or("");
+                    let opener = "R\"(".len() + delimiter.len();
+                    let closer = ")\"".len() + delimiter.len();
+                    Ok(TokenRef::RawString(&text[opener..text.len() - closer]))
+                }
+            },
+
+            // --- Identifiers (which could also be Keywords) ---
+            // Treating keywords like other identifiers: first the whole word is matched, then
+            // it is checked against the keyword table.
+            BuiltinRule::Identifier => {
+                // Iterate over the keyword table from the lexer's options (`KEYWORDS` by default).
+                for (keyword_str, token_variant) in self.options.keywords.iter() {
+                    if keyword_str == text {
+                        // If it's a keyword, return the corresponding keyword `Token` variant.
+                        // `token_variant.clone()` is used because `token_variant` is a reference
+                        // from the keyword table, and we need an owned `Token` value.
+                        return Ok(TokenRef::Other(token_variant.clone()));
+                    }
+                }
+                // It is not a keyword. If it is over the length limit, it is rejected before
+                // anything else is done with it (such as copying it into a `String`).
+                if let Some(max) = self.options.max_identifier_len
+                    && text.len() > max
+                {
+                    return Err(self.error_at(
+                        LexerErrorKind::IdentifierTooLong {
+                            len: text.len(),
+                            max,
+                        },
+                        Span::new(
+                            start_position_of_the_token,
+                            start_position_of_the_token + text.len(),
+                        ),
+                        (line, column),
+                    ));
+                }
+                // If the matched string is not found in the keyword table,
+                // then it's a regular user-defined identifier.
+                // If enabled, warn when it is a near miss of a keyword (e.g. `retrun`).
+                if self.options.warn_keyword_typos
+                    && let Some((keyword, _)) = self
+                        .options
+                        .keywords
+                        .iter()
+                        .find(|(keyword, _)| is_one_edit_apart(text, keyword))
+                {
+                    self.warnings.push(LexerWarning::PossibleKeywordTypo {
+                        found: text.to_string(),
+                        suggestion: keyword.clone(),
+                        pos: start_position_of_the_token,
+                        end: start_position_of_the_token + text.len(),
+                        line,
+                        column,
+                        file: self.options.file_name.clone(),
+                    });
+                }
+                // The identifier borrows its name from the input; `TokenRef::into_owned`
+                // copies it into a `String` for the owned `Token::Identifier`.
+                Ok(TokenRef::Identifier(text))
+            }
 
-        // If `current_slice` was somehow empty here (which is unlikely given prior checks but
-        // acts as a defensive fallback) or `chars().next()` returned `None` on a non-empty
-        // slice (even more unlikely for valid UTF-8), then report a general `NoMatch` error.
-        // This signifies that the lexer is "stuck" but cannot pinpoint a specific character.
-        Some(Err(LexerError::NoMatch {
-            pos: start_position_of_the_token,
-        }))
+            // --- Integer Constants ---
+            // Attempt to parse the matched string of digits into an `i32` integer.
+            // `parse_constant` returns a `Result<i32, ParseIntError>`.
+            BuiltinRule::Constant => match parse_constant(text) {
+                // If parsing is successful, return a `Token::Constant`.
+                // The matched text is kept alongside the value so that the original
+                // spelling (e.g., leading zeros in `007`) is not lost.
+                // A leading zero (`007`) is allowed, but warned about (see `LeadingZero`).
+                Ok(value) => {
+                    if text.len() > 1 && text.starts_with('0') {
+                        self.warnings.push(LexerWarning::LeadingZero {
+                            constant: text.to_string(),
+                            pos: start_position_of_the_token,
+                            end: start_position_of_the_token + text.len(),
+                            line,
+                            column,
+                            file: self.options.file_name.clone(),
+                        });
+                    }
+                    Ok(TokenRef::Constant { value, raw: text })
+                }
+                // If parsing fails, the number is either too large to fit in an `i32` or
+                // malformed (see `integer_error_kind`). Either way, the error has the original
+                // text and its location, and the `ParseIntError` is kept as its `source`.
+                Err(e) => {
+                    let kind = integer_error_kind(text, 10, &e);
+                    let span = Span::new(
+                        start_position_of_the_token,
+                        start_position_of_the_token + text.len(),
+                    );
+                    Err(self.error_at(kind, span, (line, column)).with_source(e))
+                }
+            },
+
+            // --- Floating-Point Constants ---
+            // The number is kept as written. Identifier characters run together with it (as
+            // in `1.5f`) are reported as an invalid suffix.
+            BuiltinRule::Float => {
+                let number_len = text
+                    .find(|c: char| !c.is_ascii_digit() && c != '.')
+                    .unwrap_or(text.len());
+                if number_len == text.len() {
+                    return Ok(TokenRef::Float(text));
+                }
+                Err(self.error_at(
+                    LexerErrorKind::InvalidNumberSuffix {
+                        number: text[..number_len].to_string(),
+                        suffix: text[number_len..].to_string(),
+                        suffix_start: start_position_of_the_token + number_len,
+                    },
+                    Span::new(
+                        start_position_of_the_token,
+                        start_position_of_the_token + text.len(),
+                    ),
+                    (line, column),
+                ))
+            }
+        }
     }
 
     // `tokenize_all` is the primary public method for using the lexer.
@@ -379,7 +1728,11 @@ impl<'a> Lexer<'a> {
     //     containing all the tokens in order.
     //   - `Err(LexerError)`: If any lexing error occurs, it stops immediately and returns
     //     the first error encountered.
+    // No input makes the lexer panic: anything it cannot lex (stray bytes, unterminated
+    // comments and literals, over-long tokens) becomes a `LexerError`.
     pub fn tokenize_all(&mut self) -> Result<Vec<Token>, LexerError> {
+        // Tokens that were peeked at are lexed again, in order (see `unpeek`).
+        self.unpeek();
         // `tokens`: Create an empty, mutable vector to store the recognized tokens.
         // `Vec::new()` is one way to create an empty vector.
         let mut tokens = Vec::new();
@@ -407,7 +1760,1791 @@ impl<'a> Lexer<'a> {
         }
         // If the loop completes without returning an `Err`, it means the entire input
         // was processed successfully (or was empty).
-        // Return the vector of collected tokens wrapped in `Ok`.
+        // Return the vector of collected tokens wrapped in `Ok`, after handing it to the
+        // post-processor, if one is configured (see `LexerOptions::post_process`).
+        match self.options.post_process {
+            Some(post_process) => Ok(post_process(tokens)),
+            None => Ok(tokens),
+        }
+    }
+
+    // `tokens` returns an iterator that produces the tokens one at a time (see `Tokens`).
+    // It is the streaming counterpart of `tokenize_all`: no `Vec` of tokens is built, so
+    // memory use does not grow with the size of the input.
+    pub fn tokens(&mut self) -> Tokens<'_, 'a> {
+        self.unpeek();
+        Tokens::new(self)
+    }
+
+    // `tokenize_range` tokenizes only the bytes from `start` to `end` (exclusive) of the input,
+    // e.g. to re-lex just the region of a file that was edited.
+    // - Both offsets must lie within the input, on character boundaries, with `start <= end`;
+    //   otherwise a `LexerErrorKind::InvalidRange` is returned and the lexer is left untouched.
+    // - `start` should be the start of a token (or of trivia before one): lexing simply begins
+    //   there, so starting in the middle of an identifier produces the rest of it as a token.
+    // - A token that starts before `end` but continues past it is included in full; lexing
+    //   stops at the first token that would *start* at or after `end`.
+    // Afterwards the lexer's position is just after the last token produced (or after the
+    // trivia that reached `end`), so lexing can be continued from there.
+    pub fn tokenize_range(&mut self, start: usize, end: usize) -> Result<Vec<Token>, LexerError> {
+        // The first offset that is out of bounds or splits a character is the one reported.
+        let invalid = [start, end]
+            .into_iter()
+            .find(|&offset| !self.input.is_char_boundary(offset))
+            .or((start > end).then_some(start));
+        if let Some(pos) = invalid {
+            return Err(self.error_located(
+                LexerErrorKind::InvalidRange {
+                    range: Span::new(start, end),
+                },
+                Span::new(pos, pos),
+            ));
+        }
+
+        self.seek(start);
+        let mut tokens = Vec::new();
+        loop {
+            // Skip trivia first, so that whitespace or comments reaching `end` don't cause
+            // the token after them (which starts past `end`) to be lexed.
+            self.skip_whitespaces_and_comments();
+            if self.position >= end {
+                break;
+            }
+            match self.next_token_internal() {
+                Some(result) => tokens.push(result?),
+                None => break,
+            }
+        }
         Ok(tokens)
     }
+
+    // `tokenize_with_warnings` works like `tokenize_all`, but also returns the warnings
+    // (see `LexerWarning`) collected along the way. Warnings never stop lexing; errors still
+    // do, in which case the warnings gathered so far are discarded along with the tokens.
+    // The returned warnings are removed from the lexer, so each warning is returned only once.
+    pub fn tokenize_with_warnings(
+        &mut self,
+    ) -> Result<(Vec<Token>, Vec<LexerWarning>), LexerError> {
+        let tokens = self.tokenize_all()?;
+        Ok((tokens, std::mem::take(&mut self.warnings)))
+    }
+
+    // `warnings` returns the warnings collected so far and not yet handed out by
+    // `tokenize_with_warnings` or `tokenize_all_with_diagnostics`, in input order.
+    pub fn warnings(&self) -> &[LexerWarning] {
+        &self.warnings
+    }
+
+    // `tokenize_all_with_diagnostics` works like `tokenize_all`, but never fails: it returns
+    // the tokens together with every `Diagnostic`, i.e. the collected warnings and, if lexing
+    // stopped early, the error that stopped it. The diagnostics are sorted by position; a
+    // successful lex has only warnings (if any). Like `tokenize_with_warnings`, it removes the
+    // returned warnings from the lexer.
+    pub fn tokenize_all_with_diagnostics(&mut self) -> (Vec<Token>, Vec<Diagnostic>) {
+        self.unpeek();
+        let mut tokens = Vec::new();
+        let mut error = None;
+        while let Some(result) = self.next_token_internal() {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+        let mut diagnostics: Vec<Diagnostic> = std::mem::take(&mut self.warnings)
+            .into_iter()
+            .map(Diagnostic::Warning)
+            .chain(error.map(Diagnostic::Error))
+            .collect();
+        // A stable sort keeps an error after any warnings at the same position.
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span().start);
+        (tokens, diagnostics)
+    }
+
+    // `tokenize_all_borrowed` works like `tokenize_all`, but returns zero-copy `TokenRef`s
+    // whose identifier, constant and comment text borrows from the input instead of being
+    // copied into a `String` per token. The tokens may outlive the lexer, but not the input.
+    pub fn tokenize_all_borrowed(&mut self) -> Result<Vec<TokenRef<'a>>, LexerError> {
+        self.unpeek();
+        std::iter::from_fn(|| self.next_spanned_token_ref())
+            .map(|result| result.map(|(token, _span)| token))
+            .collect()
+    }
+
+    // `tokenize_all_recovering` tokenizes the whole input without stopping at errors, so all
+    // problems in a file can be reported at once. Every error is recorded, lexing resumes
+    // right after the offending text (e.g. after an unexpected character, by its UTF-8
+    // length), and the tokens around the errors are still returned. With
+    // `RecoveryPolicy::SyncToStatement` (see `LexerOptions::recovery`), lexing instead resumes
+    // at the next `;`, `}` or line break, which avoids a cascade of errors in garbled input.
+    // The returned errors are in input order. `tokenize_all` keeps stopping at the first one.
+    pub fn tokenize_all_recovering(&mut self) -> (Vec<Token>, Vec<LexerError>) {
+        self.unpeek();
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        while let Some(result) = self.next_token_internal() {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(error) => {
+                    self.recover_from(&error);
+                    errors.push(error);
+                }
+            }
+        }
+        (tokens, errors)
+    }
+
+    // `recover_from` moves the position past `error`, to where lexing resumes according to
+    // `LexerOptions::recovery`.
+    fn recover_from(&mut self, error: &LexerError) {
+        self.skip_error(error);
+        if self.options.recovery == RecoveryPolicy::SyncToStatement {
+            self.sync_to_statement();
+        }
+    }
+
+    // `sync_to_statement` moves the position to the next `;`, `}` or line break (or to the end
+    // of the input if there is none), without lexing the text in between. The character found
+    // is not skipped, so it is lexed as usual when lexing resumes.
+    fn sync_to_statement(&mut self) {
+        let rest = &self.input[self.position..];
+        let len = rest.find([';', '}', '\n', '\r']).unwrap_or(rest.len());
+        self.advance(len);
+    }
+
+    // `skip_error` moves the position past the text `error` was reported for, so that lexing
+    // can continue after it. Errors that cover no text (`NoMatch`) skip one character, so
+    // recovery always makes progress.
+    fn skip_error(&mut self, error: &LexerError) {
+        let span = error.span();
+        if self.position < span.end {
+            self.advance(span.end - self.position);
+        } else if self.position == span.start
+            && let Some(ch) = self.input[self.position..].chars().next()
+        {
+            self.advance(ch.len_utf8());
+        }
+    }
+
+    // `tokenize_interned` works like `tokenize_all`, but every identifier is interned: it is
+    // returned as a `Symbol` into the `StringInterner` returned alongside the tokens. A file
+    // that uses the same identifier thousands of times then stores its name only once.
+    pub fn tokenize_interned(
+        &mut self,
+    ) -> Result<(Vec<InternedToken>, StringInterner), LexerError> {
+        self.unpeek();
+        let mut interner = StringInterner::new();
+        let tokens = std::iter::from_fn(|| self.next_spanned_token_ref())
+            .map(|result| {
+                result.map(|(token, _span)| InternedToken::from_ref(token, &mut interner))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok((tokens, interner))
+    }
+
+    // `tokenize_lossless` tokenizes the rest of the input without losing any of it: the tokens
+    // are returned together with the trivia between them (see `SourcePiece`), including
+    // trivia before the first and after the last token. Concatenating
+    // `&input[piece.span()]` over all pieces reproduces the input exactly (from the position
+    // the lexer was at), which is what formatters and other source-to-source tools rely on.
+    // A leading shebang line, skipped when the lexer was created, is the first trivia piece.
+    pub fn tokenize_lossless(&mut self) -> Result<Vec<SourcePiece>, LexerError> {
+        self.unpeek();
+        // `cursor` is where the text not yet covered by a piece starts. Right after the
+        // shebang line was skipped, that line is not covered yet, so it starts at 0.
+        let mut cursor = if self.shebang_len > 0 && self.position == self.shebang_len {
+            0
+        } else {
+            self.position
+        };
+        let mut pieces = Vec::new();
+        while let Some(result) = self.next_spanned_token_internal() {
+            let (token, span) = result?;
+            // Everything between the previous piece and this token is trivia.
+            if span.start > cursor {
+                pieces.push(SourcePiece::Trivia {
+                    span: Span::new(cursor, span.start),
+                });
+            }
+            pieces.push(SourcePiece::Token { token, span });
+            cursor = span.end;
+        }
+        // The lexer has skipped any trivia after the last token before reporting the end.
+        if self.position > cursor {
+            pieces.push(SourcePiece::Trivia {
+                span: Span::new(cursor, self.position),
+            });
+        }
+        Ok(pieces)
+    }
+
+    // `tokenize_all_spanned` works like `tokenize_all`, but pairs every token with the
+    // `Span` (byte range) of the source text it was produced from.
+    // Spans cover exactly the token's text: skipped whitespace and comments are never
+    // included, so `&input[span.start..span.end]` is the token's lexeme.
+    pub fn tokenize_all_spanned(&mut self) -> Result<Vec<(Token, Span)>, LexerError> {
+        self.unpeek();
+        // `collect` into a `Result<Vec<_>, _>` stops at the first `Err`, just like `tokenize_all`.
+        std::iter::from_fn(|| self.next_spanned_token_internal()).collect()
+    }
+
+    // `token_at` returns the token whose span contains the byte `offset`, together with that
+    // span, e.g. to find the token under the cursor in an editor. It returns `Ok(None)` if the
+    // offset is not inside any token: in whitespace, in a skipped comment, or at or past the
+    // end of the input. Spans are half-open, so the offset just after a token's last byte
+    // (e.g. a cursor right after `main`) belongs to whatever follows it, not to the token.
+    // Lexing stops as soon as the offset is reached, so an error later in the input does not
+    // matter; only an error at or before it is returned as `Err`.
+    // Like `tokenize_all`, it lexes (and consumes) the tokens from the current position.
+    pub fn token_at(&mut self, offset: usize) -> Result<Option<(Token, Span)>, LexerError> {
+        self.unpeek();
+        while let Some(result) = self.next_spanned_token_internal() {
+            let (token, span) = match result {
+                Ok(spanned) => spanned,
+                // The offset was in the whitespace or comments before the error.
+                Err(error) if error.span().start > offset => return Ok(None),
+                Err(error) => return Err(error),
+            };
+            if span.end > offset {
+                return Ok((span.start <= offset).then_some((token, span)));
+            }
+        }
+        Ok(None)
+    }
+
+    // `tokens_by_line` works like `tokenize_all`, but returns the tokens split by source line:
+    // one inner vector per line of the input, in order, holding the tokens that start on that
+    // line (e.g. for a line-oriented diff of token streams). Unlike `group_by_line`, every
+    // line is present: empty lines, and lines with only whitespace or skipped comments, get
+    // an empty vector. A line break at the very end of the input starts a final, empty line
+    // (see `LineIndex::line_count`). A token spanning several lines (e.g. a kept block
+    // comment) belongs to the line it starts on.
+    pub fn tokens_by_line(&mut self) -> Result<Vec<Vec<Token>>, LexerError> {
+        let index = LineIndex::new(self.input);
+        let mut lines = vec![Vec::new(); index.line_count()];
+        for group in group_by_line(self.tokenize_all_spanned()?, &index) {
+            lines[group.line - 1] = group.tokens;
+        }
+        Ok(lines)
+    }
+
+    // `slice` returns the source text covered by `span`, i.e. the exact lexeme of a token
+    // produced by this lexer (including keywords and punctuation, whose tokens don't store
+    // their text). Every span the lexer produces covers exactly one token's text, so
+    // concatenating the slices of all tokens gives back the input minus the skipped
+    // whitespace and comments.
+    // The returned `&'a str` borrows from the input, not from the lexer, so it stays valid
+    // while the lexer keeps being used.
+    // Panics, like string indexing, if `span` is out of bounds or splits a UTF-8 character;
+    // spans produced by this lexer never do.
+    pub fn slice(&self, span: Span) -> &'a str {
+        &self.input[span.start..span.end]
+    }
 } // End of `impl<'a> Lexer<'a>` block
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{LexerBuilder, Numbering, RecoveryPolicy};
+
+    // `lex` tokenizes `input` with the default options, expecting it to succeed.
+    fn lex(input: &str) -> Vec<Token> {
+        Lexer::new(input).tokenize_all().unwrap()
+    }
+
+    // `lex_error` tokenizes `input` with the default options, expecting it to fail.
+    fn lex_error(input: &str) -> LexerError {
+        Lexer::new(input).tokenize_all().unwrap_err()
+    }
+
+    // A constant keeps its spelling next to its value, so `007` and `7` stay apart.
+    #[test]
+    fn constants_keep_their_raw_text() {
+        assert_eq!(
+            lex("007 7"),
+            vec![
+                Token::Constant {
+                    value: 7,
+                    raw: "007".to_string()
+                },
+                Token::Constant {
+                    value: 7,
+                    raw: "7".to_string()
+                },
+            ]
+        );
+    }
+
+    // Errors report the 1-based line and column of the offending character.
+    #[test]
+    fn errors_report_line_and_column() {
+        let error = lex_error(
+            "int x;
+  $",
+        );
+        assert_eq!((error.line(), error.column()), (2, 3));
+        assert_eq!(error.pos(), 9);
+    }
+
+    // Columns count characters, not bytes, so a multi-byte `é` is one column.
+    #[test]
+    fn error_columns_count_characters() {
+        let error = lex_error("é $");
+        assert_eq!((error.line(), error.column()), (1, 1));
+        let error = lex_error("x /* é */ $");
+        assert_eq!((error.line(), error.column()), (1, 11));
+    }
+
+    // Whitespace and comments are counted as skipped, comments also on their own.
+    #[test]
+    fn skipped_and_comment_bytes_are_counted() {
+        let source = "int  /* c */ x; // d\n";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize_all().unwrap();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(lexer.comment_bytes(), "/* c */".len() + "// d".len());
+        assert_eq!(lexer.skipped_bytes(), source.len() - "intx;".len());
+    }
+
+    // Comments kept as tokens are not skipped.
+    #[test]
+    fn kept_comments_are_not_skipped() {
+        let mut lexer = LexerBuilder::new().keep_comments(true).build("x /* c */");
+        lexer.tokenize_all().unwrap();
+        assert_eq!(lexer.skipped_bytes(), 1);
+        assert_eq!(lexer.comment_bytes(), 0);
+    }
+
+    // Spans cover exactly each token's text, never the trivia around it.
+    #[test]
+    fn spans_cover_token_text_only() {
+        let source = " int main ( ) /* c */ { return 0 ; }";
+        let spanned = Lexer::new(source).tokenize_all_spanned().unwrap();
+        let lexemes: Vec<&str> = spanned
+            .iter()
+            .map(|(_, span)| &source[span.start..span.end])
+            .collect();
+        assert_eq!(
+            lexemes,
+            ["int", "main", "(", ")", "{", "return", "0", ";", "}"]
+        );
+        assert_eq!(
+            spanned[1],
+            (Token::Identifier("main".to_string()), Span::new(5, 9))
+        );
+    }
+
+    // `custom` builds the `Token::Custom` for a user rule's matched text.
+    fn custom(text: &str) -> Token {
+        Token::Custom(text.to_string())
+    }
+
+    // The longest match wins, whatever order the rules were registered in.
+    #[test]
+    fn longest_match_wins() {
+        let mut lexer = Lexer::new("<< <");
+        lexer.add_rule(Rule::new(Regex::new(r"\A<").unwrap(), custom));
+        lexer.add_rule(Rule::new(Regex::new(r"\A<<").unwrap(), custom));
+        assert_eq!(
+            lexer.tokenize_all().unwrap(),
+            vec![custom("<<"), custom("<")]
+        );
+
+        // A longer user match beats a built-in identifier, even when tried after it.
+        let mut lexer = Lexer::new("foo-bar");
+        lexer.add_rule(Rule::new(Regex::new(r"\A[a-z]+-[a-z]+").unwrap(), custom));
+        assert_eq!(lexer.tokenize_all().unwrap(), vec![custom("foo-bar")]);
+    }
+
+    // Matches of equal length go to the rule with the higher priority.
+    #[test]
+    fn ties_go_to_the_higher_priority() {
+        let rule = || Rule::new(Regex::new(r"\Aint").unwrap(), custom);
+        let tokens = LexerBuilder::new().rule(rule()).build("int").tokenize_all();
+        assert_eq!(tokens.unwrap(), vec![Token::KwInt]);
+        let tokens = LexerBuilder::new()
+            .rule(rule())
+            .user_rules_first(true)
+            .build("int")
+            .tokenize_all();
+        assert_eq!(tokens.unwrap(), vec![custom("int")]);
+    }
+
+    // The lexer counts lines the same way for LF, CRLF and lone CR line endings.
+    #[test]
+    fn error_lines_for_each_line_ending() {
+        for (source, line) in [
+            ("a\nb\n$", 3),
+            ("a\r\nb\r\n$", 3),
+            ("a\rb\r$", 3),
+            ("a\r\n\r\r\n$", 4),
+        ] {
+            let error = lex_error(source);
+            assert_eq!((error.line(), error.column()), (line, 1), "{:?}", source);
+        }
+    }
+
+    // Emitted newline tokens treat a CRLF pair as one line break.
+    #[test]
+    fn newline_tokens_for_each_line_ending() {
+        let spanned = LexerBuilder::new()
+            .emit_newlines(true)
+            .build("a\r\n\n\r")
+            .tokenize_all_spanned()
+            .unwrap();
+        let spans: Vec<Span> = spanned.iter().map(|(_, span)| *span).collect();
+        assert_eq!(
+            spans,
+            [
+                Span::new(0, 1),
+                Span::new(1, 3),
+                Span::new(3, 4),
+                Span::new(4, 5)
+            ]
+        );
+    }
+
+    // A line comment ends at any line break, so CRLF and CR files lex like LF ones.
+    #[test]
+    fn line_comments_end_at_every_line_break() {
+        for source in ["x // c\ny $", "x // c\r\ny $", "x // c\ry $"] {
+            let mut lexer = LexerBuilder::new().keep_comments(true).build(source);
+            let tokens: Vec<_> = lexer.tokens().collect();
+            assert_eq!(
+                tokens[1],
+                Ok(Token::Comment("// c".to_string())),
+                "{:?}",
+                source
+            );
+            assert_eq!(
+                tokens[2],
+                Ok(Token::Identifier("y".to_string())),
+                "{:?}",
+                source
+            );
+            let error = tokens[3].clone().unwrap_err();
+            assert_eq!((error.line(), error.column()), (2, 3), "{:?}", source);
+        }
+    }
+
+    // A leading shebang line is skipped, and lines are still counted from the top.
+    #[test]
+    fn shebang_is_skipped() {
+        let source = "#!/usr/bin/env obv\r\nint $";
+        let mut lexer = Lexer::new(source);
+        assert_eq!(lexer.next_token(), Some(Ok(Token::KwInt)));
+        let error = lexer.next_token().unwrap().unwrap_err();
+        assert_eq!((error.line(), error.column()), (2, 5));
+        assert_eq!(lexer.skipped_bytes(), "#!/usr/bin/env obv\r\n".len() + 1);
+    }
+
+    // Only the very first line can be a shebang.
+    #[test]
+    fn shebang_only_at_the_start() {
+        let error = lex_error("x #!/bin/sh");
+        assert_eq!(
+            error.kind(),
+            &LexerErrorKind::UnexpectedCharacter { char: '#' }
+        );
+        assert!(lex("#!").is_empty());
+    }
+
+    // Single-line comments start with any configured prefix; a lone `/` is a `Slash`.
+    #[test]
+    fn configurable_line_comment_prefixes() {
+        let source = "a / b # c\n// d";
+        let tokens = LexerBuilder::new()
+            .line_comment_prefixes(vec!["#".to_string()])
+            .build(source)
+            .tokenize_all()
+            .unwrap();
+        let identifier = |name: &str| Token::Identifier(name.to_string());
+        assert_eq!(
+            tokens,
+            vec![
+                identifier("a"),
+                Token::Slash,
+                identifier("b"),
+                Token::Slash,
+                Token::Slash,
+                identifier("d"),
+            ]
+        );
+        assert_eq!(
+            lex("a / b // c"),
+            vec![identifier("a"), Token::Slash, identifier("b")]
+        );
+    }
+
+    // `slice` gives back each token's exact lexeme, keywords and punctuation included.
+    #[test]
+    fn slice_returns_the_lexeme() {
+        let source = "int  x = 007; // c";
+        let mut lexer = LexerBuilder::new().lenient(true).build(source);
+        let spanned = lexer.tokenize_all_spanned().unwrap();
+        let lexemes: String = spanned.iter().map(|(_, span)| lexer.slice(*span)).collect();
+        assert_eq!(lexemes, "intx007;");
+        assert_eq!(lexer.slice(spanned[2].1), "007");
+    }
+
+    // The file name given to the lexer is carried into its errors.
+    #[test]
+    fn errors_carry_the_file_name() {
+        let error = Lexer::new_named("main.c", "int $")
+            .tokenize_all()
+            .unwrap_err();
+        assert_eq!(error.file(), Some("main.c"));
+        assert_eq!(
+            error.to_string(),
+            "[E0001] Unexpected character '$' in main.c at line 1, column 5 (bytes 4..5)"
+        );
+        assert_eq!(lex_error("$").file(), None);
+    }
+
+    // Only the tokens starting inside the range are lexed, the last one in full.
+    #[test]
+    fn tokenize_range_lexes_part_of_the_input() {
+        let source = "int main() { return 0; }";
+        let mut lexer = Lexer::new(source);
+        assert_eq!(
+            lexer.tokenize_range(3, 10).unwrap(),
+            vec![
+                Token::Identifier("main".to_string()),
+                Token::OpenParen,
+                Token::CloseParen,
+            ]
+        );
+        // The whitespace reaching the end of the range has been skipped too.
+        assert_eq!(lexer.position(), 11);
+        // `return` starts before the end of the range, so it is included whole.
+        assert_eq!(
+            lexer.tokenize_range(11, 14).unwrap(),
+            vec![Token::OpenBrace, Token::KwReturn]
+        );
+    }
+
+    // Invalid ranges are rejected without moving the lexer.
+    #[test]
+    fn tokenize_range_rejects_invalid_ranges() {
+        let mut lexer = Lexer::new("é x");
+        for (start, end, pos) in [(1, 3, 1), (3, 2, 3), (0, 9, 9)] {
+            let error = lexer.tokenize_range(start, end).unwrap_err();
+            assert_eq!(
+                error.kind(),
+                &LexerErrorKind::InvalidRange {
+                    range: Span::new(start, end)
+                }
+            );
+            assert_eq!(error.pos(), pos);
+        }
+        assert_eq!(lexer.position(), 0);
+    }
+
+    // `position`, `remaining` and `is_at_end` follow the tokens as they are produced; trailing
+    // trivia is only skipped when the next token is requested.
+    #[test]
+    fn progress_accessors() {
+        let mut lexer = Lexer::new("int x ");
+        assert_eq!((lexer.position(), lexer.remaining()), (0, "int x "));
+        lexer.next_token();
+        assert_eq!((lexer.position(), lexer.remaining()), (3, " x "));
+        lexer.next_token();
+        assert_eq!(lexer.remaining(), " ");
+        assert!(!lexer.is_at_end());
+        assert_eq!(lexer.next_token(), None);
+        assert!(lexer.is_at_end());
+        assert_eq!(lexer.remaining(), "");
+    }
+
+    // Errors follow the configured numbering.
+    #[test]
+    fn errors_use_the_configured_numbering() {
+        let error = LexerBuilder::new()
+            .numbering(Numbering::ZERO_BASED)
+            .build("int\n  $")
+            .tokenize_all()
+            .unwrap_err();
+        assert_eq!(error.location(), (6, 1, 2));
+    }
+
+    // Digits run together with identifier characters are one malformed number, not a
+    // constant followed by an identifier.
+    #[test]
+    fn digits_with_a_suffix_are_an_error() {
+        let error = lex_error("x 123abc;");
+        assert_eq!(
+            error.kind(),
+            &LexerErrorKind::InvalidNumericLiteral {
+                text: "123abc".to_string()
+            }
+        );
+        assert_eq!(error.span(), Span::new(2, 8));
+        assert_eq!(
+            lex("123 abc1"),
+            vec![
+                Token::Constant {
+                    value: 123,
+                    raw: "123".to_string()
+                },
+                Token::Identifier("abc1".to_string()),
+            ]
+        );
+    }
+
+    // Hexadecimal is not supported: `0x1F` is one invalid numeric literal.
+    #[test]
+    fn hex_constants_are_an_invalid_numeric_literal() {
+        let error = lex_error("0x1F");
+        assert_eq!(
+            error.kind(),
+            &LexerErrorKind::InvalidNumericLiteral {
+                text: "0x1F".to_string()
+            }
+        );
+        assert_eq!(error.span(), Span::new(0, 4));
+    }
+
+    // With digit separators, `1_000` is a constant whose raw text keeps the underscore; an
+    // underscore that is not between two digits is still an error.
+    #[test]
+    fn digit_separators() {
+        let lex_separated = |input| {
+            LexerBuilder::new()
+                .digit_separators(true)
+                .build(input)
+                .tokenize_all()
+        };
+        assert_eq!(
+            lex_separated("1_000 2_147_483_647"),
+            Ok(vec![
+                Token::Constant {
+                    value: 1000,
+                    raw: "1_000".to_string()
+                },
+                Token::Constant {
+                    value: i32::MAX,
+                    raw: "2_147_483_647".to_string()
+                },
+            ])
+        );
+        for text in ["1_", "1__0", "1_a"] {
+            assert_eq!(
+                lex_separated(text).unwrap_err().kind(),
+                &LexerErrorKind::InvalidNumericLiteral {
+                    text: text.to_string()
+                }
+            );
+        }
+        assert!(matches!(
+            lex_separated("2_147_483_648").unwrap_err().kind(),
+            LexerErrorKind::IntegerOverflow { value, .. } if value == "2_147_483_648"
+        ));
+    }
+
+    // `concat` joins the text of the pieces `tokenize_lossless` returns for `lexer`.
+    fn concat(lexer: &mut Lexer, input: &str) -> String {
+        let pieces = lexer.tokenize_lossless().unwrap();
+        pieces
+            .iter()
+            .map(|piece| &input[piece.span().start..piece.span().end])
+            .collect()
+    }
+
+    // Tokens and trivia together reproduce the input byte for byte.
+    #[test]
+    fn lossless_pieces_reconstruct_the_input() {
+        let inputs = [
+            "",
+            "   ",
+            "int main() {\r\n  return 0; // done\r\n}\r\n",
+            "#!/usr/bin/env obv\nx /* a\n b */ y",
+            "#!shebang only",
+            "a\t\x0B\x0C\u{2003}b \r c\r\n",
+            "x // trailing comment without a newline",
+            "x /* trailing block comment */",
+        ];
+        for input in inputs {
+            assert_eq!(concat(&mut Lexer::new(input), input), input);
+            let mut keeping = LexerBuilder::new().keep_comments(true).build(input);
+            assert_eq!(concat(&mut keeping, input), input);
+        }
+    }
+
+    // After moving the lexer, the pieces start where it is, not at offset 0.
+    #[test]
+    fn lossless_pieces_start_at_the_position() {
+        let input = " x y";
+        let mut lexer = Lexer::new(input);
+        assert_eq!(lexer.tokenize_range(0, 1), Ok(vec![]));
+        assert_eq!(concat(&mut lexer, input), "x y");
+
+        let input = "#!sh\nx y";
+        let mut lexer = Lexer::new_at(input, 5).unwrap();
+        assert_eq!(concat(&mut lexer, input), "x y");
+        let mut lexer = Lexer::new(input);
+        lexer.next_token();
+        assert_eq!(concat(&mut lexer, input), " y");
+        lexer.rewind();
+        assert_eq!(concat(&mut lexer, input), input);
+    }
+
+    // Recovering lexing reports every bad character in order and keeps the tokens around them.
+    #[test]
+    fn recovering_collects_every_error() {
+        let source = "int $x;\n  y @ é;";
+        let (tokens, errors) = Lexer::new(source).tokenize_all_recovering();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::KwInt,
+                Token::Identifier("x".to_string()),
+                Token::Semicolon,
+                Token::Identifier("y".to_string()),
+                Token::Semicolon,
+            ]
+        );
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|error| {
+                (
+                    error.kind().clone(),
+                    error.span(),
+                    error.line(),
+                    error.column(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    LexerErrorKind::UnexpectedCharacter { char: '$' },
+                    Span::new(4, 5),
+                    1,
+                    5
+                ),
+                (
+                    LexerErrorKind::UnexpectedCharacter { char: '@' },
+                    Span::new(12, 13),
+                    2,
+                    5
+                ),
+                (
+                    LexerErrorKind::UnexpectedCharacter { char: 'é' },
+                    Span::new(14, 16),
+                    2,
+                    7
+                ),
+            ]
+        );
+        // The strict method still stops at the first error.
+        assert_eq!(lex_error(source).span(), Span::new(4, 5));
+    }
+
+    // `reset` switches to a new input and `rewind` starts the same input over, clearing
+    // the progress made so far.
+    #[test]
+    fn reset_and_rewind() {
+        let mut lexer = LexerBuilder::new().file_name("a.c").build("int a;");
+        assert_eq!(lexer.tokenize_all().unwrap().len(), 3);
+        lexer.reset("  return b");
+        assert_eq!(lexer.position(), 0);
+        assert_eq!(
+            lexer.tokenize_all().unwrap(),
+            vec![Token::KwReturn, Token::Identifier("b".to_string())]
+        );
+        assert_eq!(lexer.skipped_bytes(), 3);
+        lexer.rewind();
+        assert_eq!(lexer.skipped_bytes(), 0);
+        assert_eq!(lexer.tokenize_all().unwrap().len(), 2);
+        // The options are kept.
+        lexer.reset("$");
+        assert_eq!(lexer.tokenize_all().unwrap_err().file(), Some("a.c"));
+    }
+
+    // With error tokens, a run of bad characters becomes a single in-band `Token::Error`.
+    #[test]
+    fn error_tokens_coalesce_runs() {
+        let error = |text: &str| Token::Error {
+            text: text.to_string(),
+        };
+        let spanned = |source: &str| {
+            LexerBuilder::new()
+                .error_tokens(true)
+                .build(source)
+                .tokenize_all_spanned()
+                .unwrap()
+        };
+        assert_eq!(spanned("@@@"), vec![(error("@@@"), Span::new(0, 3))]);
+        assert_eq!(
+            spanned("@a@"),
+            vec![
+                (error("@"), Span::new(0, 1)),
+                (Token::Identifier("a".to_string()), Span::new(1, 2)),
+                (error("@"), Span::new(2, 3)),
+            ]
+        );
+        assert_eq!(spanned("x é$ y")[1], (error("é$"), Span::new(2, 5)));
+        assert_eq!(
+            serde_json::to_string(&error("@@")).unwrap(),
+            r#"{"Error":{"text":"@@"}}"#
+        );
+    }
+
+    // An unclosed `/*` is reported at the opener, with the span running to the end.
+    #[test]
+    fn unterminated_block_comments() {
+        let cases = [
+            ("x /* never closed", 2),
+            ("x /* a /* b", 2),
+            ("x /* closed */ y /* not", 17),
+        ];
+        for (source, start) in cases {
+            let error = lex_error(source);
+            assert_eq!(error.kind(), &LexerErrorKind::UnterminatedComment);
+            assert_eq!(error.span(), Span::new(start, source.len()));
+        }
+    }
+
+    // Closed literals keep their quotes, and an escaped quote does not close a string.
+    #[test]
+    fn string_and_char_literals() {
+        assert_eq!(
+            lex(r#""ok" 'b' "a\"b""#),
+            vec![
+                Token::StringLiteral {
+                    value: r#""ok""#.to_string(),
+                    prefix: None
+                },
+                Token::CharLiteral("'b'".to_string()),
+                Token::StringLiteral {
+                    value: r#""a\"b""#.to_string(),
+                    prefix: None
+                },
+            ]
+        );
+    }
+
+    // A `#` line is a directive, running to the end of the line.
+    #[test]
+    fn include_directive() {
+        assert_eq!(
+            lex("#include <stdio.h>\nint x;")[..2],
+            [
+                Token::Directive("#include <stdio.h>".to_string()),
+                Token::KwInt
+            ]
+        );
+    }
+
+    // Backslash-newline continues a directive onto the next line, for every line break.
+    #[test]
+    fn define_with_a_continuation() {
+        assert_eq!(
+            lex("  #define N \\\n  10\nN"),
+            vec![
+                Token::Directive("#define N \\\n  10".to_string()),
+                Token::Identifier("N".to_string())
+            ]
+        );
+        assert_eq!(
+            lex("#define A\\\r\nB\r\nx")[0],
+            Token::Directive("#define A\\\r\nB".to_string())
+        );
+    }
+
+    // Every built-in keyword maps to its token; a longer word is still an identifier.
+    #[test]
+    fn keywords_map_to_their_tokens() {
+        assert_eq!(
+            lex("int void return sizeof typedef struct union enum static extern"),
+            vec![
+                Token::KwInt,
+                Token::KwVoid,
+                Token::KwReturn,
+                Token::KwSizeof,
+                Token::KwTypedef,
+                Token::KwStruct,
+                Token::KwUnion,
+                Token::KwEnum,
+                Token::KwStatic,
+                Token::KwExtern,
+            ]
+        );
+        assert_eq!(
+            lex("sizeof(int)"),
+            vec![
+                Token::KwSizeof,
+                Token::OpenParen,
+                Token::KwInt,
+                Token::CloseParen
+            ]
+        );
+        assert_eq!(
+            lex("sizeofx"),
+            vec![Token::Identifier("sizeofx".to_string())]
+        );
+    }
+
+    // A tab advances error columns to the next tab stop; by default it counts as one column.
+    #[test]
+    fn tab_width_sets_error_columns() {
+        let error = |tab_width: usize| {
+            let mut lexer = LexerBuilder::new().tab_width(tab_width).build("\t\tx$");
+            lexer.tokenize_all().unwrap_err().location()
+        };
+        assert_eq!(error(4), (3, 1, 10));
+        assert_eq!(error(8), (3, 1, 18));
+        assert_eq!(
+            error(1),
+            Lexer::new("\t\tx$").tokenize_all().unwrap_err().location()
+        );
+        assert_eq!(error(1), (3, 1, 4));
+        let mut lexer = LexerBuilder::new().tab_width(4).build("x\n \t007");
+        let (_, warnings) = lexer.tokenize_with_warnings().unwrap();
+        assert_eq!(warnings[0].location(), (4, 2, 5));
+    }
+
+    // Syncing to the end of the statement reports one error where per-character recovery
+    // reports a cascade, and lexes the tokens after the sync point as usual.
+    #[test]
+    fn recovery_policies() {
+        let source = "int x @#$ 12ab ~ y;\nint z;";
+        let recover = |policy: RecoveryPolicy| {
+            let mut lexer = LexerBuilder::new().recovery(policy).build(source);
+            let (tokens, errors) = lexer.tokenize_all_recovering();
+            let spans: Vec<Span> = errors.iter().map(LexerError::span).collect();
+            (tokens, spans)
+        };
+        let (tokens, spans) = recover(RecoveryPolicy::PerChar);
+        assert_eq!(spans.len(), 5);
+        assert_eq!(tokens.len(), 7);
+        let (tokens, spans) = recover(RecoveryPolicy::SyncToStatement);
+        assert_eq!(spans, [Span::new(6, 7)]);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::KwInt,
+                Token::Identifier("x".to_string()),
+                Token::Semicolon,
+                Token::KwInt,
+                Token::Identifier("z".to_string()),
+                Token::Semicolon,
+            ]
+        );
+    }
+
+    // `last_token` is the most recent token produced; errors leave it unchanged.
+    #[test]
+    fn last_token_follows_successes() {
+        let mut lexer = Lexer::new("int main $");
+        assert_eq!(lexer.last_token(), None);
+        lexer.next_token().unwrap().unwrap();
+        lexer.next_token().unwrap().unwrap();
+        assert_eq!(
+            lexer.last_token(),
+            Some(&Token::Identifier("main".to_string()))
+        );
+        assert!(lexer.next_token().unwrap().is_err());
+        assert_eq!(
+            lexer.last_token(),
+            Some(&Token::Identifier("main".to_string()))
+        );
+    }
+
+    // Overflow and malformed digits are told apart by why parsing failed.
+    #[test]
+    fn overflow_is_not_an_invalid_digit() {
+        let overflow = "2147483648".parse::<i32>().unwrap_err();
+        assert_eq!(
+            integer_error_kind("2147483648", 10, &overflow),
+            LexerErrorKind::IntegerOverflow {
+                value: "2147483648".to_string(),
+                radix: 10,
+                bits: 32,
+                max: 2147483647,
+            }
+        );
+        let invalid = i32::from_str_radix("1G", 16).unwrap_err();
+        assert_eq!(
+            integer_error_kind("1G", 16, &invalid),
+            LexerErrorKind::InvalidInteger {
+                value: "1G".to_string(),
+                radix: 16,
+            }
+        );
+    }
+
+    // Encoding prefixes are recorded apart from the string; other letters stay identifiers.
+    #[test]
+    fn prefixed_string_literals() {
+        let string = |value: &str, prefix: Option<&str>| Token::StringLiteral {
+            value: value.to_string(),
+            prefix: prefix.map(str::to_string),
+        };
+        assert_eq!(
+            lex(r#""a" L"b" u8"c" u"d" U"e""#),
+            vec![
+                string(r#""a""#, None),
+                string(r#""b""#, Some("L")),
+                string(r#""c""#, Some("u8")),
+                string(r#""d""#, Some("u")),
+                string(r#""e""#, Some("U")),
+            ]
+        );
+        assert_eq!(
+            lex(r#"x"a" L"#),
+            vec![
+                Token::Identifier("x".to_string()),
+                string(r#""a""#, None),
+                Token::Identifier("L".to_string()),
+            ]
+        );
+    }
+
+    // `lex_everything` runs `input` through the strict, recovering and lossless entry points,
+    // each of which must return rather than panic.
+    fn lex_everything(input: &str) {
+        let _ = Lexer::new(input).tokenize_all();
+        let _ = Lexer::new(input).tokenize_all_recovering();
+        let _ = Lexer::new(input).tokenize_lossless();
+        let _ = LexerBuilder::new()
+            .error_tokens(true)
+            .build(input)
+            .tokenize_all();
+    }
+
+    // Inputs ending inside a delimiter or right around a multi-byte character, long runs of
+    // `/` and input that is only trivia all lex without panicking.
+    #[test]
+    fn adversarial_inputs_do_not_panic() {
+        let slashes = "/".repeat(257);
+        let inputs = [
+            "",
+            " ",
+            "/",
+            "/*",
+            "/* *",
+            "/* é",
+            "/* é*",
+            "x /",
+            "x */",
+            "// é",
+            "\"é",
+            "'é",
+            "é",
+            "x é",
+            "R\"é(",
+            "#é",
+            "\u{00A0}",
+            " \t\r\n",
+            "/* a */ // b\n",
+            slashes.as_str(),
+        ];
+        for input in inputs {
+            lex_everything(input);
+        }
+    }
+
+    // Every prefix and suffix of a sample program, cut at each character boundary, lexes
+    // without panicking. The sample stays short, since this is quadratic in its length.
+    #[test]
+    fn every_prefix_lexes_without_panicking() {
+        let sample =
+            "#!x\n#define é 1\nint f(){ /* ö /* */ return L\"ü\"[0] + 'ß' + 0x1f; } // ∑\r\n$";
+        for (end, _) in sample.char_indices().chain([(sample.len(), ' ')]) {
+            lex_everything(&sample[..end]);
+            lex_everything(&sample[end..]);
+        }
+    }
+
+    // `limited` lexes `input` with the given limits, returning the tokens before the first
+    // error and the error's kind and span.
+    fn limited(
+        max_input_bytes: Option<usize>,
+        max_tokens: Option<usize>,
+        input: &str,
+    ) -> (Vec<Token>, Option<(LexerErrorKind, Span)>) {
+        let mut lexer = LexerBuilder::new()
+            .max_input_bytes(max_input_bytes)
+            .max_tokens(max_tokens)
+            .build(input);
+        let mut tokens = Vec::new();
+        for item in lexer.tokens() {
+            match item {
+                Ok(token) => tokens.push(token),
+                Err(error) => return (tokens, Some((error.kind().clone(), error.span()))),
+            }
+        }
+        (tokens, None)
+    }
+
+    // Exactly `max_tokens` tokens are fine; one more is an error at that token.
+    #[test]
+    fn max_tokens_boundary() {
+        assert_eq!(limited(None, Some(3), "int x;").0.len(), 3);
+        assert_eq!(limited(None, Some(3), "int x;").1, None);
+        let (tokens, error) = limited(None, Some(2), "int x;");
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(
+            error,
+            Some((
+                LexerErrorKind::ResourceLimitExceeded {
+                    kind: ResourceKind::Tokens,
+                    limit: 2,
+                },
+                Span::new(5, 6),
+            ))
+        );
+        assert_eq!(limited(None, None, &"x ".repeat(1000)).0.len(), 1000);
+    }
+
+    // An input of exactly `max_input_bytes` bytes is lexed in full; one byte less is an error.
+    #[test]
+    fn max_input_bytes_boundary() {
+        assert_eq!(limited(Some(6), None, "int x;"), (lex("int x;"), None));
+        assert_eq!(limited(None, None, "int x;"), (lex("int x;"), None));
+        let (tokens, error) = limited(Some(5), None, "int x;");
+        assert_eq!(tokens, lex("int x"));
+        assert_eq!(
+            error,
+            Some((
+                LexerErrorKind::ResourceLimitExceeded {
+                    kind: ResourceKind::InputBytes,
+                    limit: 5,
+                },
+                Span::new(5, 6),
+            ))
+        );
+    }
+
+    // The input limit is checked after each scanning step, so the token crossing it is read in
+    // full and the error covers everything read past the limit.
+    #[test]
+    fn max_input_bytes_is_checked_after_each_step() {
+        let (tokens, error) = limited(Some(5), None, "abc defgh");
+        assert_eq!(tokens, lex("abc"));
+        assert_eq!(error.unwrap().1, Span::new(5, 9));
+
+        let (tokens, error) = limited(Some(1), None, "x    ");
+        assert_eq!(tokens, lex("x"));
+        assert_eq!(error.unwrap().1, Span::new(1, 5));
+    }
+
+    // Input holding only whitespace and comments is blank; `is_blank` leaves the lexer as it was.
+    #[test]
+    fn blank_input() {
+        let mut lexer = Lexer::new("   // hi\n/* x */");
+        assert!(lexer.is_blank());
+        assert_eq!(lexer.position(), 0);
+        assert_eq!(lexer.tokenize_all(), Ok(vec![]));
+
+        let mut lexer = Lexer::new("  int");
+        assert!(!lexer.is_blank());
+        assert_eq!(lexer.position(), 0);
+        assert_eq!(lexer.tokenize_all(), Ok(vec![Token::KwInt]));
+
+        assert!(Lexer::new("").is_blank());
+        assert!(!Lexer::new("$").is_blank());
+        assert!(!Lexer::new("/* x").is_blank());
+        assert!(
+            !LexerBuilder::new()
+                .keep_comments(true)
+                .build("// hi")
+                .is_blank()
+        );
+    }
+
+    // Every one- and two-character input from a pool of awkward characters lexes without a
+    // `NoMatch`, whatever the options: anything no rule accepts is an `UnexpectedCharacter`.
+    #[test]
+    fn no_match_never_occurs() {
+        let pool: Vec<char> = "aZ_09x.'\"/*#\\ \t\r\n(){};$@`?=\0\u{7f}\u{a0}é∑\u{201C}\u{FEFF}"
+            .chars()
+            .collect();
+        let builders = [
+            LexerBuilder::new(),
+            LexerBuilder::new().lenient(true),
+            LexerBuilder::new().error_tokens(true),
+            LexerBuilder::new().keep_comments(true).emit_newlines(true),
+        ];
+        for &first in &pool {
+            for second in pool.iter().map(|c| Some(*c)).chain([None]) {
+                let input: String = [Some(first), second].into_iter().flatten().collect();
+                for builder in &builders {
+                    let (_, errors) = builder.clone().build(&input).tokenize_all_recovering();
+                    assert!(
+                        errors.iter().all(|e| e.kind() != &LexerErrorKind::NoMatch),
+                        "NoMatch for {:?}",
+                        input
+                    );
+                }
+            }
+        }
+    }
+
+    // Multi-byte characters right next to token and comment boundaries keep the position on
+    // a character boundary (checked by the `debug_assert`s in `advance`).
+    #[test]
+    fn multi_byte_characters_at_token_boundaries() {
+        let mut lexer = LexerBuilder::new()
+            .keep_comments(true)
+            .build("x/*é*/y\"é\"//ü\n'ß'");
+        let spans: Vec<Span> = lexer
+            .tokenize_all_spanned()
+            .unwrap()
+            .into_iter()
+            .map(|(_, span)| span)
+            .collect();
+        assert_eq!(
+            spans,
+            [(0, 1), (1, 7), (7, 8), (8, 12), (12, 16), (17, 21)].map(|(s, e)| Span::new(s, e))
+        );
+    }
+
+    // Seeking into the middle of a character is caught in debug builds.
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "is_char_boundary")]
+    fn seek_inside_a_character_panics() {
+        Lexer::new("xé").seek(2);
+    }
+
+    // So is restoring a checkpoint whose position is inside a character.
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "is_char_boundary")]
+    fn restore_inside_a_character_panics() {
+        let mut lexer = Lexer::new("xé");
+        let mut checkpoint = lexer.checkpoint();
+        checkpoint.position = 2;
+        lexer.restore(&checkpoint);
+    }
+
+    // A `.` after an identifier is member access, even before a digit; elsewhere a `.` next
+    // to digits is part of a floating-point constant.
+    #[test]
+    fn dots_and_floats() {
+        let ident = |name: &str| Token::Identifier(name.to_string());
+        let float = |text: &str| Token::Float(text.to_string());
+        assert_eq!(lex("a.b"), vec![ident("a"), Token::Dot, ident("b")]);
+        assert_eq!(lex(".5"), vec![float(".5")]);
+        assert_eq!(lex("3.14"), vec![float("3.14")]);
+        assert_eq!(lex("5."), vec![float("5.")]);
+        assert_eq!(
+            lex("a.5"),
+            vec![
+                ident("a"),
+                Token::Dot,
+                Token::Constant {
+                    value: 5,
+                    raw: "5".to_string()
+                }
+            ]
+        );
+        assert_eq!(lex("(.5)")[1], float(".5"));
+        assert_eq!(lex("a .b"), vec![ident("a"), Token::Dot, ident("b")]);
+    }
+
+    // By default `next_token` returns the same error on every call after it, without moving.
+    #[test]
+    fn next_token_repeats_an_error() {
+        let mut lexer = Lexer::new("x $ y");
+        assert_eq!(
+            lexer.next_token(),
+            Some(Ok(Token::Identifier("x".to_string())))
+        );
+        let first = lexer.next_token().unwrap().unwrap_err();
+        let position = lexer.position();
+        let second = lexer.next_token().unwrap().unwrap_err();
+        assert_eq!(first, second);
+        assert_eq!(first.span(), Span::new(2, 3));
+        assert_eq!(lexer.position(), position);
+    }
+
+    // With `resume_after_error`, the next call continues after the error, following the
+    // recovery policy.
+    #[test]
+    fn next_token_resumes_after_an_error() {
+        let mut lexer = LexerBuilder::new().resume_after_error(true).build("x $ y");
+        lexer.next_token();
+        assert_eq!(
+            lexer.next_token().unwrap().unwrap_err().span(),
+            Span::new(2, 3)
+        );
+        assert_eq!(
+            lexer.next_token(),
+            Some(Ok(Token::Identifier("y".to_string())))
+        );
+        assert_eq!(lexer.next_token(), None);
+        assert_eq!(lexer.next_token(), None);
+
+        let mut lexer = LexerBuilder::new()
+            .resume_after_error(true)
+            .recovery(RecoveryPolicy::SyncToStatement)
+            .build("x $ y; z");
+        lexer.next_token();
+        assert!(lexer.next_token().unwrap().is_err());
+        assert_eq!(lexer.next_token(), Some(Ok(Token::Semicolon)));
+        assert_eq!(
+            lexer.next_token(),
+            Some(Ok(Token::Identifier("z".to_string())))
+        );
+    }
+
+    // A resource limit error is final, whether or not errors are resumed after.
+    #[test]
+    fn next_token_stops_after_a_resource_limit() {
+        for resume in [false, true] {
+            let mut lexer = LexerBuilder::new()
+                .resume_after_error(resume)
+                .max_tokens(Some(1))
+                .build("x y z");
+            assert!(lexer.next_token().unwrap().is_ok());
+            assert_eq!(lexer.next_token().unwrap().unwrap_err().code(), "E0014");
+            assert_eq!(lexer.next_token(), None);
+            assert_eq!(lexer.next_token(), None);
+        }
+    }
+
+    // The longest line counts characters, not bytes or line breaks, and includes a last line
+    // without a trailing newline.
+    #[test]
+    fn max_line_length_after_lexing() {
+        let mut lexer = Lexer::new("int x;\nreturn x / y;\n}");
+        assert_eq!(lexer.max_line_length(), 0);
+        lexer.tokenize_all().unwrap();
+        assert_eq!(lexer.max_line_length(), 13);
+
+        let mut lexer = Lexer::new("a;\r\nb;\r\n// é é é");
+        lexer.tokenize_all().unwrap();
+        assert_eq!(lexer.max_line_length(), 8);
+
+        let mut lexer = Lexer::new("x;\n\n");
+        lexer.tokenize_all().unwrap();
+        assert_eq!(lexer.max_line_length(), 2);
+    }
+
+    // Peeked tokens are the ones `next_token` returns next, in order.
+    #[test]
+    fn peek_then_consume() {
+        let mut lexer = Lexer::new("int x; return");
+        assert_eq!(lexer.peek(), Some(&Ok(Token::KwInt)));
+        assert_eq!(lexer.peek_n(2), Some(&Ok(Token::Semicolon)));
+        assert_eq!(lexer.position(), 0);
+        let consumed: Vec<_> = (0..3)
+            .map(|_| lexer.next_token().unwrap().unwrap())
+            .collect();
+        assert_eq!(consumed, lex("int x;"));
+        assert_eq!(lexer.position(), 6);
+        assert_eq!(lexer.last_token(), Some(&Token::Semicolon));
+        assert_eq!(lexer.next_token(), Some(Ok(Token::KwReturn)));
+        assert_eq!(lexer.peek_n(5), None);
+        assert!(Lexer::new("int x = 1;").peek_n(3).unwrap().is_err());
+    }
+
+    // The bulk methods start with the tokens that were peeked at, and count them only once:
+    // the token limit is exactly the number of tokens, and the counters match a plain lex.
+    #[test]
+    fn bulk_methods_include_peeked_tokens() {
+        let source = "int x; /* c */ return 007;";
+        let peeked = |consume: usize| {
+            let mut lexer = LexerBuilder::new().max_tokens(Some(6)).build(source);
+            lexer.peek_n(3);
+            for _ in 0..consume {
+                lexer.next_token();
+            }
+            lexer
+        };
+        let mut unpeeked = Lexer::new(source);
+        let all = unpeeked.tokenize_all().unwrap();
+        for consume in 0..3 {
+            let mut lexer = peeked(consume);
+            assert_eq!(lexer.tokenize_all().unwrap(), all[consume..]);
+            assert_eq!(lexer.skipped_bytes(), unpeeked.skipped_bytes());
+            assert_eq!(lexer.warnings(), unpeeked.warnings());
+
+            let spanned = peeked(consume).tokenize_all_spanned().unwrap();
+            assert_eq!(spanned.len(), all.len() - consume);
+            let streamed: Vec<_> = peeked(consume).tokens().map(Result::unwrap).collect();
+            assert_eq!(streamed, all[consume..]);
+            let owned: Vec<_> = peeked(consume).into_iter().map(Result::unwrap).collect();
+            assert_eq!(owned, all[consume..]);
+            assert_eq!(peeked(consume).tokenize_all_recovering().0, all[consume..]);
+        }
+    }
+
+    // `remaining_tokens` lexes the rest of the input one `next_token` at a time.
+    fn remaining_tokens(lexer: &mut Lexer) -> Vec<Result<Token, LexerError>> {
+        std::iter::from_fn(|| lexer.next_token()).collect()
+    }
+
+    // After restoring a checkpoint, the lexer produces the same tokens, warnings and
+    // position as the first time.
+    #[test]
+    fn restore_replays_the_same_tokens() {
+        let mut lexer = Lexer::new("int x;\n/* c */ return 007;");
+        lexer.next_token();
+        lexer.next_token();
+        let checkpoint = lexer.checkpoint();
+        let (line, position) = (lexer.current_location(), lexer.position());
+
+        let first = remaining_tokens(&mut lexer);
+        assert_eq!(first.len(), 4);
+        let (warnings, skipped) = (lexer.warnings().to_vec(), lexer.skipped_bytes());
+        assert_eq!(warnings.len(), 1);
+
+        lexer.restore(&checkpoint);
+        assert_eq!(
+            (lexer.current_location(), lexer.position()),
+            (line, position)
+        );
+        assert_eq!(
+            lexer.last_token(),
+            Some(&Token::Identifier("x".to_string()))
+        );
+        assert!(lexer.warnings().is_empty());
+        assert_eq!(remaining_tokens(&mut lexer), first);
+        assert_eq!(lexer.warnings(), warnings);
+        assert_eq!(lexer.skipped_bytes(), skipped);
+    }
+
+    // A checkpoint taken with tokens peeked at restores them too.
+    #[test]
+    fn restore_keeps_the_peek_buffer() {
+        let mut lexer = Lexer::new("a b c d");
+        lexer.next_token();
+        lexer.peek_n(1);
+        let checkpoint = lexer.checkpoint();
+        let first = remaining_tokens(&mut lexer);
+
+        lexer.restore(&checkpoint);
+        assert_eq!(lexer.position(), 1);
+        assert_eq!(
+            lexer.peek_n(1),
+            Some(&Ok(Token::Identifier("c".to_string())))
+        );
+        assert_eq!(remaining_tokens(&mut lexer), first);
+        lexer.restore(&checkpoint);
+        assert_eq!(lexer.tokenize_all().unwrap(), lex("b c d"));
+    }
+
+    // `trigraphs` lexes `input` with trigraph translation on.
+    fn trigraphs(input: &str) -> Result<Vec<(Token, Span)>, LexerError> {
+        LexerBuilder::new()
+            .translate_trigraphs(true)
+            .build(input)
+            .tokenize_all_spanned()
+    }
+
+    // Each trigraph stands for its character, with a span over the original three; those
+    // that are not tokens are reported as the character they stand for.
+    #[test]
+    fn trigraph_mappings() {
+        assert_eq!(
+            trigraphs("??<??>").unwrap(),
+            vec![
+                (Token::OpenBrace, Span::new(0, 3)),
+                (Token::CloseBrace, Span::new(3, 6))
+            ]
+        );
+        assert_eq!(
+            trigraphs("??=define X 1").unwrap(),
+            vec![(
+                Token::Directive("??=define X 1".to_string()),
+                Span::new(0, 13)
+            )]
+        );
+        for (trigraph, char) in [
+            ("??(", '['),
+            ("??)", ']'),
+            ("??/", '\\'),
+            ("??'", '^'),
+            ("??!", '|'),
+            ("??-", '~'),
+        ] {
+            let error = trigraphs(&format!("x {}", trigraph)).unwrap_err();
+            assert_eq!(error.kind(), &LexerErrorKind::UnexpectedCharacter { char });
+            assert_eq!(error.span(), Span::new(2, 5));
+        }
+    }
+
+    // `???` is not a trigraph, trigraphs inside literals and comments are kept as written,
+    // and without the option a trigraph is just question marks.
+    #[test]
+    fn trigraphs_left_alone() {
+        let error = trigraphs("a ??? b").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &LexerErrorKind::UnexpectedCharacter { char: '?' }
+        );
+        assert_eq!(error.span(), Span::new(2, 3));
+        assert_eq!(
+            trigraphs("\"??<\" // ??<").unwrap(),
+            vec![(
+                Token::StringLiteral {
+                    value: "\"??<\"".to_string(),
+                    prefix: None
+                },
+                Span::new(0, 5)
+            )]
+        );
+        assert_eq!(
+            lex_error("??<").kind(),
+            &LexerErrorKind::UnexpectedCharacter { char: '?' }
+        );
+    }
+
+    // Every line gets a vector, empty and comment-only lines included.
+    #[test]
+    fn tokens_by_line_keeps_every_line() {
+        let mut lexer = Lexer::new("int x;\nreturn x;");
+        assert_eq!(
+            lexer.tokens_by_line().unwrap(),
+            vec![lex("int x;"), lex("return x;")]
+        );
+        let mut lexer = Lexer::new("a\n\n// c\nb /* x\n y */\n");
+        assert_eq!(
+            lexer.tokens_by_line().unwrap(),
+            vec![lex("a"), vec![], vec![], lex("b"), vec![], vec![]]
+        );
+        assert!(Lexer::new("a\n$").tokens_by_line().is_err());
+    }
+
+    // Lexing the tail of a file right after a comment gives the same tokens and spans as the
+    // full lex, and errors keep their real line and column.
+    #[test]
+    fn new_at_lexes_the_tail_in_place() {
+        let source = "int x;\n/* c */ return x;\ny";
+        let offset = source.find("*/").unwrap() + 2;
+        let full = Lexer::new(source).tokenize_all_spanned().unwrap();
+        let mut tail = Lexer::new_at(source, offset).unwrap();
+        assert_eq!(tail.position(), offset);
+        assert_eq!(tail.tokenize_all_spanned().unwrap(), full[3..]);
+        assert_eq!(tail.skipped_bytes(), 3);
+
+        let error = Lexer::new_at("int x;\n/* c */ y $", offset)
+            .unwrap()
+            .tokenize_all()
+            .unwrap_err();
+        assert_eq!((error.line(), error.column()), (2, 11));
+        assert_eq!(error.span(), Span::new(17, 18));
+    }
+
+    // An offset inside a character is an `InvalidRange` error; the input's length is fine.
+    #[test]
+    fn new_at_checks_the_offset() {
+        let error = Lexer::new_at("é x", 1).err().unwrap();
+        assert_eq!(
+            error.kind(),
+            &LexerErrorKind::InvalidRange {
+                range: Span::new(1, 4)
+            }
+        );
+        assert!(
+            Lexer::new_at("x", 1)
+                .unwrap()
+                .tokenize_all()
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    // The combined regex matches exactly what the separate identifier and constant regexes
+    // did, at every character boundary of a large mixed input, and `Identifier` and `Constant`
+    // asked for on their own only match their half of it.
+    #[test]
+    fn word_match_equals_separate_rules() {
+        let source = "int main(void) { return x1 + 42; } _a __b9 007 12ab 0x1F é1 a.b 3.14 ü_ 9_\n"
+            .repeat(20);
+        let options = LexerOptions::default();
+        // The dispatch `word_match` replaced: an identifier regex tried first, then a constant
+        // regex.
+        let identifier = Regex::new(r"\A[a-zA-Z_]\w*\b").unwrap();
+        let constant = Regex::new(r"\A[0-9]+\b").unwrap();
+        let separate_word_match = |input: &str| {
+            identifier
+                .find(input)
+                .map(|mat| (mat.end(), BuiltinRule::Identifier))
+                .or_else(|| {
+                    constant
+                        .find(input)
+                        .map(|mat| (mat.end(), BuiltinRule::Constant))
+                })
+        };
+        for (start, _) in source.char_indices() {
+            let rest = &source[start..];
+            let expected = separate_word_match(rest);
+            assert_eq!(word_match(rest), expected, "at {}", start);
+            for rule in [BuiltinRule::Identifier, BuiltinRule::Constant] {
+                assert_eq!(
+                    rule.match_len(rest, &options, false),
+                    expected.filter(|&(_, found)| found == rule)
+                );
+            }
+        }
+    }
+
+    // Lexing the large mixed input gives the tokens of the separate rules, keywords included.
+    #[test]
+    fn combined_regex_tokens() {
+        let source =
+            "int main(void) { return x1 / 42; } _a __b9 007 sizeof extern9 a.b 3.14\n".repeat(20);
+        let tokens = lex(&source);
+        assert_eq!(tokens.len(), 20 * 21);
+        assert_eq!(
+            tokens[..21],
+            [
+                Token::KwInt,
+                Token::Identifier("main".to_string()),
+                Token::OpenParen,
+                Token::KwVoid,
+                Token::CloseParen,
+                Token::OpenBrace,
+                Token::KwReturn,
+                Token::Identifier("x1".to_string()),
+                Token::Slash,
+                Token::Constant {
+                    value: 42,
+                    raw: "42".to_string()
+                },
+                Token::Semicolon,
+                Token::CloseBrace,
+                Token::Identifier("_a".to_string()),
+                Token::Identifier("__b9".to_string()),
+                Token::Constant {
+                    value: 7,
+                    raw: "007".to_string()
+                },
+                Token::KwSizeof,
+                Token::Identifier("extern9".to_string()),
+                Token::Identifier("a".to_string()),
+                Token::Dot,
+                Token::Identifier("b".to_string()),
+                Token::Float("3.14".to_string()),
+            ]
+        );
+    }
+
+    // Raw strings keep their body verbatim, with the default or a custom delimiter.
+    #[test]
+    fn raw_strings() {
+        let raw = |body: &str| Token::RawString(body.to_string());
+        assert_eq!(
+            lex(r#"R"(no \escapes here)""#),
+            vec![raw(r"no \escapes here")]
+        );
+        assert_eq!(
+            lex("R\"xyz(a )\" b\nc)xyz\" ;"),
+            vec![raw("a )\" b\nc"), Token::Semicolon]
+        );
+        assert_eq!(lex(r#"R"()""#), vec![raw("")]);
+    }
+
+    // An unclosed raw string is an error covering the rest of the input; without a valid
+    // opener, `R` is an identifier.
+    #[test]
+    fn raw_string_errors() {
+        let error = lex_error(r#"x R"xyz(a)xy""#);
+        assert_eq!(error.kind(), &LexerErrorKind::UnterminatedString);
+        assert_eq!(error.span(), Span::new(2, 13));
+        assert_eq!(lex_error("R\"(open").span(), Span::new(0, 7));
+        assert_eq!(lex("R x")[0], Token::Identifier("R".to_string()));
+        assert_eq!(
+            lex(r#"R"a b(x)a b""#)[0],
+            Token::Identifier("R".to_string())
+        );
+    }
+
+    // A delimiter has at most `RAW_STRING_MAX_DELIMITER` characters, and the `(` after it is
+    // only looked for that far: a long tail without one is not searched to its end.
+    #[test]
+    fn raw_string_delimiter_length() {
+        let delimiter = "d".repeat(RAW_STRING_MAX_DELIMITER);
+        assert_eq!(
+            lex(&format!("R\"{delimiter}(x){delimiter}\"")),
+            vec![Token::RawString("x".to_string())]
+        );
+        assert_eq!(
+            lex(&format!("R\"{delimiter}d(x){delimiter}d\""))[0],
+            Token::Identifier("R".to_string())
+        );
+        let tail = "a".repeat(10_000);
+        assert_eq!(raw_string_delimiter(&format!("R\"{tail}")), None);
+        assert_eq!(raw_string_delimiter(&format!("R\"{tail}(")), None);
+        assert_eq!(
+            lex(&format!("R\"{tail}\"")),
+            vec![
+                Token::Identifier("R".to_string()),
+                Token::StringLiteral {
+                    value: format!("\"{tail}\""),
+                    prefix: None,
+                },
+            ]
+        );
+    }
+
+    // `token_at` finds the token containing an offset, and nothing in whitespace or past the
+    // end; the offset just after a token belongs to what follows.
+    #[test]
+    fn token_at_offsets() {
+        let at = |offset: usize| Lexer::new("int main() /* c */").token_at(offset).unwrap();
+        let main = (Token::Identifier("main".to_string()), Span::new(4, 8));
+        assert_eq!(at(5), Some(main.clone()));
+        assert_eq!(at(4), Some(main));
+        assert_eq!(at(3), None);
+        assert_eq!(at(8), Some((Token::OpenParen, Span::new(8, 9))));
+        assert_eq!(at(13), None);
+        assert_eq!(at(100), None);
+        // Peeked tokens are lexed again, so peeking first changes nothing.
+        let mut lexer = Lexer::new("int main");
+        lexer.peek_n(1);
+        assert_eq!(lexer.token_at(0), Ok(Some((Token::KwInt, Span::new(0, 3)))));
+    }
+
+    // An error after the offset does not matter; one at or before it is returned.
+    #[test]
+    fn token_at_errors() {
+        let at = |offset: usize| Lexer::new("x  $ y").token_at(offset);
+        assert_eq!(
+            at(0).unwrap().unwrap().0,
+            Token::Identifier("x".to_string())
+        );
+        assert_eq!(at(2), Ok(None));
+        assert_eq!(at(3).unwrap_err().span(), Span::new(3, 4));
+        assert_eq!(at(5).unwrap_err().span(), Span::new(3, 4));
+    }
+
+    // A clone taken mid-stream continues from the same point, and advancing either one
+    // leaves the other where it was, including what it had peeked.
+    #[test]
+    fn clones_advance_independently() {
+        let mut original = Lexer::new("int x y;");
+        original.next_token();
+        original.peek();
+        let mut clone = original.clone();
+        assert_eq!(clone.position(), original.position());
+
+        assert_eq!(
+            clone.next_token(),
+            Some(Ok(Token::Identifier("x".to_string())))
+        );
+        assert_eq!(
+            clone.next_token(),
+            Some(Ok(Token::Identifier("y".to_string())))
+        );
+        assert_eq!(original.position(), 3);
+        assert_eq!(
+            original.next_token(),
+            Some(Ok(Token::Identifier("x".to_string())))
+        );
+        assert_eq!(clone.next_token(), Some(Ok(Token::Semicolon)));
+        assert_eq!(clone.next_token(), None);
+        assert_eq!(
+            original.next_token(),
+            Some(Ok(Token::Identifier("y".to_string())))
+        );
+    }
+}
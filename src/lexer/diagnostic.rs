@@ -0,0 +1,120 @@
+// Import the `Serialize` trait, so diagnostics can be written out (e.g. as JSON).
+use serde::Serialize;
+
+// Import the two kinds of problems a diagnostic can describe.
+use super::error::LexerError;
+use super::warning::LexerWarning;
+
+// Import the `Span` struct, describing the range of text a diagnostic is about.
+use super::span::Span;
+
+// --- Severity ---
+// `Severity` says how serious a `Diagnostic` is.
+// - `Error`: Lexing could not continue past this point (see `LexerError`).
+// - `Warning`: The input is valid, but probably not what the author meant (see `LexerWarning`).
+// Serializes as `"error"` or `"warning"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// --- Diagnostics ---
+// A `Diagnostic` is either an error or a warning, so that tools can handle everything the
+// lexer reports about an input in one list, sorted by position (see
+// `Lexer::tokenize_all_with_diagnostics`). The wrapped `LexerError` or `LexerWarning` keeps
+// all of its details; `Diagnostic` only adds what they have in common.
+// Serializes as e.g. `{"Warning": {"leading_zero": {...}}}`.
+#[derive(Debug, PartialEq, Serialize)]
+pub enum Diagnostic {
+    Error(LexerError),
+    Warning(LexerWarning),
+}
+
+impl Diagnostic {
+    // `severity` returns whether the diagnostic is an error or a warning.
+    pub fn severity(&self) -> Severity {
+        match self {
+            Diagnostic::Error(_) => Severity::Error,
+            Diagnostic::Warning(_) => Severity::Warning,
+        }
+    }
+
+    // `message` returns the description of the problem, without its location.
+    pub fn message(&self) -> String {
+        match self {
+            Diagnostic::Error(error) => error.message(),
+            Diagnostic::Warning(warning) => warning.message(),
+        }
+    }
+
+    // `span` returns the byte range of the text the diagnostic is about.
+    pub fn span(&self) -> Span {
+        match self {
+            Diagnostic::Error(error) => error.span(),
+            Diagnostic::Warning(warning) => warning.span(),
+        }
+    }
+
+    // `render` formats the diagnostic rustc-style, labelled `error` or `warning` (see
+    // `LexerError::render` and `LexerWarning::render`).
+    pub fn render(&self, source: &str) -> String {
+        match self {
+            Diagnostic::Error(error) => error.render(source),
+            Diagnostic::Warning(warning) => warning.render(source),
+        }
+    }
+
+    // `render_colored` formats the diagnostic like `render`, with tabs expanded to `tab_width`
+    // columns and ANSI colors for a terminal (see `LexerError::render_colored`).
+    pub fn render_colored(&self, source: &str, tab_width: usize) -> String {
+        match self {
+            Diagnostic::Error(error) => error.render_colored(source, tab_width),
+            Diagnostic::Warning(warning) => warning.render_colored(source, tab_width),
+        }
+    }
+
+    // `to_gcc_string` formats the diagnostic on a single `file:line:col: severity: message` line.
+    pub fn to_gcc_string(&self) -> String {
+        match self {
+            Diagnostic::Error(error) => error.to_gcc_string(),
+            Diagnostic::Warning(warning) => warning.to_gcc_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    // A successful lex can still carry warnings, sorted by position.
+    #[test]
+    fn successful_lex_with_two_warnings() {
+        let (tokens, diagnostics) =
+            Lexer::new("int x /* a /* b */ 007;").tokenize_all_with_diagnostics();
+        assert_eq!(tokens.len(), 4);
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.severity(), diagnostic.span()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Severity::Warning, Span::new(11, 13)),
+                (Severity::Warning, Span::new(19, 22)),
+            ]
+        );
+    }
+
+    // An error ends the list, after the warnings found before it.
+    #[test]
+    fn errors_follow_earlier_warnings() {
+        let (tokens, diagnostics) = Lexer::new("x 007 $ 008").tokenize_all_with_diagnostics();
+        assert_eq!(tokens.len(), 2);
+        let severities: Vec<_> = diagnostics.iter().map(Diagnostic::severity).collect();
+        assert_eq!(severities, [Severity::Warning, Severity::Error]);
+        assert_eq!(diagnostics[1].message(), "Unexpected character '$'");
+    }
+}
@@ -3,97 +3,1513 @@
 // is used to implement the `Display` trait for custom error types.
 use std::fmt;
 
-// Import the `Serialize` trait from the `serde` crate.
-// This allows `LexerError` enum to be converted into formats like JSON,
+// Import `ParseIntError`, the underlying cause kept by integer constant errors.
+use std::num::ParseIntError;
+
+// Import the `Serialize` and `Deserialize` traits from the `serde` crate.
+// This allows `LexerError` to be converted into formats like JSON,
 // which is useful if there is a need to communicate errors to other
-// programs or log them in a structured way.
-use serde::Serialize;
+// programs or log them in a structured way, and to be loaded back from them.
+use serde::{Deserialize, Serialize};
+
+// Import the caret-style snippet renderer used by `LexerError::render`,
+// and the tab width it uses unless told otherwise. `colorize` adds the colors of `render_colored`.
+use super::line_index::DEFAULT_TAB_WIDTH;
+use super::render::{ERROR_COLOR, colorize, render_snippet};
 
-// Definition of the `LexerError` enumeration.
-// This enum represents the different kinds of errors that can occur during
-// the lexical analysis (tokenization) phase.
+// Import the `Span` struct, used to describe the byte range an error covers.
+use super::span::Span;
+
+// --- Lexer Errors ---
+// `LexerError` represents an error that occurred during the lexical analysis (tokenization)
+// phase. It is made of two parts:
+// - `kind`: Which kind of error it is, together with the data specific to that kind, e.g. the
+//   unexpected character (see `LexerErrorKind`). It is boxed for the same reason as `context`.
+// - The data every error has, whatever its kind:
+//   - `span`: The byte range of the offending text (`end` is exclusive), so that tools can
+//     underline all of it, e.g. an entire out-of-range integer. For an unexpected character
+//     the range is that one character (which may be several bytes long in UTF-8); when there
+//     is no offending text, the range is empty (`start == end`). Its start is the error's `pos`.
+//   - `line` and `column`: The human-readable location of `pos`. Both are 1-based by default;
+//     `LexerOptions::numbering` can make either one 0-based. Columns count characters (Unicode
+//     scalar values) rather than bytes, so they stay correct after multi-byte UTF-8 characters
+//     earlier on the same line: in `é $` the `$` is at column 3, although it is at byte 3 of
+//     the line. `LineIndex::line_col_bytes` gives the byte-based column if that is what a
+//     consumer needs. A tab also counts as a single character column by default; with
+//     `LexerOptions::tab_width`, it advances the column to the next tab stop instead, like
+//     `LineIndex::visual_col` does.
+//   - `file`: The name of the file the error occurred in (see `LexerOptions::file_name`), or
+//     `None` for unnamed input.
+//   - `context`: The text of the source line the error occurred on (see `ErrorContext`), so
+//     that consumers of the serialized error can show the offending code without re-reading
+//     the file. It is boxed to keep `LexerError` (and every `Result` carrying one) small.
+//   - `source`: The error of the library call that failed, if the error has such a cause:
+//     the `ParseIntError` of an integer constant that could not be parsed. It is exposed
+//     through `std::error::Error::source` (see below).
+// The fields are private: the accessors (`kind`, `pos`, `span`, `line`, `column`, `file`,
+// `message`, `code`, ...) are the way to inspect an error, and the stable error `code`
+// follows from the kind.
 //
 // `#[derive(...)]` is an attribute for automatic trait implementations:
+// - `Clone`: Allows copying an error, e.g. to keep it while also reporting it.
 // - `Debug`: Allows instances of `LexerError` to be printed with `{:?}` for debugging.
-// - `PartialEq`: Allows comparing `LexerError` instances, useful for testing error conditions.
 // - `Serialize`: Enables serialization of `LexerError` instances into formats like JSON.
-#[derive(Debug, PartialEq, Serialize)]
-pub enum LexerError {
-    // Variant representing an error where an unexpected character is encountered.
-    // This means a character was found that cannot start any known token pattern.
-    //
-    // `#[serde(rename = "unexpected_character")]` is a `serde` attribute.
-    // It specifies that when this variant is serialized to JSON (or other formats),
-    // the field name for this variant should be "unexpected_character" instead of
-    // the default "UnexpectedCharacter". This is done to maintain the writing style
-    // here, snake_case.
-    //
-    // This variant uses named fields (`char` and `pos`) for clarity in both Rust code
-    // and the serialized output (i.e., JSON).
-    #[serde(rename = "unexpected_character")]
+// - `Deserialize`: Enables loading them back, e.g. to re-render errors stored as JSON. It reads
+//   exactly what `Serialize` writes (the same snake_case names; a missing `file` is `None`).
+// `#[serde(into = ..., from = ...)]` makes both go through `CodedError` (see the end of this
+// file), which gives the JSON the error's stable `code` (see `LexerError::code`) and a single
+// key naming the kind, holding the kind's data followed by the shared fields, e.g.
+// `{"code": "E0001", "unexpected_character": {"char": "$", "pos": 20, "end": 21, "line": 1,
+// "column": 21, "context": {...}}}`.
+// The `code` is ignored when loading an error back, since it follows from the kind; the
+// `source` is not serialized.
+// `PartialEq` is implemented below, comparing everything but the `source`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(into = "CodedError", from = "CodedError")]
+pub struct LexerError {
+    kind: Box<LexerErrorKind>,
+    span: Span,
+    line: usize,
+    column: usize,
+    file: Option<String>,
+    context: Box<ErrorContext>,
+    source: Option<ParseIntError>,
+}
+
+// --- Error Kinds ---
+// `LexerErrorKind` is the kind of a `LexerError` (see `LexerError::kind`), with the data that
+// only errors of that kind have. Code can `match err.kind()` to handle particular kinds.
+// `#[non_exhaustive]` means new kinds may be added without it being a breaking change: code
+// outside this crate must include a wildcard arm when matching on `LexerErrorKind`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LexerErrorKind {
+    // An unexpected character: a character that cannot start any known token pattern.
     UnexpectedCharacter {
         char: char, // The actual unexpected character that was encountered.
-        pos: usize, // The byte offset (position) in the input string where the character was found
     },
 
-    // Variant representing an error where a sequence of digits was found that
-    // looked like an integer constant, but it could not be parsed into a valid
-    // integer (example case: it was too large for an `i32`)
-    #[serde(rename = "invalid_integer")]
+    // A control character that is not whitespace, e.g. a NUL byte (`\0`) or an escape
+    // (`\x1B`), usually from a corrupted or binary file. Its message shows the character as a
+    // code point (`U+0000`) rather than raw, since printing it could mangle the terminal.
+    // The span is that one character.
+    DisallowedControlCharacter {
+        char: char, // The control character.
+    },
+
+    // A sequence of digits that looked like an integer constant, but could not be parsed as
+    // one because it is malformed, e.g. a digit that is not valid in its base. The current
+    // constants are plain decimal digits, which always parse (or overflow, see
+    // `IntegerOverflow`), so this is kept for literal forms such as hexadecimal. Digits
+    // followed by letters, like `123abc`, are reported as `InvalidNumericLiteral` instead.
+    // The `ParseIntError` is the error's `source`.
     InvalidInteger {
-        value: String, // The string representation of the malformed integer.
-        pos: usize,    // The starting position of this malformed integer in the input.
+        value: String, // The malformed integer, exactly as written.
+        radix: u32,    // The base the digits were parsed in, e.g. 10 for decimal.
     },
 
-    // Variant representing a situation where, at the current position in the input,
-    // no defined token pattern (regex) could be matched. This is a more general
-    // error than `UnexpectedCharacter` if the lexer can't even identify a single
-    // problematic character and is simply "stuck."
-    #[serde(rename = "no_match")]
-    NoMatch {
-        pos: usize, // The position in the input string where no token rule could be applied.
+    // A well-formed integer constant whose value is out of range for the type of
+    // `Token::Constant` (currently a 32-bit signed integer, `i32`).
+    // `bits` and `max` describe the type the value had to fit in, so they follow it if
+    // constants ever get wider. The `ParseIntError` is the error's `source`.
+    IntegerOverflow {
+        value: String, // The digits of the out-of-range integer, exactly as written.
+        radix: u32,    // The base the digits were parsed in, e.g. 10 for decimal.
+        bits: u32,     // The width of the signed integer type, e.g. 32.
+        max: i64,      // The largest value of that type, e.g. 2147483647.
+    },
+
+    // A number immediately followed by a suffix it cannot have: a floating-point constant
+    // with a suffix or exponent, like `1.5f` or `1.e5`, as neither is supported (`number` is
+    // then `1.5` or `1.`). The span covers the whole literal, digits and suffix, so the
+    // offending text is `number` followed by `suffix`. Digits run together with identifier
+    // characters, like `123abc`, are reported as the more general `InvalidNumericLiteral`
+    // instead.
+    InvalidNumberSuffix {
+        number: String,      // The digits, e.g. "123".
+        suffix: String,      // The identifier characters following them, e.g. "abc".
+        suffix_start: usize, // The byte offset where the suffix starts.
+    },
+
+    // Digits immediately followed by identifier characters, like `123abc`, `0x` or `1_000`
+    // (without `LexerOptions::digit_separators`). This is neither a valid constant nor a valid
+    // identifier (identifiers cannot start with a digit, which `hint` points out). `text` is
+    // the whole run, which the span covers.
+    InvalidNumericLiteral {
+        text: String, // The offending text, e.g. "123abc".
+    },
+
+    // A block comment that is never closed: a `/*` with no `*/` anywhere after it. The error
+    // is located at the opening `/*`, and its span runs to the end of the input, covering the
+    // whole comment.
+    UnterminatedComment,
+
+    // A string (`"...`) or character (`'...`) literal whose closing quote is missing.
+    // Literals may not contain a raw line break, so the literal is considered unterminated as
+    // soon as its line ends, rather than running on to the end of the input. The error is
+    // located at the opening quote, and its span runs from there to the end of that line.
+    // `UnterminatedString` is also reported for a raw string (`R"(...`) whose closing
+    // `)delimiter"` is missing. A raw string may span lines, so that error is located at the
+    // `R` and its span runs to the end of the input.
+    UnterminatedString,
+    UnterminatedChar,
+
+    // A situation where, at the current position in the input, no defined token pattern
+    // (regex) could be matched. This is a more general error than `UnexpectedCharacter` if
+    // the lexer can't even identify a single problematic character and is simply "stuck."
+    // The lexer never produces it: at any position before the end of the input there is a
+    // character to report as `UnexpectedCharacter` (or `DisallowedControlCharacter`) instead
+    // (see `Lexer::match_token`). The kind is kept so that code E0004 stays assigned and
+    // existing `match`es and stored JSON keep working.
+    NoMatch,
+
+    // An invalid byte range passed to `Lexer::tokenize_range`: an offset past the end of the
+    // input or inside a multi-byte character, or `start > end`. Also returned by
+    // `Lexer::new_at` for such a start offset, with the range running to the end of the input.
+    // The error is located at the offending offset (clamped to the input for the line and
+    // column), and its span is empty.
+    InvalidRange {
+        range: Span, // The requested range.
+    },
+
+    // An identifier or a string literal longer than the limit set with
+    // `LexerOptions::max_identifier_len` or `LexerOptions::max_string_len`. Such a token is
+    // rejected before its text is copied, so a corrupted input cannot make the lexer (or its
+    // consumer) allocate a gigantic `String`. The span covers the whole token.
+    IdentifierTooLong {
+        len: usize, // The length of the identifier, in bytes.
+        max: usize, // The largest length allowed, in bytes.
+    },
+    StringTooLong {
+        len: usize, // The length of the string literal, quotes included, in bytes.
+        max: usize, // The largest length allowed, in bytes.
+    },
+
+    // Input that exceeds one of the resource limits set with `LexerOptions::max_input_bytes`
+    // or `LexerOptions::max_tokens` (`kind` says which). For the input size, the span runs
+    // from the limit to where the lexer had read to; for the token count, it covers the first
+    // token over the limit. The lexer stops after it.
+    ResourceLimitExceeded {
+        kind: ResourceKind, // The resource whose limit was exceeded.
+        limit: usize,       // The limit that was exceeded (in bytes or tokens).
     },
 }
 
+// `ResourceKind` names the resource limited by a `LexerErrorKind::ResourceLimitExceeded`:
+// - `InputBytes`: The number of bytes of input read (see `LexerOptions::max_input_bytes`).
+// - `Tokens`: The number of tokens produced (see `LexerOptions::max_tokens`).
+// Serialized in snake_case, e.g. `"input_bytes"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    InputBytes,
+    Tokens,
+}
+
+// `describe_radix` returns the note on a constant's base added to integer error messages, e.g.
+// " (base 16)". Decimal is what everyone expects, so it is not mentioned.
+fn describe_radix(radix: u32) -> String {
+    if radix == 10 {
+        String::new()
+    } else {
+        format!(" (base {})", radix)
+    }
+}
+
+// --- Error Context ---
+// `ErrorContext` is the source line an error occurred on, captured when the error is created.
+// - `text`: The line, without its line break. Very long lines (e.g. minified code) are cut
+//   down to at most `MAX_CONTEXT_CHARS` characters around the error, and `truncated` is set.
+// - `column`: The 0-based character index of the error's `pos` within `text`, e.g. 4 for the
+//   `$` in `int $`. It is relative to `text` (and so not affected by `LexerOptions::numbering`
+//   or `LexerOptions::tab_width`), so `text.chars().skip(column)` starts at the error.
+// - `truncated`: Whether `text` is only a part of the line. Left out of the JSON when `false`.
+// Serializes as e.g. `{"text": "int main() { return $; }", "column": 20}`, and deserializes
+// from the same (a missing `truncated` is `false`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ErrorContext {
+    pub text: String,
+    pub column: usize,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+// `MAX_CONTEXT_CHARS`: The maximum number of characters of a line kept in an `ErrorContext`.
+const MAX_CONTEXT_CHARS: usize = 120;
+
+impl ErrorContext {
+    // `new` captures the line of `source` containing the byte offset `pos`. Offsets past the
+    // end of `source` or inside a multi-byte character are moved back to the nearest character.
+    pub(crate) fn new(source: &str, pos: usize) -> Self {
+        let mut pos = pos.min(source.len());
+        while !source.is_char_boundary(pos) {
+            pos -= 1;
+        }
+        let line_start = source[..pos].rfind(['\r', '\n']).map_or(0, |i| i + 1);
+        let line_end = source[pos..]
+            .find(['\r', '\n'])
+            .map_or(source.len(), |i| pos + i);
+        let line: Vec<char> = source[line_start..line_end].chars().collect();
+        let column = source[line_start..pos].chars().count();
+        if line.len() <= MAX_CONTEXT_CHARS {
+            return ErrorContext {
+                text: line.into_iter().collect(),
+                column,
+                truncated: false,
+            };
+        }
+        // Keep a window of the line centred on the error, as far as the line allows.
+        let start = column
+            .saturating_sub(MAX_CONTEXT_CHARS / 2)
+            .min(line.len() - MAX_CONTEXT_CHARS);
+        ErrorContext {
+            text: line[start..start + MAX_CONTEXT_CHARS].iter().collect(),
+            column: column - start,
+            truncated: true,
+        }
+    }
+}
+
+// --- Helper Methods ---
+impl LexerError {
+    // `new` creates an error of the given `kind`, covering the bytes in `span`, whose start is
+    // at `line` and `column`. `context` is the source line it occurred on. The error has no
+    // `source`; `with_source` adds one.
+    pub(crate) fn new(
+        kind: LexerErrorKind,
+        span: Span,
+        (line, column): (usize, usize),
+        file: Option<String>,
+        context: ErrorContext,
+    ) -> Self {
+        LexerError {
+            kind: Box::new(kind),
+            span,
+            line,
+            column,
+            file,
+            context: Box::new(context),
+            source: None,
+        }
+    }
+
+    // `with_source` records `source` as the underlying cause of the error.
+    pub(crate) fn with_source(mut self, source: ParseIntError) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    // `message` returns the description of the error without its location, e.g.
+    // "Unexpected character '$'". `Display` and `render` add the location in their own format.
+    pub fn message(&self) -> String {
+        // `match` is used to handle each kind of error differently.
+        match self.kind() {
+            LexerErrorKind::UnexpectedCharacter { char } => {
+                format!("Unexpected character '{}'", char)
+            }
+            LexerErrorKind::DisallowedControlCharacter { char } => {
+                format!("Disallowed control character U+{:04X}", *char as u32)
+            }
+            LexerErrorKind::InvalidInteger { value, radix } => {
+                format!(
+                    "Invalid integer constant '{}'{}",
+                    value,
+                    describe_radix(*radix)
+                )
+            }
+            LexerErrorKind::IntegerOverflow {
+                value,
+                radix,
+                bits,
+                max,
+            } => format!(
+                "Integer constant '{}'{} is out of range for {}-bit signed integers (max {})",
+                value,
+                describe_radix(*radix),
+                bits,
+                max
+            ),
+            LexerErrorKind::InvalidNumberSuffix { number, suffix, .. } => {
+                format!("Invalid suffix '{}' on number '{}'", suffix, number)
+            }
+            LexerErrorKind::InvalidNumericLiteral { text } => {
+                format!("Invalid numeric literal '{}'", text)
+            }
+            LexerErrorKind::UnterminatedComment => "Unterminated block comment".to_string(),
+            LexerErrorKind::UnterminatedString => "Unterminated string literal".to_string(),
+            LexerErrorKind::UnterminatedChar => "Unterminated character literal".to_string(),
+            LexerErrorKind::NoMatch => "No token matched".to_string(),
+            LexerErrorKind::InvalidRange { range } => {
+                format!("Invalid byte range {}..{}", range.start, range.end)
+            }
+            LexerErrorKind::IdentifierTooLong { len, max } => {
+                format!(
+                    "Identifier is {} bytes long, more than the limit of {}",
+                    len, max
+                )
+            }
+            LexerErrorKind::StringTooLong { len, max } => format!(
+                "String literal is {} bytes long, more than the limit of {}",
+                len, max
+            ),
+            LexerErrorKind::ResourceLimitExceeded {
+                kind: ResourceKind::InputBytes,
+                limit,
+            } => format!("Input is longer than the limit of {} bytes", limit),
+            LexerErrorKind::ResourceLimitExceeded {
+                kind: ResourceKind::Tokens,
+                limit,
+            } => format!("Input has more tokens than the limit of {}", limit),
+        }
+    }
+
+    // `kind` returns which kind of error this is, with its kind-specific data, e.g.
+    // `LexerErrorKind::UnexpectedCharacter { char: '$' }`.
+    pub fn kind(&self) -> &LexerErrorKind {
+        &self.kind
+    }
+
+    // `pos` returns the byte offset where the error occurred (the start of its `span`).
+    pub fn pos(&self) -> usize {
+        self.span.start
+    }
+
+    // `line` returns the line number of `pos`, numbered as configured with
+    // `LexerOptions::numbering` (1-based by default).
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    // `column` returns the column number of `pos`, numbered like `line`.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    // `location` returns the `(pos, line, column)` of the error.
+    pub(crate) fn location(&self) -> (usize, usize, usize) {
+        (self.span.start, self.line, self.column)
+    }
+
+    // `span` returns the byte range of the offending text (see `LexerError`).
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    // `file` returns the name of the file the error occurred in, or `None` for unnamed input.
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    // `context` returns the source line the error occurred on (see `ErrorContext`).
+    pub fn context(&self) -> &ErrorContext {
+        &self.context
+    }
+
+    // `code` returns the error's stable code, rustc-style, e.g. "E0001" for an unexpected
+    // character. Codes let tools and documentation refer to a kind of error without matching on
+    // its message. Each code is written out explicitly (rather than derived from the kind's
+    // position) so that adding a kind never renumbers the existing ones: a new kind gets
+    // the next unused code, and codes are never reused. A new kind is also added to the
+    // schema printed by `--schema` (see `SCHEMA` in `schema.rs`).
+    pub fn code(&self) -> &'static str {
+        match *self.kind {
+            LexerErrorKind::UnexpectedCharacter { .. } => "E0001",
+            LexerErrorKind::InvalidInteger { .. } => "E0002",
+            LexerErrorKind::InvalidNumberSuffix { .. } => "E0003",
+            LexerErrorKind::NoMatch => "E0004",
+            LexerErrorKind::InvalidRange { .. } => "E0005",
+            LexerErrorKind::UnterminatedComment => "E0006",
+            LexerErrorKind::UnterminatedString => "E0007",
+            LexerErrorKind::UnterminatedChar => "E0008",
+            LexerErrorKind::InvalidNumericLiteral { .. } => "E0009",
+            LexerErrorKind::DisallowedControlCharacter { .. } => "E0010",
+            LexerErrorKind::IdentifierTooLong { .. } => "E0011",
+            LexerErrorKind::StringTooLong { .. } => "E0012",
+            LexerErrorKind::IntegerOverflow { .. } => "E0013",
+            LexerErrorKind::ResourceLimitExceeded { .. } => "E0014",
+        }
+    }
+
+    // `hint` returns a short note explaining the error at its location, shown by `render` next
+    // to the carets, e.g. "string literal starting here is never closed". Most errors are
+    // clear enough from their message alone and have no hint.
+    // An unexpected character that looks like an ASCII one (see `ascii_lookalike`), as found in
+    // code pasted from documents or chat apps, gets a hint naming the character it resembles,
+    // e.g. "did you mean '"'? this is a typographic quotation mark (U+201C)".
+    pub fn hint(&self) -> Option<String> {
+        let hint = match *self.kind {
+            LexerErrorKind::UnexpectedCharacter { char } => {
+                let (ascii, description) = ascii_lookalike(char)?;
+                return Some(format!(
+                    "did you mean '{}'? this is {} (U+{:04X})",
+                    ascii, description, char as u32
+                ));
+            }
+            LexerErrorKind::InvalidNumberSuffix { .. } => {
+                "floating-point suffixes and exponents are not supported"
+            }
+            LexerErrorKind::InvalidNumericLiteral { .. } => "identifiers cannot start with a digit",
+            LexerErrorKind::UnterminatedComment => "block comment starting here is never closed",
+            LexerErrorKind::UnterminatedString => "string literal starting here is never closed",
+            LexerErrorKind::UnterminatedChar => "character literal starting here is never closed",
+            _ => return None,
+        };
+        Some(hint.to_string())
+    }
+
+    // `to_gcc_string` formats the error on a single line in the style of GCC and Clang:
+    //
+    //   main.c:1:21: error: Unexpected character '$' [E0001]
+    //
+    // This is the shape build tools and editors (vim's quickfix list, Emacs'
+    // compilation-mode, ...) recognize to jump to the location of an error. The file name is
+    // printed as-is (spaces included); errors from unnamed input use `<input>` instead.
+    // The error's `code` follows the message in brackets, like GCC's `[-Wflag]` suffix.
+    pub fn to_gcc_string(&self) -> String {
+        let (_, line, column) = self.location();
+        format!(
+            "{}:{}:{}: error: {} [{}]",
+            self.file().unwrap_or("<input>"),
+            line,
+            column,
+            self.message(),
+            self.code()
+        )
+    }
+
+    // `render` formats the error like rustc does: the message, the location, and the
+    // offending source line with carets (`^`) under the text where the error occurred.
+    // `source` must be the input the error was produced from. For example:
+    //
+    //   error[E0001]: Unexpected character '$'
+    //    --> 1:21
+    //     |
+    //   1 | int main() { return $; }
+    //     |                     ^
+    //
+    // The `error` label carries the error's `code`. If the error has a file name, the location line shows it too: ` --> main.c:1:21`.
+    // If the error has a `hint`, it is printed after the carets.
+    // See `render::render_snippet` for how tabs, very long lines and errors at the end of
+    // the input are displayed. Tabs use the default tab width (see `render_with_tab_width`).
+    pub fn render(&self, source: &str) -> String {
+        self.render_with_tab_width(source, DEFAULT_TAB_WIDTH)
+    }
+
+    // `render_with_tab_width` works like `render`, but expands tabs in the source line to
+    // tab stops `tab_width` columns apart, so the snippet looks like it does in an editor
+    // configured with that tab width.
+    pub fn render_with_tab_width(&self, source: &str, tab_width: usize) -> String {
+        let mut rendered = render_snippet(
+            source,
+            self.span(),
+            self.file(),
+            (self.location().1, self.location().2),
+            &format!("error[{}]", self.code()),
+            &self.message(),
+            tab_width,
+        );
+        if let Some(hint) = self.hint() {
+            rendered.push(' ');
+            rendered.push_str(&hint);
+        }
+        rendered
+    }
+
+    // `render_colored` works like `render_with_tab_width`, but colors the snippet with ANSI
+    // escape codes for display in a terminal: the `error` label and the carets in red, the
+    // message in bold and the gutter dimmed. Without the escape codes, the text is identical.
+    pub fn render_colored(&self, source: &str, tab_width: usize) -> String {
+        colorize(&self.render_with_tab_width(source, tab_width), ERROR_COLOR)
+    }
+}
+
 // Implementation of the `std::fmt::Display` trait for `LexerError`.
 // The `Display` trait is used to provide a user-friendly, human-readable
 // string representation of a type. This is what gets printed when using
 // the `{}` formatting placeholder (e.g., `println!("Error: {}", my_error);`)
+// The output is the `code` in brackets, the `message` and the location, e.g.
+// "[E0001] Unexpected character '$' at line 1, column 21 (bytes 20..21)", or, for a named
+// file, "[E0001] Unexpected character '$' in main.c at line 1, column 21 (bytes 20..21)".
 impl fmt::Display for LexerError {
     // The `fmt` method takes a mutable reference to a `Formatter` and writes
     // the string representation into it.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // `match` is used to handle each variant of the `LexerError` enum differently.
-        match self {
-            // If the error is `UnexpectedCharacter`, format a specific message.
-            // `char` and `pos` are destructured from the `UnexpectedCharacter` variant.
-            LexerError::UnexpectedCharacter { char, pos } => {
-                // `write!` is a macro similar to `println!`, but it writes to the
-                // provided `Formatter` (`f`) instead of standard output.
-                write!(f, "Unexpected character '{}' at position {}", char, pos)
-            }
-
-            // If the error is `InvalidInteger`, format its specific message.
-            LexerError::InvalidInteger { value, pos } => {
-                write!(
-                    f,
-                    "Invalid integer constant '{}' at position {}",
-                    value, pos
-                )
-            }
-            // If the error is `NoMatch`, format its specific message.
-            LexerError::NoMatch { pos } => {
-                write!(f, "No token matched at position {}", pos)
-            }
+        let (_, line, column) = self.location();
+        let span = self.span();
+        write!(f, "[{}] {}", self.code(), self.message())?;
+        if let Some(file) = self.file() {
+            write!(f, " in {}", file)?;
         }
+        // `write!` is a macro similar to `println!`, but it writes to the
+        // provided `Formatter` (`f`) instead of standard output.
+        write!(
+            f,
+            " at line {}, column {} (bytes {}..{})",
+            line, column, span.start, span.end
+        )
     }
 }
 
+// `ascii_lookalike` returns the ASCII character that `ch` is commonly confused with, and a
+// description of `ch`, for characters that typically sneak into code through word processors,
+// PDFs and chat apps (which "prettify" quotes and dashes) or fullwidth input methods.
+fn ascii_lookalike(ch: char) -> Option<(char, &'static str)> {
+    let lookalike = match ch {
+        '\u{00A0}' => (' ', "a non-breaking space"),
+        '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' => ('"', "a typographic quotation mark"),
+        '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' => {
+            ('\'', "a typographic apostrophe or quotation mark")
+        }
+        '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' => ('-', "a typographic dash"),
+        '\u{2014}' | '\u{2015}' => ('-', "an em dash"),
+        '\u{2212}' => ('-', "a minus sign"),
+        '\u{037E}' => (';', "a Greek question mark"),
+        '\u{FF1B}' => (';', "a fullwidth semicolon"),
+        '\u{FF08}' => ('(', "a fullwidth parenthesis"),
+        '\u{FF09}' => (')', "a fullwidth parenthesis"),
+        '\u{FF5B}' => ('{', "a fullwidth brace"),
+        '\u{FF5D}' => ('}', "a fullwidth brace"),
+        '\u{FF0F}' => ('/', "a fullwidth slash"),
+        _ => return None,
+    };
+    Some(lookalike)
+}
+
 // Implemention of the `std::error::Error` trait for `LexerError`.
 // The `Error` trait is the base trait for all error types in Rust.
 // Implementing it allows `LexerError` to be used with Rust's standard error
 // handling mechanisms, such as the `?` operator, and to be composed with
 // other error types.
-// An empty implementation (`{}`) is often sufficient if the error type
-// doesn't need to provide a "source" for the error (i.e., it's not wrapping another error).
-impl std::error::Error for LexerError {}
+// `source` returns the underlying cause, if the error has one: for `InvalidInteger` and
+// `IntegerOverflow`, the `ParseIntError` from parsing the constant, which callers can reach
+// with `error.source()` and `downcast_ref::<ParseIntError>()`.
+impl std::error::Error for LexerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| source as &(dyn std::error::Error + 'static))
+    }
+}
+
+// Two errors are equal if they have the same kind, location and context. The `source` is not
+// compared: it follows from the kind's data, and is not kept when an error is serialized, so
+// leaving it out makes an error equal to itself after a trip through JSON.
+impl PartialEq for LexerError {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.span == other.span
+            && self.line == other.line
+            && self.column == other.column
+            && self.file == other.file
+            && self.context == other.context
+    }
+}
+
+// --- Token Parsing Errors ---
+// `ParseTokenError` is the error returned when parsing a string as a single `Token` (via
+// `str::parse::<Token>`, see the `FromStr` implementation in `token.rs`) fails.
+// - `Lexer`: The string could not be lexed at all.
+// - `Empty`: The string contains no token (it is empty, or only whitespace and comments).
+// - `MultipleTokens`: The string contains more than one token; holds how many.
+#[derive(Debug, PartialEq)]
+pub enum ParseTokenError {
+    Lexer(LexerError),
+    Empty,
+    MultipleTokens(usize),
+}
+
+// `Display` describes why the string is not a single token.
+impl fmt::Display for ParseTokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseTokenError::Lexer(error) => write!(f, "{}", error),
+            ParseTokenError::Empty => write!(f, "Expected a token, found none"),
+            ParseTokenError::MultipleTokens(count) => {
+                write!(f, "Expected a single token, found {}", count)
+            }
+        }
+    }
+}
+
+// The lexer error, if any, is exposed as the `source` of a `ParseTokenError`.
+impl std::error::Error for ParseTokenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseTokenError::Lexer(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+// --- Serialized Form ---
+// `CodedError` is the shape a `LexerError` has when serialized (see `LexerError`): its `code`
+// next to the flattened `SerializedError`. `LexerError` is converted to and from it by `serde`.
+// The `code` is skipped when deserializing, so that it is ignored as an unknown key, and errors
+// written without one, e.g. `{"unexpected_character": {...}}`, load just the same.
+#[derive(Serialize, Deserialize)]
+struct CodedError {
+    #[serde(skip_deserializing)]
+    code: &'static str,
+    #[serde(flatten)]
+    error: SerializedError,
+}
+
+// `SerializedError` holds one variant per kind, named in snake_case, with the kind's data
+// followed by the `ErrorLocation` every error has.
+#[derive(Serialize, Deserialize)]
+enum SerializedError {
+    #[serde(rename = "unexpected_character")]
+    UnexpectedCharacter {
+        char: char,
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+    #[serde(rename = "disallowed_control_character")]
+    DisallowedControlCharacter {
+        char: char,
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+    #[serde(rename = "invalid_integer")]
+    InvalidInteger {
+        value: String,
+        radix: u32,
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+    #[serde(rename = "integer_overflow")]
+    IntegerOverflow {
+        value: String,
+        radix: u32,
+        bits: u32,
+        max: i64,
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+    #[serde(rename = "invalid_number_suffix")]
+    InvalidNumberSuffix {
+        number: String,
+        suffix: String,
+        suffix_start: usize,
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+    #[serde(rename = "invalid_numeric_literal")]
+    InvalidNumericLiteral {
+        text: String,
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+    #[serde(rename = "unterminated_comment")]
+    UnterminatedComment {
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+    #[serde(rename = "unterminated_string")]
+    UnterminatedString {
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+    #[serde(rename = "unterminated_char")]
+    UnterminatedChar {
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+    #[serde(rename = "no_match")]
+    NoMatch {
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+    #[serde(rename = "invalid_range")]
+    InvalidRange {
+        range: Span,
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+    #[serde(rename = "identifier_too_long")]
+    IdentifierTooLong {
+        len: usize,
+        max: usize,
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+    #[serde(rename = "string_too_long")]
+    StringTooLong {
+        len: usize,
+        max: usize,
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+    #[serde(rename = "resource_limit_exceeded")]
+    ResourceLimitExceeded {
+        kind: ResourceKind,
+        limit: usize,
+        #[serde(flatten)]
+        at: ErrorLocation,
+    },
+}
+
+// `ErrorLocation` holds the serialized form of the data every `LexerError` has. `span` is
+// written as `pos` and `end`, and a `file` of `None` is left out.
+#[derive(Serialize, Deserialize)]
+struct ErrorLocation {
+    pos: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    context: Box<ErrorContext>,
+}
+
+impl From<LexerError> for CodedError {
+    fn from(error: LexerError) -> Self {
+        CodedError {
+            code: error.code(),
+            error: error.into(),
+        }
+    }
+}
+
+impl From<CodedError> for LexerError {
+    fn from(coded: CodedError) -> Self {
+        coded.error.into()
+    }
+}
+
+impl From<LexerError> for SerializedError {
+    fn from(error: LexerError) -> Self {
+        let at = ErrorLocation {
+            pos: error.span.start,
+            end: error.span.end,
+            line: error.line,
+            column: error.column,
+            file: error.file,
+            context: error.context,
+        };
+        match *error.kind {
+            LexerErrorKind::UnexpectedCharacter { char } => {
+                SerializedError::UnexpectedCharacter { char, at }
+            }
+            LexerErrorKind::DisallowedControlCharacter { char } => {
+                SerializedError::DisallowedControlCharacter { char, at }
+            }
+            LexerErrorKind::InvalidInteger { value, radix } => {
+                SerializedError::InvalidInteger { value, radix, at }
+            }
+            LexerErrorKind::IntegerOverflow {
+                value,
+                radix,
+                bits,
+                max,
+            } => SerializedError::IntegerOverflow {
+                value,
+                radix,
+                bits,
+                max,
+                at,
+            },
+            LexerErrorKind::InvalidNumberSuffix {
+                number,
+                suffix,
+                suffix_start,
+            } => SerializedError::InvalidNumberSuffix {
+                number,
+                suffix,
+                suffix_start,
+                at,
+            },
+            LexerErrorKind::InvalidNumericLiteral { text } => {
+                SerializedError::InvalidNumericLiteral { text, at }
+            }
+            LexerErrorKind::UnterminatedComment => SerializedError::UnterminatedComment { at },
+            LexerErrorKind::UnterminatedString => SerializedError::UnterminatedString { at },
+            LexerErrorKind::UnterminatedChar => SerializedError::UnterminatedChar { at },
+            LexerErrorKind::NoMatch => SerializedError::NoMatch { at },
+            LexerErrorKind::InvalidRange { range } => SerializedError::InvalidRange { range, at },
+            LexerErrorKind::IdentifierTooLong { len, max } => {
+                SerializedError::IdentifierTooLong { len, max, at }
+            }
+            LexerErrorKind::StringTooLong { len, max } => {
+                SerializedError::StringTooLong { len, max, at }
+            }
+            LexerErrorKind::ResourceLimitExceeded { kind, limit } => {
+                SerializedError::ResourceLimitExceeded { kind, limit, at }
+            }
+        }
+    }
+}
+
+impl From<SerializedError> for LexerError {
+    fn from(error: SerializedError) -> Self {
+        let (kind, at) = match error {
+            SerializedError::UnexpectedCharacter { char, at } => {
+                (LexerErrorKind::UnexpectedCharacter { char }, at)
+            }
+            SerializedError::DisallowedControlCharacter { char, at } => {
+                (LexerErrorKind::DisallowedControlCharacter { char }, at)
+            }
+            SerializedError::InvalidInteger { value, radix, at } => {
+                (LexerErrorKind::InvalidInteger { value, radix }, at)
+            }
+            SerializedError::IntegerOverflow {
+                value,
+                radix,
+                bits,
+                max,
+                at,
+            } => (
+                LexerErrorKind::IntegerOverflow {
+                    value,
+                    radix,
+                    bits,
+                    max,
+                },
+                at,
+            ),
+            SerializedError::InvalidNumberSuffix {
+                number,
+                suffix,
+                suffix_start,
+                at,
+            } => (
+                LexerErrorKind::InvalidNumberSuffix {
+                    number,
+                    suffix,
+                    suffix_start,
+                },
+                at,
+            ),
+            SerializedError::InvalidNumericLiteral { text, at } => {
+                (LexerErrorKind::InvalidNumericLiteral { text }, at)
+            }
+            SerializedError::UnterminatedComment { at } => {
+                (LexerErrorKind::UnterminatedComment, at)
+            }
+            SerializedError::UnterminatedString { at } => (LexerErrorKind::UnterminatedString, at),
+            SerializedError::UnterminatedChar { at } => (LexerErrorKind::UnterminatedChar, at),
+            SerializedError::NoMatch { at } => (LexerErrorKind::NoMatch, at),
+            SerializedError::InvalidRange { range, at } => {
+                (LexerErrorKind::InvalidRange { range }, at)
+            }
+            SerializedError::IdentifierTooLong { len, max, at } => {
+                (LexerErrorKind::IdentifierTooLong { len, max }, at)
+            }
+            SerializedError::StringTooLong { len, max, at } => {
+                (LexerErrorKind::StringTooLong { len, max }, at)
+            }
+            SerializedError::ResourceLimitExceeded { kind, limit, at } => {
+                (LexerErrorKind::ResourceLimitExceeded { kind, limit }, at)
+            }
+        };
+        LexerError {
+            kind: Box::new(kind),
+            span: Span::new(at.pos, at.end),
+            line: at.line,
+            column: at.column,
+            file: at.file,
+            context: at.context,
+            source: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{DEFAULT_MAX_TOKEN_LEN, Lexer, LexerBuilder};
+
+    // `lex_error` returns the first error lexing `input` with the default options.
+    fn lex_error(input: &str) -> LexerError {
+        Lexer::new(input).tokenize_all().unwrap_err()
+    }
+
+    // Errors cover the whole offending text, not just its first byte.
+    #[test]
+    fn errors_cover_the_offending_text() {
+        assert_eq!(lex_error("x \"abc\nd").span(), Span::new(2, 6));
+        assert_eq!(lex_error("x 12ab;").span(), Span::new(2, 6));
+        assert_eq!(lex_error("é €").span(), Span::new(0, 2));
+        let error = LexerBuilder::new()
+            .max_identifier_len(Some(2))
+            .build("abc")
+            .tokenize_all()
+            .unwrap_err();
+        assert_eq!(error.span(), Span::new(0, 3));
+    }
+
+    // The rendered snippet underlines the whole span.
+    #[test]
+    fn render_underlines_the_span() {
+        let source = "x 99999999999;";
+        let error = lex_error(source);
+        assert_eq!(error.span(), Span::new(2, 13));
+        assert!(error.render(source).ends_with("|   ^^^^^^^^^^^"));
+    }
+
+    // `to_gcc_string` gives the exact `file:line:col: error: message [code]` line for each
+    // kind of error, keeping spaces in the file name as they are.
+    #[test]
+    fn gcc_strings_for_each_kind() {
+        let error = |builder: LexerBuilder, input: &str| {
+            let error = builder.file_name("my file.c").build(input).tokenize_all();
+            error.unwrap_err().to_gcc_string()
+        };
+        let cases = [
+            (
+                error(LexerBuilder::new(), "x $"),
+                "my file.c:1:3: error: Unexpected character '$' [E0001]",
+            ),
+            (
+                error(LexerBuilder::new(), "x \0"),
+                "my file.c:1:3: error: Disallowed control character U+0000 [E0010]",
+            ),
+            (
+                error(LexerBuilder::new(), "x 99999999999"),
+                "my file.c:1:3: error: Integer constant '99999999999' is out of range for 32-bit signed integers (max 2147483647) [E0013]",
+            ),
+            (
+                error(LexerBuilder::new(), "x 12ab"),
+                "my file.c:1:3: error: Invalid numeric literal '12ab' [E0009]",
+            ),
+            (
+                error(LexerBuilder::new(), "x\n /* a"),
+                "my file.c:2:2: error: Unterminated block comment [E0006]",
+            ),
+            (
+                error(LexerBuilder::new(), "x \"a"),
+                "my file.c:1:3: error: Unterminated string literal [E0007]",
+            ),
+            (
+                error(LexerBuilder::new(), "x 'a"),
+                "my file.c:1:3: error: Unterminated character literal [E0008]",
+            ),
+            (
+                error(LexerBuilder::new().max_identifier_len(Some(2)), "abc"),
+                "my file.c:1:1: error: Identifier is 3 bytes long, more than the limit of 2 [E0011]",
+            ),
+            (
+                error(LexerBuilder::new().max_string_len(Some(2)), "\"abc\""),
+                "my file.c:1:1: error: String literal is 5 bytes long, more than the limit of 2 [E0012]",
+            ),
+            (
+                error(LexerBuilder::new().max_tokens(Some(1)), "a b"),
+                "my file.c:1:3: error: Input has more tokens than the limit of 1 [E0014]",
+            ),
+            (
+                LexerBuilder::new()
+                    .file_name("my file.c")
+                    .build("ab")
+                    .tokenize_range(2, 1)
+                    .unwrap_err()
+                    .to_gcc_string(),
+                "my file.c:1:3: error: Invalid byte range 2..1 [E0005]",
+            ),
+        ];
+        for (actual, expected) in cases {
+            assert_eq!(actual, expected);
+        }
+    }
+
+    // Errors without a file name are reported against `<input>`.
+    #[test]
+    fn gcc_string_without_a_file_name() {
+        assert_eq!(
+            lex_error("x $").to_gcc_string(),
+            "<input>:1:3: error: Unexpected character '$' [E0001]"
+        );
+    }
+
+    // An unclosed literal is reported at its opening quote and ends with its line, rather
+    // than running on to the end of the input; the rendering says where it starts.
+    #[test]
+    fn unterminated_literals_stop_at_the_line_break() {
+        let source = "x \"abc\ny;";
+        let error = lex_error(source);
+        assert_eq!(error.kind(), &LexerErrorKind::UnterminatedString);
+        assert_eq!(error.span(), Span::new(2, 6));
+        assert!(
+            error
+                .render(source)
+                .ends_with("|   ^^^^ string literal starting here is never closed")
+        );
+
+        let error = lex_error("x 'a\r\nb");
+        assert_eq!(error.kind(), &LexerErrorKind::UnterminatedChar);
+        assert_eq!(error.span(), Span::new(2, 4));
+        assert_eq!(
+            error.hint().as_deref(),
+            Some("character literal starting here is never closed")
+        );
+    }
+
+    // Digits followed by identifier characters are one malformed literal, covered whole,
+    // with a hint explaining why it is neither a number nor an identifier. A float with a
+    // suffix gets a hint of its own.
+    #[test]
+    fn malformed_number_literals() {
+        let cases = [
+            ("x 0x;", "0x"),
+            ("x 1_000;", "1_000"),
+            ("x 123abc;", "123abc"),
+        ];
+        for (source, literal) in cases {
+            let error = lex_error(source);
+            assert_eq!(
+                error.kind(),
+                &LexerErrorKind::InvalidNumericLiteral {
+                    text: literal.to_string()
+                }
+            );
+            assert_eq!(error.span(), Span::new(2, 2 + literal.len()));
+            assert_eq!(
+                error.message(),
+                format!("Invalid numeric literal '{}'", literal)
+            );
+            assert_eq!(
+                error.hint().as_deref(),
+                Some("identifiers cannot start with a digit")
+            );
+        }
+        let error = lex_error("x 1.5f;");
+        assert_eq!(
+            error.kind(),
+            &LexerErrorKind::InvalidNumberSuffix {
+                number: "1.5".to_string(),
+                suffix: "f".to_string(),
+                suffix_start: 5,
+            }
+        );
+        assert_eq!(error.span(), Span::new(2, 6));
+        assert_eq!(
+            error.hint().as_deref(),
+            Some("floating-point suffixes and exponents are not supported")
+        );
+    }
+
+    // `every_kind` returns one error kind of each variant, with the code it must have.
+    fn every_kind() -> Vec<(LexerErrorKind, &'static str)> {
+        vec![
+            (LexerErrorKind::UnexpectedCharacter { char: '$' }, "E0001"),
+            (
+                LexerErrorKind::InvalidInteger {
+                    value: "0x".to_string(),
+                    radix: 16,
+                },
+                "E0002",
+            ),
+            (
+                LexerErrorKind::InvalidNumberSuffix {
+                    number: "1.5".to_string(),
+                    suffix: "f".to_string(),
+                    suffix_start: 3,
+                },
+                "E0003",
+            ),
+            (LexerErrorKind::NoMatch, "E0004"),
+            (
+                LexerErrorKind::InvalidRange {
+                    range: Span::new(1, 0),
+                },
+                "E0005",
+            ),
+            (LexerErrorKind::UnterminatedComment, "E0006"),
+            (LexerErrorKind::UnterminatedString, "E0007"),
+            (LexerErrorKind::UnterminatedChar, "E0008"),
+            (
+                LexerErrorKind::InvalidNumericLiteral {
+                    text: "1x".to_string(),
+                },
+                "E0009",
+            ),
+            (
+                LexerErrorKind::DisallowedControlCharacter { char: '\0' },
+                "E0010",
+            ),
+            (
+                LexerErrorKind::IdentifierTooLong { len: 3, max: 2 },
+                "E0011",
+            ),
+            (LexerErrorKind::StringTooLong { len: 3, max: 2 }, "E0012"),
+            (
+                LexerErrorKind::IntegerOverflow {
+                    value: "2147483648".to_string(),
+                    radix: 10,
+                    bits: 32,
+                    max: 2147483647,
+                },
+                "E0013",
+            ),
+            (
+                LexerErrorKind::ResourceLimitExceeded {
+                    kind: ResourceKind::Tokens,
+                    limit: 1,
+                },
+                "E0014",
+            ),
+        ]
+    }
+
+    // Every kind keeps the code it was given; codes are never renumbered.
+    #[test]
+    fn every_error_code_is_pinned() {
+        for (kind, code) in every_kind() {
+            let error = LexerError::new(
+                kind,
+                Span::new(0, 1),
+                (1, 1),
+                None,
+                ErrorContext::new("", 0),
+            );
+            assert_eq!(error.code(), code);
+            assert!(error.to_string().starts_with(&format!("[{}] ", code)));
+        }
+    }
+
+    // The serialized error carries its code next to the variant.
+    #[test]
+    fn serialized_errors_carry_their_code() {
+        assert_eq!(
+            serde_json::to_string(&lex_error("x 1x")).unwrap(),
+            r#"{"code":"E0009","invalid_numeric_literal":{"text":"1x","pos":2,"end":4,"line":1,"column":3,"context":{"text":"x 1x","column":2}}}"#
+        );
+    }
+
+    // Lookalikes of ASCII characters get a hint naming the character they resemble.
+    #[test]
+    fn lookalike_hints() {
+        let cases = [
+            (
+                "\u{201C}",
+                "did you mean '\"'? this is a typographic quotation mark (U+201C)",
+            ),
+            (
+                "\u{2019}",
+                "did you mean '''? this is a typographic apostrophe or quotation mark (U+2019)",
+            ),
+            (
+                "\u{2013}",
+                "did you mean '-'? this is a typographic dash (U+2013)",
+            ),
+            (
+                "\u{A0}",
+                "did you mean ' '? this is a non-breaking space (U+00A0)",
+            ),
+            (
+                "\u{FF1B}",
+                "did you mean ';'? this is a fullwidth semicolon (U+FF1B)",
+            ),
+        ];
+        for (ch, hint) in cases {
+            let source = format!("x{}1", ch);
+            let error = lex_error(&source);
+            let char = ch.chars().next().unwrap();
+            assert_eq!(error.kind(), &LexerErrorKind::UnexpectedCharacter { char });
+            assert_eq!(error.span(), Span::new(1, 1 + ch.len()));
+            assert_eq!(error.hint().as_deref(), Some(hint));
+        }
+        assert_eq!(lex_error("x é").hint(), None);
+    }
+
+    // A non-breaking space is not skipped as whitespace, even between tokens on one line.
+    #[test]
+    fn non_breaking_space_is_not_whitespace() {
+        let error = lex_error("int\u{A0}x;");
+        assert_eq!(error.span(), Span::new(3, 5));
+        let mut lexer = LexerBuilder::new().emit_newlines(true).build("x \u{A0}");
+        assert_eq!(lexer.tokenize_all().unwrap_err().span(), Span::new(2, 4));
+        // Other Unicode spaces still are.
+        assert!(Lexer::new("int\u{2003}x;").tokenize_all().is_ok());
+    }
+
+    // Control characters are shown as code points, never raw, wherever they appear.
+    #[test]
+    fn control_characters_are_escaped() {
+        let error = lex_error("\0int x;");
+        assert_eq!(
+            error.kind(),
+            &LexerErrorKind::DisallowedControlCharacter { char: '\0' }
+        );
+        assert_eq!(error.span(), Span::new(0, 1));
+        assert_eq!(error.message(), "Disallowed control character U+0000");
+
+        let error = lex_error("int x;\n\x1B[0m");
+        assert_eq!(error.span(), Span::new(7, 8));
+        assert_eq!((error.line(), error.column()), (2, 1));
+        assert_eq!(
+            error.to_string(),
+            "[E0010] Disallowed control character U+001B at line 2, column 1 (bytes 7..8)"
+        );
+        // Whitespace control characters are still skipped.
+        assert!(Lexer::new("int\t\x0B\x0Cx;").tokenize_all().is_ok());
+    }
+
+    // The context is the error's line, without its line break, with the error's column in it.
+    #[test]
+    fn context_is_the_offending_line() {
+        let error = lex_error("int x;\r\nint /* é */ $;\r\nint y;");
+        assert_eq!(
+            error.context(),
+            &ErrorContext {
+                text: "int /* é */ $;".to_string(),
+                column: 12,
+                truncated: false,
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(error.context()).unwrap(),
+            r#"{"text":"int /* é */ $;","column":12}"#
+        );
+    }
+
+    // Very long lines are cut down to a window around the error.
+    #[test]
+    fn context_of_long_lines_is_truncated() {
+        let source = format!("{}$;{}", "a ".repeat(100), " b".repeat(100));
+        let context = lex_error(&source).context().clone();
+        assert!(context.truncated);
+        assert_eq!(context.text.chars().count(), MAX_CONTEXT_CHARS);
+        assert!(
+            context
+                .text
+                .chars()
+                .skip(context.column)
+                .collect::<String>()
+                .starts_with("$;")
+        );
+        let json = serde_json::to_value(&context).unwrap();
+        assert_eq!(json["truncated"], true);
+        // Near the start of the line, the window starts with the line.
+        let context = ErrorContext::new(&source, 0);
+        assert_eq!((context.column, context.text.len()), (0, MAX_CONTEXT_CHARS));
+    }
+
+    // The accessors give the same view of every kind of error.
+    #[test]
+    fn accessors_on_every_kind() {
+        let error = |builder: LexerBuilder, input: &str| {
+            builder
+                .file_name("a.c")
+                .build(input)
+                .tokenize_all()
+                .unwrap_err()
+        };
+        let cases = [
+            (
+                error(LexerBuilder::new(), "x $"),
+                2..3,
+                "Unexpected character '$'",
+            ),
+            (
+                error(LexerBuilder::new(), "x \0"),
+                2..3,
+                "Disallowed control character U+0000",
+            ),
+            (
+                error(LexerBuilder::new(), "x 12ab"),
+                2..6,
+                "Invalid numeric literal '12ab'",
+            ),
+            (
+                error(LexerBuilder::new(), "x /* a"),
+                2..6,
+                "Unterminated block comment",
+            ),
+            (
+                error(LexerBuilder::new(), "x \"a"),
+                2..4,
+                "Unterminated string literal",
+            ),
+            (
+                error(LexerBuilder::new(), "x 'a"),
+                2..4,
+                "Unterminated character literal",
+            ),
+            (
+                error(LexerBuilder::new().max_identifier_len(Some(2)), "x abc"),
+                2..5,
+                "Identifier is 3 bytes long, more than the limit of 2",
+            ),
+            (
+                error(LexerBuilder::new().max_tokens(Some(1)), "x abc"),
+                2..5,
+                "Input has more tokens than the limit of 1",
+            ),
+        ];
+        for (error, range, message) in cases {
+            assert_eq!(error.pos(), range.start);
+            assert_eq!(error.span(), Span::from(range));
+            assert_eq!(error.message(), message);
+            assert_eq!((error.line(), error.column()), (1, error.pos() + 1));
+            assert_eq!(error.file(), Some("a.c"));
+            assert_eq!(error.context().column, error.pos());
+            // Downstream code matches on the kind, with a wildcard arm.
+            let unexpected = match error.kind() {
+                LexerErrorKind::UnexpectedCharacter { char } => Some(*char),
+                _ => None,
+            };
+            assert_eq!(unexpected.is_some(), message.starts_with("Unexpected"));
+        }
+    }
+
+    // `2147483647` is the largest constant; anything above it overflows, however long.
+    #[test]
+    fn integer_overflow_boundary() {
+        let tokens = Lexer::new("2147483647").tokenize_all().unwrap();
+        assert_eq!(tokens[0].as_constant(), Some(i32::MAX));
+
+        let error = lex_error("x 2147483648;");
+        assert_eq!(
+            error.kind(),
+            &LexerErrorKind::IntegerOverflow {
+                value: "2147483648".to_string(),
+                radix: 10,
+                bits: 32,
+                max: 2147483647,
+            }
+        );
+        assert_eq!(error.code(), "E0013");
+        assert_eq!(error.span(), Span::new(2, 12));
+        assert_eq!(
+            error.message(),
+            "Integer constant '2147483648' is out of range for 32-bit signed integers (max 2147483647)"
+        );
+
+        let digits = "1".repeat(100);
+        let error = lex_error(&digits);
+        assert_eq!(error.code(), "E0013");
+        assert_eq!(error.span(), Span::new(0, 100));
+    }
+
+    // Every kind of error reads back from its JSON as the same error, location included.
+    #[test]
+    fn errors_round_trip_through_json() {
+        for (kind, _) in every_kind() {
+            let error = LexerError::new(
+                kind,
+                Span::new(4, 9),
+                (2, 3),
+                Some("my file.c".to_string()),
+                ErrorContext::new("int x;\nab $ cd", 10),
+            );
+            let json = serde_json::to_string(&error).unwrap();
+            let decoded: LexerError = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded, error);
+            assert_eq!(decoded.span(), error.span());
+            assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+        }
+        // A lexed error too; its `source` is not serialized, which `==` does not compare.
+        let error = lex_error("x 2147483648");
+        assert!(std::error::Error::source(&error).is_some());
+        let json = serde_json::to_string(&error).unwrap();
+        let decoded: LexerError = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, error);
+        assert!(std::error::Error::source(&decoded).is_none());
+    }
+
+    // The `code` is skipped when reading an error back, and may be left out entirely.
+    #[test]
+    fn deserializing_ignores_the_code() {
+        let error = lex_error("x $");
+        let json = r#"{"unexpected_character":{"char":"$","pos":2,"end":3,"line":1,"column":3,"context":{"text":"x $","column":2}}}"#;
+        assert_eq!(serde_json::from_str::<LexerError>(json).unwrap(), error);
+        let json = json.replacen('{', r#"{"code":"E0042","#, 1);
+        assert_eq!(serde_json::from_str::<LexerError>(&json).unwrap(), error);
+    }
+
+    // Identifiers and string literals may be exactly as long as their limit, but no longer.
+    #[test]
+    fn token_length_limits() {
+        let lex = |input: &str| {
+            LexerBuilder::new()
+                .max_identifier_len(Some(4))
+                .max_string_len(Some(6))
+                .build(input)
+                .tokenize_all()
+        };
+        assert!(lex("abcd \"abcd\"").is_ok());
+        assert_eq!(
+            lex("x abcde").unwrap_err().kind(),
+            &LexerErrorKind::IdentifierTooLong { len: 5, max: 4 }
+        );
+        let error = lex("x \"abcde\"").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &LexerErrorKind::StringTooLong { len: 7, max: 6 }
+        );
+        assert_eq!(error.span(), Span::new(2, 9));
+        // Without a limit, any length is fine.
+        let long = "a".repeat(DEFAULT_MAX_TOKEN_LEN + 1);
+        let mut lexer = LexerBuilder::new().max_identifier_len(None).build(&long);
+        assert!(lexer.tokenize_all().is_ok());
+        assert_eq!(
+            lex_error(&long).kind(),
+            &LexerErrorKind::IdentifierTooLong {
+                len: DEFAULT_MAX_TOKEN_LEN + 1,
+                max: DEFAULT_MAX_TOKEN_LEN
+            }
+        );
+    }
+
+    // `NoMatch` is never produced by the lexer, but its message and code stay pinned.
+    #[test]
+    fn no_match_message() {
+        let error = LexerError::new(
+            LexerErrorKind::NoMatch,
+            Span::new(4, 4),
+            (1, 5),
+            None,
+            ErrorContext::new("int ", 4),
+        );
+        assert_eq!(error.code(), "E0004");
+        assert_eq!(error.message(), "No token matched");
+        assert_eq!(
+            error.to_string(),
+            "[E0004] No token matched at line 1, column 5 (bytes 4..4)"
+        );
+    }
+
+    // A constant that does not fit keeps the `ParseIntError` behind it as its `source`;
+    // errors without such a cause have none.
+    #[test]
+    fn source_is_the_parse_int_error() {
+        use std::error::Error;
+        use std::num::IntErrorKind;
+
+        let error = lex_error("x 99999999999");
+        let source = error.source().expect("overflow has a source");
+        let parse_error = source.downcast_ref::<ParseIntError>().unwrap();
+        assert_eq!(parse_error.kind(), &IntErrorKind::PosOverflow);
+        assert!(lex_error("x $").source().is_none());
+    }
+
+    // The schema printed by `--schema` lists every error kind, in code order, under the name
+    // it is serialized with.
+    #[test]
+    fn schema_lists_every_error_kind() {
+        let kinds = every_kind();
+        assert_eq!(crate::lexer::SCHEMA.errors.len(), kinds.len());
+        for ((kind, code), schema) in kinds.into_iter().zip(crate::lexer::SCHEMA.errors) {
+            let error = LexerError::new(
+                kind,
+                Span::new(0, 1),
+                (1, 1),
+                None,
+                ErrorContext::new("", 0),
+            );
+            let json = serde_json::to_value(&error).unwrap();
+            assert!(
+                json.get(schema.name).is_some(),
+                "{} is not {}",
+                json,
+                schema.name
+            );
+            assert_eq!(schema.code, code);
+            assert!(
+                schema
+                    .shape
+                    .starts_with(&format!("{{\"{}\": ", schema.name))
+            );
+        }
+    }
+}
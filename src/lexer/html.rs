@@ -0,0 +1,104 @@
+// Import the `Token` enum, whose category picks the CSS class of each highlighted token.
+use super::token::Token;
+
+// Import the `Span` struct, which locates each token in the source.
+use super::span::Span;
+
+// `to_html` writes `source` as syntax-highlighted HTML, for embedding lexed code on a web page.
+// `tokens` are the spanned tokens lexed from `source` (see `Lexer::tokenize_all_spanned`),
+// in order. Each token's source text is wrapped in a `<span>` whose class names its
+// category (see `css_class`), e.g. `int x;` becomes:
+//
+//   <pre class="obv-lexer"><code><span class="kw">int</span> <span class="ident">x</span><span class="punct">;</span></code></pre>
+//
+// Everything between the tokens (whitespace, and comments unless they were lexed as
+// `Token::Comment`s) is copied as it is, so the page shows exactly the original source.
+// `<`, `>` and `&` are escaped everywhere.
+pub fn to_html(source: &str, tokens: &[(Token, Span)]) -> String {
+    let mut out = String::from("<pre class=\"obv-lexer\"><code>");
+    let mut copied = 0;
+    for (token, span) in tokens {
+        escape_into(&mut out, &source[copied..span.start]);
+        let text = &source[span.start..span.end];
+        match css_class(token) {
+            Some(class) => {
+                out.push_str("<span class=\"");
+                out.push_str(class);
+                out.push_str("\">");
+                escape_into(&mut out, text);
+                out.push_str("</span>");
+            }
+            None => escape_into(&mut out, text),
+        }
+        copied = span.end;
+    }
+    escape_into(&mut out, &source[copied..]);
+    out.push_str("</code></pre>");
+    out
+}
+
+// `css_class` returns the class of the `<span>` around a token of the given category:
+// `kw`, `ident`, `num`, `str`, `char`, `punct`, `comment`, `directive`, `error` or `custom`.
+// Newlines are not wrapped, as there is nothing to highlight.
+fn css_class(token: &Token) -> Option<&'static str> {
+    match token.category() {
+        "keyword" => Some("kw"),
+        "identifier" => Some("ident"),
+        "constant" => Some("num"),
+        "string" => Some("str"),
+        "char" => Some("char"),
+        "punctuation" => Some("punct"),
+        "comment" => Some("comment"),
+        "directive" => Some("directive"),
+        "error" => Some("error"),
+        "custom" => Some("custom"),
+        _ => None,
+    }
+}
+
+// `escape_into` appends `text` to `out`, escaping the characters that are special in HTML text.
+fn escape_into(out: &mut String, text: &str) {
+    for ch in text.chars() {
+        match ch {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            _ => out.push(ch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{Lexer, LexerBuilder};
+
+    // `html` lexes `source` with `lexer` and highlights it.
+    fn html(lexer: &mut Lexer, source: &str) -> String {
+        to_html(source, &lexer.tokenize_all_spanned().unwrap())
+    }
+
+    // Each token is wrapped in the span of its category, in order.
+    #[test]
+    fn int_x() {
+        assert_eq!(
+            html(&mut Lexer::new("int x;"), "int x;"),
+            "<pre class=\"obv-lexer\"><code><span class=\"kw\">int</span> <span class=\"ident\">x</span><span class=\"punct\">;</span></code></pre>"
+        );
+    }
+
+    // Trivia is copied as is, and `<`, `>` and `&` are escaped everywhere.
+    #[test]
+    fn trivia_is_kept_and_escaped() {
+        let source = "#include <a&b>\n/* <x> */ 1\n";
+        assert_eq!(
+            html(&mut Lexer::new(source), source),
+            "<pre class=\"obv-lexer\"><code><span class=\"directive\">#include &lt;a&amp;b&gt;</span>\n/* &lt;x&gt; */ <span class=\"num\">1</span>\n</code></pre>"
+        );
+        let mut lexer = LexerBuilder::new().keep_comments(true).build("// c");
+        assert_eq!(
+            html(&mut lexer, "// c"),
+            "<pre class=\"obv-lexer\"><code><span class=\"comment\">// c</span></code></pre>"
+        );
+    }
+}
@@ -0,0 +1,117 @@
+// Import `HashMap`, used to find the symbol of a string that was interned before.
+use std::collections::HashMap;
+
+// Import the `Serialize` trait, so symbols can be written out (as plain numbers).
+use serde::Serialize;
+
+// Import the token types an `InternedToken` is built from.
+use super::token::{Token, TokenRef};
+
+// --- Symbols ---
+// A `Symbol` is the id of a string stored in a `StringInterner`. Equal strings always get the
+// same symbol from the same interner, so comparing two identifiers is a cheap integer
+// comparison, and each distinct identifier is stored only once however often it occurs.
+// `StringInterner::resolve` turns a symbol back into its text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct Symbol(pub u32);
+
+// --- String Interner ---
+// `StringInterner` stores each distinct string once and hands out a `Symbol` for it.
+#[derive(Clone, Debug, Default)]
+pub struct StringInterner {
+    // `symbols`: Maps every interned string to its symbol.
+    symbols: HashMap<String, Symbol>,
+
+    // `strings`: The interned strings, indexed by symbol id.
+    strings: Vec<String>,
+}
+
+impl StringInterner {
+    // Creates an empty interner.
+    pub fn new() -> Self {
+        StringInterner::default()
+    }
+
+    // `intern` returns the symbol for `text`, storing `text` first if it hasn't been seen yet.
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(text) {
+            return symbol;
+        }
+        let symbol = Symbol(self.strings.len() as u32);
+        self.strings.push(text.to_string());
+        self.symbols.insert(text.to_string(), symbol);
+        symbol
+    }
+
+    // `resolve` returns the text of `symbol`, or `None` if it doesn't come from this interner.
+    pub fn resolve(&self, symbol: Symbol) -> Option<&str> {
+        self.strings.get(symbol.0 as usize).map(String::as_str)
+    }
+
+    // `len` returns the number of distinct strings interned so far.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    // `is_empty` returns `true` if nothing has been interned yet.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+// --- Interned Tokens ---
+// `InternedToken` is the token type produced by `Lexer::tokenize_interned`: identifiers hold
+// a `Symbol` into the interner returned alongside the tokens, instead of their own `String`.
+// All other tokens are kept as regular `Token`s in `Other`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InternedToken {
+    Identifier(Symbol), // An identifier; `StringInterner::resolve` gives its name.
+    Other(Token),       // Any other token.
+}
+
+impl InternedToken {
+    // `from_ref` converts a borrowed token, interning its name if it is an identifier.
+    pub(crate) fn from_ref(token: TokenRef<'_>, interner: &mut StringInterner) -> Self {
+        match token {
+            TokenRef::Identifier(name) => InternedToken::Identifier(interner.intern(name)),
+            other => InternedToken::Other(other.into_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    // Equal strings get the same symbol, which resolves back to the string.
+    #[test]
+    fn intern_and_resolve() {
+        let mut interner = StringInterner::new();
+        assert!(interner.is_empty());
+        let foo = interner.intern("foo");
+        let bar = interner.intern("bar");
+        assert_eq!(interner.intern("foo"), foo);
+        assert_ne!(foo, bar);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.resolve(foo), Some("foo"));
+        assert_eq!(interner.resolve(Symbol(2)), None);
+    }
+
+    // `foo foo` gives two identifiers with the same symbol and a single interned string.
+    #[test]
+    fn repeated_identifiers_share_a_symbol() {
+        let (tokens, interner) = Lexer::new("foo foo;").tokenize_interned().unwrap();
+        assert_eq!(interner.len(), 1);
+        let symbol = Symbol(0);
+        assert_eq!(
+            tokens,
+            vec![
+                InternedToken::Identifier(symbol),
+                InternedToken::Identifier(symbol),
+                InternedToken::Other(Token::Semicolon),
+            ]
+        );
+        assert_eq!(interner.resolve(symbol), Some("foo"));
+    }
+}
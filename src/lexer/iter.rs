@@ -0,0 +1,139 @@
+// Import the `Lexer` struct, which the iterator drives.
+use super::core::Lexer;
+
+// Import the item types the iterator yields.
+use super::error::LexerError;
+use super::token::Token;
+
+// --- Token Iterator ---
+// `Tokens` is a borrowing iterator over the tokens of a `Lexer`, created with `Lexer::tokens`.
+// Unlike `tokenize_all`, it never collects the tokens into a `Vec`, so each token can be
+// processed (e.g. written out) and dropped before the next one is produced. This keeps memory
+// use flat even for very large inputs.
+//
+// Each item is a `Result<Token, LexerError>`. After yielding an error the iterator ends,
+// mirroring `tokenize_all`, which stops at the first error.
+// - `'l`: The lifetime of the mutable borrow of the lexer.
+// - `'a`: The lifetime of the input the lexer is reading.
+pub struct Tokens<'l, 'a> {
+    // `lexer`: The lexer producing the tokens. It is borrowed mutably because producing a token
+    // advances its position; once the iterator is dropped, the lexer can be used again.
+    lexer: &'l mut Lexer<'a>,
+
+    // `done`: Set once an error has been yielded, so that the iterator does not keep
+    // returning the same error (the lexer does not advance past the offending input).
+    done: bool,
+}
+
+impl<'l, 'a> Tokens<'l, 'a> {
+    pub(crate) fn new(lexer: &'l mut Lexer<'a>) -> Self {
+        Tokens { lexer, done: false }
+    }
+}
+
+impl Iterator for Tokens<'_, '_> {
+    type Item = Result<Token, LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let item = self.lexer.next_token_internal();
+        if let Some(Err(_)) = item {
+            self.done = true;
+        }
+        item
+    }
+}
+
+// `&mut Lexer` can be used directly in a `for` loop (`for token in &mut lexer { ... }`),
+// which is the same as iterating over `lexer.tokens()`. The lexer is usable again afterwards.
+impl<'l, 'a> IntoIterator for &'l mut Lexer<'a> {
+    type Item = Result<Token, LexerError>;
+    type IntoIter = Tokens<'l, 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tokens()
+    }
+}
+
+// --- Owning Token Iterator ---
+// `IntoTokens` is the consuming counterpart of `Tokens`: it takes ownership of the lexer,
+// which makes `for token in lexer { ... }` possible when the lexer isn't needed afterwards
+// (e.g. when it was created just for the loop, or is returned from a function).
+// It yields the same items as `Tokens` and also ends after the first error.
+pub struct IntoTokens<'a> {
+    // `lexer`: The lexer producing the tokens, owned by the iterator.
+    lexer: Lexer<'a>,
+
+    // `done`: Set once an error has been yielded (see `Tokens::done`).
+    done: bool,
+}
+
+impl<'a> IntoIterator for Lexer<'a> {
+    type Item = Result<Token, LexerError>;
+    type IntoIter = IntoTokens<'a>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        // Like `Lexer::tokens`, start with any tokens that were peeked at.
+        self.unpeek();
+        IntoTokens {
+            lexer: self,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for IntoTokens<'_> {
+    type Item = Result<Token, LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let item = self.lexer.next_token_internal();
+        if let Some(Err(_)) = item {
+            self.done = true;
+        }
+        item
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The iterator yields the same tokens as `tokenize_all`.
+    #[test]
+    fn tokens_match_tokenize_all() {
+        let source = "int main() { return 0; }";
+        let streamed: Result<Vec<Token>, LexerError> = Lexer::new(source).tokens().collect();
+        assert_eq!(streamed, Lexer::new(source).tokenize_all());
+    }
+
+    // The iterator ends after the first error instead of repeating it.
+    #[test]
+    fn tokens_end_after_an_error() {
+        let mut lexer = Lexer::new("x $ y");
+        let items: Vec<_> = lexer.tokens().collect();
+        assert_eq!(items.len(), 2);
+        assert!(items[1].is_err());
+    }
+
+    // A lexer can be iterated by `&mut` (and reused afterwards) or by value.
+    #[test]
+    fn lexers_work_in_for_loops() {
+        let mut lexer = Lexer::new("int x");
+        let mut count = 0;
+        for token in &mut lexer {
+            token.unwrap();
+            count += 1;
+        }
+        assert_eq!(count, 2);
+        assert!(lexer.is_at_end());
+
+        let owned: Vec<_> = Lexer::new("x $ y").into_iter().collect();
+        assert_eq!(owned.len(), 2);
+        assert!(owned[1].is_err());
+    }
+}
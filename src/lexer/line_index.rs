@@ -0,0 +1,460 @@
+// Import the `UnicodeSegmentation` trait, which adds `grapheme_indices` to `str`.
+// It is used to count columns in grapheme clusters (see `ColumnMode::Grapheme`).
+use unicode_segmentation::UnicodeSegmentation;
+
+// --- Line Index ---
+// `LineIndex` converts between byte offsets (which the lexer reports in `Span`s and errors)
+// and human-readable 1-based line/column positions (which editors and people use).
+//
+// It is built once from the source text by recording the byte offset at which every line
+// starts. Converting an offset is then a binary search over those starts, instead of
+// re-scanning the source from the beginning for every lookup.
+//
+// Lines end at `\n` (Unix), `\r\n` (Windows) or a lone `\r` (classic Mac OS). Each of these
+// counts as exactly one line break; a `\r\n` pair is never counted twice (see `ends_line_at`).
+// There are three ways to count the column of an offset:
+// - Character column (`line_col`): counts characters (Unicode scalar values), matching the
+//   `column` fields of `LexerError`. A `é` earlier on the line occupies one column even
+//   though it is two bytes long.
+// - Byte column (`line_col_bytes`): counts bytes, for tools that address text by bytes
+//   (e.g. some editor protocols).
+// - Visual column (`visual_col`): like the character column, except that a tab advances the
+//   column to the next tab stop, i.e. to the next multiple of the tab width (plus one).
+//   This is where the character *appears* in an editor configured with that tab width.
+//   With the default tab width of 1, visual and character columns are identical.
+// The first two (plus grapheme columns) can also be selected at runtime with `ColumnMode`
+// and `line_col_with`.
+//
+// `#[derive(...)]`:
+// - `Clone`: Allows an index to be copied, e.g. to hand one to several diagnostics consumers.
+// - `Debug`: Allows the index to be printed with `{:?}` for debugging.
+#[derive(Clone, Debug)]
+pub struct LineIndex<'a> {
+    // `source`: The text this index was built from. It is needed to count the characters
+    // between the start of a line and an offset.
+    source: &'a str,
+
+    // `line_starts`: The byte offset at which each line begins, in ascending order.
+    // The first entry is always `0`, so the vector is never empty.
+    line_starts: Vec<usize>,
+
+    // `tab_width`: The distance between tab stops used for visual columns (see `visual_col`).
+    tab_width: usize,
+}
+
+// `DEFAULT_TAB_WIDTH`: The tab width used unless another one is configured. A width of 1
+// makes a tab count as a single column, like any other character.
+pub const DEFAULT_TAB_WIDTH: usize = 1;
+
+impl<'a> LineIndex<'a> {
+    // Builds the index for `source` with a single pass over its bytes.
+    pub fn new(source: &'a str) -> Self {
+        LineIndex::with_tab_width(source, DEFAULT_TAB_WIDTH)
+    }
+
+    // Builds the index for `source`, using `tab_width` for visual columns (e.g. 4 or 8).
+    // A `tab_width` of 0 is treated as 1.
+    pub fn with_tab_width(source: &'a str, tab_width: usize) -> Self {
+        let mut line_starts = vec![0];
+        for offset in 0..source.len() {
+            if ends_line_at(source, offset) {
+                line_starts.push(offset + 1);
+            }
+        }
+        LineIndex {
+            source,
+            line_starts,
+            tab_width: tab_width.max(1),
+        }
+    }
+
+    // `line_col` returns the 1-based `(line, column)` of the byte `offset`.
+    // - Offsets past the end of the source are clamped to the end, i.e. they report the
+    //   position just after the last character.
+    // - Offsets inside a multi-byte UTF-8 character report the column of that character.
+    pub fn line_col(&self, offset: usize) -> (u32, u32) {
+        let (line, offset) = self.locate(offset);
+        let column = count_columns(&self.source[self.line_starts[line]..offset]);
+        (line as u32 + 1, column as u32 + 1)
+    }
+
+    // `line_col_bytes` works like `line_col`, but the 1-based column counts bytes from the
+    // start of the line rather than characters. For ASCII-only lines both columns are equal.
+    pub fn line_col_bytes(&self, offset: usize) -> (u32, u32) {
+        let (line, offset) = self.locate(offset);
+        let column = offset - self.line_starts[line];
+        (line as u32 + 1, column as u32 + 1)
+    }
+
+    // `line_col_with` returns the 1-based `(line, column)` of the byte `offset`, counting the
+    // column in the units selected by `mode` (see `ColumnMode`). `ColumnMode::Char` gives the
+    // same result as `line_col`, and `ColumnMode::Byte` the same as `line_col_bytes`.
+    pub fn line_col_with(&self, offset: usize, mode: ColumnMode) -> (u32, u32) {
+        match mode {
+            ColumnMode::Byte => self.line_col_bytes(offset),
+            ColumnMode::Char => self.line_col(offset),
+            ColumnMode::Grapheme => {
+                let (line, offset) = self.locate(offset);
+                let line_start = self.line_starts[line];
+                // Count the clusters that end at or before `offset`. An offset inside a cluster
+                // (e.g. pointing at a combining accent) reports the column of that cluster.
+                let column = self.source[line_start..]
+                    .grapheme_indices(true)
+                    .take_while(|(index, cluster)| line_start + index + cluster.len() <= offset)
+                    .count();
+                (line as u32 + 1, column as u32 + 1)
+            }
+        }
+    }
+
+    // `visual_col` works like `line_col`, but the 1-based column is the visual column:
+    // every tab before `offset` on its line advances to the next tab stop.
+    // E.g. with a tab width of 4, the `x` in `\t\tx` is at visual column 9.
+    pub fn visual_col(&self, offset: usize) -> (u32, u32) {
+        let (line, offset) = self.locate(offset);
+        let column = visual_width(&self.source[self.line_starts[line]..offset], self.tab_width);
+        (line as u32 + 1, column as u32 + 1)
+    }
+
+    // `locate` clamps `offset` to the source and to a character boundary, and returns the
+    // 0-based index of the line containing it together with the adjusted offset.
+    fn locate(&self, offset: usize) -> (usize, usize) {
+        let offset = floor_char_boundary(self.source, offset.min(self.source.len()));
+        // `partition_point` returns how many line starts are `<= offset`; the line containing
+        // `offset` is the last of those, so its 0-based index is one less.
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        (line, offset)
+    }
+
+    // `offset` is the inverse of `line_col`: it returns the byte offset of the 1-based
+    // `line` and `column`, or `None` if the position does not exist in the source.
+    // A column one past the last character of a line addresses the line break itself
+    // (the `\r` or `\n`); on the last line it addresses the end of the source.
+    pub fn offset(&self, line: u32, column: u32) -> Option<usize> {
+        let line_index = (line as usize).checked_sub(1)?;
+        let column_index = (column as usize).checked_sub(1)?;
+        let start = *self.line_starts.get(line_index)?;
+        // The line's text runs up to (and includes) its line break, or to the end of the source.
+        let end = self
+            .line_starts
+            .get(line_index + 1)
+            .copied()
+            .unwrap_or(self.source.len());
+        let line_text = &self.source[start..end];
+        match line_text.char_indices().nth(column_index) {
+            Some((byte_offset, _)) => Some(start + byte_offset),
+            // One past the last character is only a valid position at the very end of the
+            // source; on any other line that offset belongs to the next line's column 1.
+            None if end == self.source.len() && column_index == count_columns(line_text) => {
+                Some(end)
+            }
+            None => None,
+        }
+    }
+
+    // `line_text` returns the text of the 1-based `line`, without its line break
+    // (`\n`, `\r\n` or `\r`), or `None` if the source has no such line.
+    pub fn line_text(&self, line: u32) -> Option<&'a str> {
+        let line_index = (line as usize).checked_sub(1)?;
+        let start = *self.line_starts.get(line_index)?;
+        let end = self
+            .line_starts
+            .get(line_index + 1)
+            .copied()
+            .unwrap_or(self.source.len());
+        let text = &self.source[start..end];
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        // Stripping `\r` after `\n` handles both `\r\n` and a lone `\r`.
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    // `line_starts` returns the byte offset at which each line starts, in ascending order.
+    // The first entry is always `0`; a line break at the very end of the source starts a
+    // final, empty line. Consumers can binary-search this table to turn an offset into a line.
+    pub fn line_starts(&self) -> &[usize] {
+        &self.line_starts
+    }
+
+    // `line_count` returns the number of lines in the source. An empty source, or a source
+    // whose last line ends with a line break, still has a (possibly empty) final line.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+}
+
+// --- Column Units ---
+// `ColumnMode` selects what a column counts (see `LineIndex::line_col_with`):
+// - `Byte`: UTF-8 bytes. `é` occupies two columns.
+// - `Char`: Unicode scalar values (`char`s), like the columns in `LexerError`. `é` occupies
+//   one column, but `e` followed by a combining acute accent (U+0301) occupies two.
+// - `Grapheme`: Extended grapheme clusters, i.e. what a reader perceives as one character.
+//   Both spellings of `é` occupy one column, as do emoji made of several code points.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColumnMode {
+    Byte,
+    #[default]
+    Char,
+    Grapheme,
+}
+
+// --- Line/Column Numbering ---
+// Consumers disagree on where counting starts: people, editors' status bars and GCC-style
+// messages use 1-based lines and columns, while e.g. the Language Server Protocol uses
+// 0-based ones. `Numbering` selects the base separately for lines and for columns.
+// `LineIndex` always works with 1-based positions; `Numbering::apply` converts them.
+
+// `IndexBase` is the number the first line (or column) is given.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IndexBase {
+    Zero, // The first line/column is 0.
+    #[default]
+    One, // The first line/column is 1.
+}
+
+// `Numbering` holds the base used for lines and the base used for columns.
+// The default is 1-based for both, which is what `LexerError` reports unless configured
+// otherwise (see `LexerOptions::numbering`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Numbering {
+    pub line: IndexBase,
+    pub column: IndexBase,
+}
+
+impl Numbering {
+    // 1-based lines and columns, as used by people and GCC-style messages.
+    pub const ONE_BASED: Numbering = Numbering {
+        line: IndexBase::One,
+        column: IndexBase::One,
+    };
+
+    // 0-based lines and columns, as used by the Language Server Protocol.
+    pub const ZERO_BASED: Numbering = Numbering {
+        line: IndexBase::Zero,
+        column: IndexBase::Zero,
+    };
+
+    // `apply` converts a 1-based `(line, column)` (as returned by `LineIndex::line_col`)
+    // into this numbering.
+    pub fn apply(self, (line, column): (usize, usize)) -> (usize, usize) {
+        let shift = |base| match base {
+            IndexBase::Zero => 1,
+            IndexBase::One => 0,
+        };
+        (line - shift(self.line), column - shift(self.column))
+    }
+}
+
+// --- Line Break Detection ---
+// These helpers are the single definition of what a line break is. They are used by
+// `LineIndex`, by the lexer's own line tracking, and by the lexer's newline tokens, so
+// that every part of the crate agrees on where lines begin and end.
+
+// `line_break_len` returns the length in bytes of the line break at the start of `text`
+// (2 for `\r\n`, 1 for `\n` or a lone `\r`), or `None` if `text` doesn't start with one.
+pub(crate) fn line_break_len(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    match bytes.first()? {
+        b'\n' => Some(1),
+        b'\r' if bytes.get(1) == Some(&b'\n') => Some(2),
+        b'\r' => Some(1),
+        _ => None,
+    }
+}
+
+// `ends_line_at` returns `true` if the byte at `index` of `text` is the last byte of a line
+// break, i.e. if the next line starts at `index + 1`. That is every `\n`, and every `\r`
+// that is *not* followed by `\n` (in `\r\n`, only the `\n` ends the line).
+// Looking at the following byte in the full text (rather than in some smaller chunk) means
+// the answer is the same no matter how the text is split up while it is being scanned.
+pub(crate) fn ends_line_at(text: &str, index: usize) -> bool {
+    let bytes = text.as_bytes();
+    match bytes.get(index) {
+        Some(b'\n') => true,
+        Some(b'\r') => bytes.get(index + 1) != Some(&b'\n'),
+        _ => false,
+    }
+}
+
+// `count_columns` returns how many columns `text` (which must not contain a line break)
+// occupies. This is the single place where columns are measured, shared by `LineIndex`
+// and the lexer's own error locations so that both always agree.
+pub(crate) fn count_columns(text: &str) -> usize {
+    text.chars().count()
+}
+
+// `visual_width` returns how many display columns `text` (which must not contain a line break)
+// occupies when tab stops are `tab_width` columns apart: a tab advances to the next multiple
+// of `tab_width`, every other character occupies one column.
+pub(crate) fn visual_width(text: &str, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    text.chars().fold(0, |width, ch| {
+        if ch == '\t' {
+            width + tab_width - width % tab_width
+        } else {
+            width + 1
+        }
+    })
+}
+
+// `floor_char_boundary` moves `offset` back to the start of the UTF-8 character it falls in.
+// `offset` must not exceed `text.len()`.
+fn floor_char_boundary(text: &str, mut offset: usize) -> usize {
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `naive_positions` finds the 1-based `(line, column)` of every character boundary of
+    // `source` by walking it from the start, independently of `LineIndex`.
+    fn naive_positions(source: &str) -> Vec<(usize, (u32, u32))> {
+        let (mut line, mut column) = (1, 1);
+        let mut positions = Vec::new();
+        for (offset, ch) in source.char_indices() {
+            positions.push((offset, (line, column)));
+            let crlf = ch == '\r' && source[offset + 1..].starts_with('\n');
+            if ch == '\n' || (ch == '\r' && !crlf) {
+                (line, column) = (line + 1, 1);
+            } else {
+                column += 1;
+            }
+        }
+        positions.push((source.len(), (line, column)));
+        positions
+    }
+
+    // `all_strings` returns every string of up to `max_len` characters from `alphabet`.
+    fn all_strings(alphabet: &[char], max_len: usize) -> Vec<String> {
+        let mut strings = vec![String::new()];
+        let mut previous = vec![String::new()];
+        for _ in 0..max_len {
+            previous = previous
+                .iter()
+                .flat_map(|prefix| alphabet.iter().map(move |ch| format!("{}{}", prefix, ch)))
+                .collect();
+            strings.extend(previous.iter().cloned());
+        }
+        strings
+    }
+
+    // `line_col` agrees with a naive scan at every offset, and `offset` inverts it, for every
+    // short mix of LF, CRLF, lone CR, tabs and multi-byte characters.
+    #[test]
+    fn line_col_round_trips_against_a_naive_scan() {
+        for source in all_strings(&['a', '\n', '\r', 'é', '\t'], 6) {
+            let index = LineIndex::new(&source);
+            for (offset, expected) in naive_positions(&source) {
+                assert_eq!(
+                    index.line_col(offset),
+                    expected,
+                    "{:?} at {}",
+                    source,
+                    offset
+                );
+                assert_eq!(
+                    index.offset(expected.0, expected.1),
+                    Some(offset),
+                    "{:?} at {}",
+                    source,
+                    offset
+                );
+            }
+        }
+    }
+
+    // Each kind of line break ends exactly one line.
+    #[test]
+    fn line_starts_for_each_line_ending() {
+        let cases: [(&str, &[usize]); 4] = [
+            ("a\nb", &[0, 2]),
+            ("a\r\nb", &[0, 3]),
+            ("a\rb", &[0, 2]),
+            ("a\n\r\r\n", &[0, 2, 3, 5]),
+        ];
+        for (source, starts) in cases {
+            let index = LineIndex::new(source);
+            assert_eq!(index.line_count(), starts.len());
+            for (line, &start) in starts.iter().enumerate() {
+                assert_eq!(index.offset(line as u32 + 1, 1), Some(start));
+            }
+        }
+        assert_eq!(LineIndex::new("").line_count(), 1);
+    }
+
+    // Offsets inside a character report that character; offsets past the end are clamped.
+    #[test]
+    fn line_col_clamps_odd_offsets() {
+        let index = LineIndex::new("é\nx");
+        assert_eq!(index.line_col(1), (1, 1));
+        assert_eq!(index.line_col(100), (2, 2));
+        assert_eq!(index.offset(0, 1), None);
+        assert_eq!(index.offset(3, 1), None);
+    }
+
+    // Character columns count `é` once, byte columns count its two bytes.
+    #[test]
+    fn byte_and_character_columns() {
+        let index = LineIndex::new("é = 1;\naé");
+        assert_eq!(index.line_col(3), (1, 3));
+        assert_eq!(index.line_col_bytes(3), (1, 4));
+        assert_eq!(index.line_col(10), (2, 2));
+        assert_eq!(index.line_col_bytes(10), (2, 2));
+        assert_eq!(index.line_col_bytes(12), (2, 4));
+    }
+
+    // A combining sequence is two characters but a single grapheme cluster.
+    #[test]
+    fn grapheme_columns() {
+        // "e\u{301}" is an `e` followed by a combining acute accent, displayed as `é`.
+        let source = "e\u{301}x = 1;\n\u{1F1EB}\u{1F1F7}y";
+        let index = LineIndex::new(source);
+        let x = source.find('x').unwrap();
+        assert_eq!(index.line_col_with(x, ColumnMode::Byte), (1, 4));
+        assert_eq!(index.line_col_with(x, ColumnMode::Char), (1, 3));
+        assert_eq!(index.line_col_with(x, ColumnMode::Grapheme), (1, 2));
+        let y = source.find('y').unwrap();
+        assert_eq!(index.line_col_with(y, ColumnMode::Char), (2, 3));
+        assert_eq!(index.line_col_with(y, ColumnMode::Grapheme), (2, 2));
+    }
+
+    // A tab advances the visual column to the next tab stop.
+    #[test]
+    fn visual_columns_expand_tabs() {
+        let index = LineIndex::with_tab_width("\t\tx\n a\tb", 4);
+        assert_eq!(index.visual_col(2), (1, 9));
+        assert_eq!(index.line_col(2), (1, 3));
+        assert_eq!(index.visual_col(7), (2, 5));
+        // A tab width of 0 is treated as 1.
+        assert_eq!(LineIndex::with_tab_width("\t\tx", 0).visual_col(2), (1, 3));
+    }
+
+    // `line_break_len` and `ends_line_at` agree on LF, CRLF and a lone CR.
+    #[test]
+    fn line_break_helpers() {
+        assert_eq!(line_break_len("\nx"), Some(1));
+        assert_eq!(line_break_len("\r\nx"), Some(2));
+        assert_eq!(line_break_len("\rx"), Some(1));
+        assert_eq!(line_break_len("x\n"), None);
+        assert_eq!(line_break_len(""), None);
+        let text = "a\r\nb\rc\n";
+        let ends: Vec<usize> = (0..text.len()).filter(|&i| ends_line_at(text, i)).collect();
+        assert_eq!(ends, [2, 4, 6]);
+    }
+
+    // `apply` shifts lines and columns to their configured base independently.
+    #[test]
+    fn numbering_applies_each_base() {
+        assert_eq!(Numbering::ONE_BASED.apply((3, 7)), (3, 7));
+        assert_eq!(Numbering::ZERO_BASED.apply((3, 7)), (2, 6));
+        let lsp_lines = Numbering {
+            line: IndexBase::Zero,
+            column: IndexBase::One,
+        };
+        assert_eq!(lsp_lines.apply((3, 7)), (2, 7));
+        assert_eq!(Numbering::default(), Numbering::ONE_BASED);
+    }
+}
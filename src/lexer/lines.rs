@@ -0,0 +1,78 @@
+// Import the `Serialize` trait, so grouped lines can be written out as JSON.
+use serde::Serialize;
+
+// Import `LineIndex`, used to find the line each token starts on.
+use super::line_index::LineIndex;
+
+// Import the item types being grouped.
+use super::span::Span;
+use super::token::Token;
+
+// --- Tokens Grouped by Line ---
+// `LineTokens` holds the tokens that start on one source line, e.g. for teaching tools that
+// show the token stream next to each line of the program.
+// It serializes as `{"line": 2, "tokens": [...]}`.
+//
+// `#[derive(...)]`:
+// - `Clone`, `Debug`, `PartialEq`: For copying, printing and comparing groups (e.g. in assertions).
+// - `Serialize`: Enables serialization into formats like JSON.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LineTokens {
+    // `line`: The 1-based line number.
+    pub line: usize,
+
+    // `tokens`: The tokens starting on this line, in source order.
+    pub tokens: Vec<Token>,
+}
+
+// `group_by_line` groups spanned tokens (as returned by `Lexer::tokenize_all_spanned`) by the
+// line they start on. `index` must be built from the same source the tokens were lexed from.
+// - Only lines with at least one token get a group, so empty lines and lines holding only
+//   whitespace or (skipped) comments are simply absent.
+// - A token spanning several lines (e.g. a kept multi-line comment) belongs to the line it
+//   starts on.
+// Tokens must be in source order, which is the order the lexer produces them in.
+pub fn group_by_line(tokens: Vec<(Token, Span)>, index: &LineIndex) -> Vec<LineTokens> {
+    let mut groups: Vec<LineTokens> = Vec::new();
+    for (token, span) in tokens {
+        let line = index.line_col(span.start).0 as usize;
+        match groups.last_mut() {
+            Some(group) if group.line == line => group.tokens.push(token),
+            _ => groups.push(LineTokens {
+                line,
+                tokens: vec![token],
+            }),
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{Lexer, LexerBuilder};
+
+    // `lines` returns the line number and token count of each group of `source`.
+    fn lines(lexer: &mut Lexer, source: &str) -> Vec<(usize, usize)> {
+        let tokens = lexer.tokenize_all_spanned().unwrap();
+        group_by_line(tokens, &LineIndex::new(source))
+            .iter()
+            .map(|group| (group.line, group.tokens.len()))
+            .collect()
+    }
+
+    // Blank lines and lines holding only comments get no group.
+    #[test]
+    fn blank_and_comment_lines_are_absent() {
+        let source = "// c\nint x;\n\n/* a\n b */\nreturn x; // d\n";
+        assert_eq!(lines(&mut Lexer::new(source), source), vec![(2, 3), (6, 3)]);
+    }
+
+    // A token spanning several lines belongs to the line it starts on.
+    #[test]
+    fn multi_line_tokens_belong_to_their_first_line() {
+        let source = "x /* a\n b */ y\nz";
+        let mut lexer = LexerBuilder::new().keep_comments(true).build(source);
+        assert_eq!(lines(&mut lexer, source), vec![(1, 2), (2, 1), (3, 1)]);
+    }
+}
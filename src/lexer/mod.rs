@@ -1,5 +1,5 @@
 // This file serves as the root of the `lexer` module.
-// When another part of the crate (like `src/main.rs`) declares `mod lexer;`,
+// When the library root (`src/lib.rs`) declares `pub mod lexer;`,
 // Rust looks for `src/lexer.rs` or `src/lexer/mod.rs`. Since this file exists,
 // it becomes the entry point for the `lexer` module.
 
@@ -10,24 +10,97 @@
 // (i.e., within `src/lexer/`).
 // These lines effectively bring the contents of those files into the `lexer` module's scope,
 // under their respective submodule names (e.g., `lexer::token`, `lexer::error`).
+mod checkpoint; // Declares the `checkpoint` submodule, sourcing from `src/lexer/checkpoint.rs`.
 mod core; // Declares the `core` submodule, sourcing from `src/lexer/core.rs`.
+mod diagnostic; // Declares the `diagnostic` submodule, sourcing from `src/lexer/diagnostic.rs`.
 mod error; // Declares the `error` submodule, sourcing from `src/lexer/error.rs`.
+mod html; // Declares the `html` submodule, sourcing from `src/lexer/html.rs`.
+mod interner; // Declares the `interner` submodule, sourcing from `src/lexer/interner.rs`.
+mod iter; // Declares the `iter` submodule, sourcing from `src/lexer/iter.rs`.
+mod line_index; // Declares the `line_index` submodule, sourcing from `src/lexer/line_index.rs`.
+mod lines; // Declares the `lines` submodule, sourcing from `src/lexer/lines.rs`.
+mod options; // Declares the `options` submodule, sourcing from `src/lexer/options.rs`.
+mod render; // Declares the `render` submodule, sourcing from `src/lexer/render.rs`.
+mod rule; // Declares the `rule` submodule, sourcing from `src/lexer/rule.rs`.
+mod schema; // Declares the `schema` submodule, sourcing from `src/lexer/schema.rs`.
+mod sexpr; // Declares the `sexpr` submodule, sourcing from `src/lexer/sexpr.rs`.
+mod span; // Declares the `span` submodule, sourcing from `src/lexer/span.rs`.
 mod token; // Declares the `token` submodule, sourcing from `src/lexer/token.rs`.
+mod warning; // Declares the `warning` submodule, sourcing from `src/lexer/warning.rs`.
 
 // --- 2. Re-export Public Items ---
 // The `pub use` keyword is used to re-export items from the submodules,
 // making them directly accessible from outside the `lexer` module as if they were
 // defined directly within `lexer`.
 // For example, after `pub use token::Token;`, code outside this module can write
-// `use my_crate::lexer::Token;` instead of the more verbose `use my_crate::lexer::token::Token;`.
+// `use obv_lexer::lexer::Token;` instead of the more verbose `use obv_lexer::lexer::token::Token;`
+// (which would not even compile, as the submodules themselves are private).
 // This creates a cleaner public API for the `lexer` module.
 
 // Re-export the `Token` enum from the `token` submodule.
 pub use core::Lexer;
 
-// Re-export the `LexerError` enum from the `error` submodule.
-pub use error::LexerError;
+// Re-export the `LexerError` struct, the `LexerErrorKind` saying which kind of error it is,
+// and the `ErrorContext` each error carries, from the `error` submodule. `ResourceKind` says which resource limit a
+// `LexerErrorKind::ResourceLimitExceeded` is about.
+pub use error::{ErrorContext, LexerError, LexerErrorKind, ResourceKind};
+
+// Re-export the `Checkpoint` struct, the saved lexer state of `Lexer::checkpoint`.
+pub use checkpoint::Checkpoint;
+
+// Re-export the `Diagnostic` enum and its `Severity` from the `diagnostic` submodule.
+// A diagnostic is either an error or a warning (see `Lexer::tokenize_all_with_diagnostics`).
+pub use diagnostic::{Diagnostic, Severity};
+
+// Re-export `ParseTokenError`, returned when `str::parse::<Token>` fails.
+pub use error::ParseTokenError;
+
+// Re-export `to_html` from the `html` submodule, which writes source as highlighted HTML.
+pub use html::to_html;
+
+// Re-export the configuration types from the `options` submodule.
+// `LexerBuilder` is the chainable way to create a configured `Lexer`, and `RecoveryPolicy`
+// selects how `Lexer::tokenize_all_recovering` resumes after an error.
+// `DEFAULT_MAX_TOKEN_LEN` is the default length limit for identifiers and string literals.
+pub use options::{DEFAULT_MAX_TOKEN_LEN, LexerBuilder, LexerOptions, RecoveryPolicy};
+
+// Re-export the string interning types from the `interner` submodule (see `Lexer::tokenize_interned`).
+pub use interner::{InternedToken, StringInterner, Symbol};
+
+// Re-export the token iterators from the `iter` submodule: `Tokens` borrows the lexer
+// (returned by `Lexer::tokens`), `IntoTokens` owns it (used by `for token in lexer`).
+pub use iter::{IntoTokens, Tokens};
+
+// Re-export the `LineIndex` struct from the `line_index` submodule.
+// It converts the byte offsets found in spans and errors into line/column positions.
+pub use line_index::{ColumnMode, LineIndex};
+
+// Re-export the per-line grouping of tokens from the `lines` submodule.
+pub use lines::{LineTokens, group_by_line};
+
+// Re-export the line/column numbering types from the `line_index` submodule, used to choose
+// between 0-based and 1-based lines and columns in errors.
+pub use line_index::{IndexBase, Numbering};
+
+// Re-export the `Rule` struct from the `rule` submodule, for registering custom token patterns.
+pub use rule::Rule;
+
+// Re-export the description of the lexer's JSON output from the `schema` submodule: `SCHEMA`
+// lists every token kind and error kind with its JSON shape (used by `--schema`).
+pub use schema::{ErrorSchema, SCHEMA, Schema, TokenSchema};
+
+// Re-export `to_sexpr` from the `sexpr` submodule, which writes tokens as an S-expression.
+pub use sexpr::to_sexpr;
+
+// Re-export the `Span` struct from the `span` submodule.
+// Spans describe which bytes of the input a token was produced from.
+pub use span::{SourcePiece, Span};
 
 // Re-export the `Lexer` struct from the `core` submodule.
 // This makes the main lexer functionality available.
-pub use token::Token;
+// `format_tokens` joins tokens back into readable text, using their `Display` form.
+pub use token::{Token, TokenRef, format_tokens};
+
+// Re-export the `LexerWarning` enum from the `warning` submodule.
+// Warnings point out suspicious (but valid) input without stopping the lexer.
+pub use warning::LexerWarning;
@@ -0,0 +1,439 @@
+// Import the `Lexer` struct from the sibling module `core.rs`.
+// The builder's `build` method hands its finished configuration to `Lexer::with_options`.
+use super::core::Lexer;
+
+// Import the default keyword table, used to seed `LexerOptions::keywords`.
+use super::core::default_keywords;
+
+// Import the `Token` enum, which keyword entries map their spelling to.
+use super::token::Token;
+
+// Import the `Rule` struct, describing user-registered token patterns.
+use super::rule::Rule;
+
+// Import `Numbering`, which selects 0- or 1-based lines and columns in errors, and the
+// default tab width used when counting columns.
+use super::line_index::{DEFAULT_TAB_WIDTH, Numbering};
+
+// --- Lexer Options ---
+// `LexerOptions` gathers every setting that changes how the lexer behaves.
+// Keeping the settings in one struct (instead of adding a new constructor for every
+// combination of modes) means new options only need a new field and a new builder method.
+//
+// `#[derive(...)]`:
+// - `Clone`: Allows the options to be copied, e.g. to build several lexers with the same setup.
+// - `Debug`: Allows the options to be printed with `{:?}` for debugging.
+#[derive(Clone, Debug)]
+pub struct LexerOptions {
+    // `keep_comments`: When `true`, comments are not skipped. Each comment is instead
+    // emitted as a `Token::Comment` holding its full text (including the `//` or `/* */`).
+    pub keep_comments: bool,
+
+    // `lenient`: When `true`, characters that cannot start any token are silently skipped
+    // instead of producing a `LexerErrorKind::UnexpectedCharacter` error (or, for control
+    // characters, a `LexerErrorKind::DisallowedControlCharacter`). Other errors (e.g. an
+    // out-of-range integer) are still reported.
+    pub lenient: bool,
+
+    // `emit_newlines`: When `true`, line breaks are no longer treated as plain whitespace.
+    // Each one is emitted as a `Token::Newline`.
+    pub emit_newlines: bool,
+
+    // `keywords`: The table used to promote identifiers to keyword tokens.
+    // Each entry maps the keyword's spelling to the token it should produce.
+    // Defaults to the built-in keywords: `int`, `void`, `return`, `sizeof`, `typedef`,
+    // `struct`, `union`, `enum`, `static` and `extern`.
+    pub keywords: Vec<(String, Token)>,
+
+    // `rules`: Extra token patterns registered by the user, tried in insertion order.
+    pub rules: Vec<Rule>,
+
+    // `user_rules_first`: When `true`, the user `rules` are tried before the built-in
+    // token patterns (so they can override them). By default they are tried afterwards,
+    // which means they only apply where no built-in token matches.
+    pub user_rules_first: bool,
+
+    // `line_comment_prefixes`: The strings that start a single-line comment, which then runs
+    // to the end of the line. Defaults to `["//"]`; e.g. `["#"]` gives shell-style comments.
+    // A `/` that doesn't start a comment is lexed as `Token::Slash`.
+    pub line_comment_prefixes: Vec<String>,
+
+    // `file_name`: The name of the file being lexed (e.g. its path), copied into every
+    // `LexerError` so diagnostics can be traced back to their file. `None` (the default)
+    // means the input has no name, and errors report only line and column.
+    pub file_name: Option<String>,
+
+    // `numbering`: Whether the `line` and `column` of errors start counting at 0 or at 1.
+    // Defaults to 1-based lines and columns (`Numbering::ONE_BASED`).
+    pub numbering: Numbering,
+
+    // `tab_width`: The distance between tab stops when counting the `column` of errors and
+    // warnings: a tab advances the column to the next multiple of `tab_width` (plus one), so
+    // columns match an editor configured with the same tab width. E.g. with a tab width of 4,
+    // the `x` in `\t\tx` is at column 9. Defaults to 1, where a tab is a single column like
+    // any other character; 0 is treated as 1.
+    pub tab_width: usize,
+
+    // `warn_keyword_typos`: When `true`, an identifier that is a single edit away from a
+    // keyword (e.g. `retrun`) produces a `LexerWarning::PossibleKeywordTypo`. Off by default,
+    // since such identifiers are perfectly valid.
+    pub warn_keyword_typos: bool,
+
+    // `error_tokens`: When `true`, characters that cannot start any token are reported
+    // in-band as `Token::Error` instead of as a `LexerErrorKind::UnexpectedCharacter`, so the
+    // token stream covers the whole input. A run of such characters (e.g. `@@@`) becomes a
+    // single error token; whitespace, comments or any valid token end the run. Takes
+    // precedence over `lenient`. Other errors (e.g. an out-of-range integer) are unaffected.
+    pub error_tokens: bool,
+
+    // `digit_separators`: When `true`, underscores may separate the digits of an integer
+    // constant, e.g. `1_000`. The value is parsed without them, while `raw` keeps them. Each
+    // underscore must sit between two digits, so `1_` and `1__0` are still errors. Off by
+    // default, in which case `1_000` is an `InvalidNumericLiteral`, like C.
+    pub digit_separators: bool,
+
+    // `max_identifier_len`: The longest identifier accepted, in bytes. A longer one (e.g. in a
+    // corrupted machine-generated file) is reported as a `LexerErrorKind::IdentifierTooLong`
+    // instead of being copied into a token. `None` means no limit. Defaults to
+    // `DEFAULT_MAX_TOKEN_LEN` (64 KiB), far more than any real identifier needs.
+    pub max_identifier_len: Option<usize>,
+
+    // `max_string_len`: The same limit for string literals (quotes included), reported as a
+    // `LexerErrorKind::StringTooLong`. Also defaults to `DEFAULT_MAX_TOKEN_LEN`.
+    pub max_string_len: Option<usize>,
+
+    // `max_input_bytes`: The number of bytes of input the lexer is allowed to read. Once it
+    // would read past this offset (while matching a token or skipping trivia), it reports a
+    // `LexerErrorKind::ResourceLimitExceeded` and produces nothing more. The check is made as
+    // lexing goes, so an input of exactly `max_input_bytes` bytes is lexed in full, and
+    // streaming consumers (`Lexer::tokens`) get every token before the limit first.
+    // `None` (the default) means no limit.
+    pub max_input_bytes: Option<usize>,
+
+    // `max_tokens`: The number of tokens the lexer is allowed to produce (since it was created,
+    // reset or rewound). Asking for one more reports a `LexerErrorKind::ResourceLimitExceeded`
+    // pointing at the token that would have been produced, and the lexer produces nothing
+    // more, so a `Vec` of tokens never grows beyond this. `None` (the default) means no limit.
+    pub max_tokens: Option<usize>,
+
+    // `recovery`: How `Lexer::tokenize_all_recovering` resumes after an error (see
+    // `RecoveryPolicy`). Defaults to `RecoveryPolicy::PerChar`.
+    pub recovery: RecoveryPolicy,
+
+    // `post_process`: A function applied to the complete token list before `Lexer::tokenize_all`
+    // (and so `Lexer::tokenize_with_warnings`) returns it, e.g. to drop `Token::Newline`s or
+    // merge adjacent tokens. It keeps such transformations out of the scanner itself. It is
+    // not called when lexing fails, and the other methods (streaming, spanned, borrowed,
+    // recovering, ...) return the tokens as scanned. `None` (the default) returns the tokens
+    // unchanged.
+    pub post_process: Option<fn(Vec<Token>) -> Vec<Token>>,
+
+    // `resume_after_error`: When `true`, `Lexer::next_token` steps past the offending text
+    // whenever it returns an error (following `recovery`), so the next call continues lexing.
+    // When `false` (the default), the lexer stays at the error, and calling `next_token` again
+    // returns the same error. The other methods are unaffected.
+    pub resume_after_error: bool,
+
+    // `translate_trigraphs`: When `true`, the trigraphs of old C source are recognized as the
+    // characters they stand for: `??<` lexes as `Token::OpenBrace`, `??>` as
+    // `Token::CloseBrace`, and `??=` at the start of a line begins a directive. The other
+    // trigraphs (`??(` for `[`, `??)` for `]`, `??/` for `\`, `??'` for `^`, `??!` for `|` and
+    // `??-` for `~`) stand for characters that are not tokens, and are reported as those
+    // characters. Spans and positions always refer to the original three characters, and
+    // token texts keep them as written. Trigraphs inside comments, literals and directives are
+    // left as they are. Off by default, as modern C no longer has trigraphs.
+    pub translate_trigraphs: bool,
+}
+
+// --- Recovery Policy ---
+// `RecoveryPolicy` selects where `Lexer::tokenize_all_recovering` resumes lexing after it has
+// recorded an error.
+// - `PerChar`: Right after the offending text (e.g. after a single unexpected character).
+//   Every problem is reported, but a garbled stretch of input can produce a cascade of
+//   follow-on errors, one per piece the lexer trips over.
+// - `SyncToStatement`: At the next `;`, `}` or line break after the offending text, i.e. at
+//   the end of the statement, block or line the error is in. The rest of that statement is
+//   skipped without further errors, and the `;` or `}` itself is lexed as a token again.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RecoveryPolicy {
+    #[default]
+    PerChar,
+    SyncToStatement,
+}
+
+// Implementation of the `Default` trait for `LexerOptions`.
+// The defaults reproduce the behavior of `Lexer::new`: comments and newlines are skipped,
+// unexpected characters are errors, and the built-in keyword table is used.
+impl Default for LexerOptions {
+    fn default() -> Self {
+        LexerOptions {
+            keep_comments: false,
+            lenient: false,
+            emit_newlines: false,
+            keywords: default_keywords(),
+            rules: Vec::new(),
+            user_rules_first: false,
+            line_comment_prefixes: vec!["//".to_string()],
+            file_name: None,
+            numbering: Numbering::ONE_BASED,
+            tab_width: DEFAULT_TAB_WIDTH,
+            warn_keyword_typos: false,
+            error_tokens: false,
+            digit_separators: false,
+            recovery: RecoveryPolicy::PerChar,
+            max_identifier_len: Some(DEFAULT_MAX_TOKEN_LEN),
+            max_string_len: Some(DEFAULT_MAX_TOKEN_LEN),
+            max_input_bytes: None,
+            max_tokens: None,
+            post_process: None,
+            resume_after_error: false,
+            translate_trigraphs: false,
+        }
+    }
+}
+
+// `DEFAULT_MAX_TOKEN_LEN` is the default limit (in bytes) on the length of identifiers and
+// string literals (see `LexerOptions::max_identifier_len`).
+pub const DEFAULT_MAX_TOKEN_LEN: usize = 64 * 1024;
+
+// --- Lexer Builder ---
+// `LexerBuilder` offers a chainable way to configure a `Lexer`.
+// Example:
+//   let lexer = LexerBuilder::new().keep_comments(true).lenient(true).build(source);
+// Each setter takes `self` by value and returns it, which is what allows the calls to be chained.
+#[derive(Clone, Debug, Default)]
+pub struct LexerBuilder {
+    // `options`: The configuration being assembled. It starts out as `LexerOptions::default()`.
+    options: LexerOptions,
+}
+
+impl LexerBuilder {
+    // Creates a builder holding the default options.
+    pub fn new() -> Self {
+        LexerBuilder::default()
+    }
+
+    // Sets whether comments are emitted as `Token::Comment` instead of being skipped.
+    pub fn keep_comments(mut self, keep: bool) -> Self {
+        self.options.keep_comments = keep;
+        self
+    }
+
+    // Sets whether unexpected characters are skipped instead of reported as errors.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.options.lenient = lenient;
+        self
+    }
+
+    // Sets whether line breaks are emitted as `Token::Newline`.
+    pub fn emit_newlines(mut self, emit: bool) -> Self {
+        self.options.emit_newlines = emit;
+        self
+    }
+
+    // Replaces the keyword table. Identifiers whose text matches an entry's string
+    // are turned into that entry's token. Passing an empty vector disables keywords entirely.
+    pub fn keywords(mut self, keywords: Vec<(String, Token)>) -> Self {
+        self.options.keywords = keywords;
+        self
+    }
+
+    // Registers an extra token pattern. Rules are tried in the order they are added.
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.options.rules.push(rule);
+        self
+    }
+
+    // Sets whether user rules are tried before (`true`) or after (`false`) the built-in tokens.
+    pub fn user_rules_first(mut self, first: bool) -> Self {
+        self.options.user_rules_first = first;
+        self
+    }
+
+    // Replaces the list of single-line comment prefixes. Passing an empty vector disables
+    // single-line comments entirely (`/* */` comments are unaffected).
+    pub fn line_comment_prefixes(mut self, prefixes: Vec<String>) -> Self {
+        self.options.line_comment_prefixes = prefixes;
+        self
+    }
+
+    // Sets the file name reported in errors (see `LexerOptions::file_name`).
+    pub fn file_name(mut self, name: impl Into<String>) -> Self {
+        self.options.file_name = Some(name.into());
+        self
+    }
+
+    // Sets the tab width used for error columns (see `LexerOptions::tab_width`).
+    pub fn tab_width(mut self, tab_width: usize) -> Self {
+        self.options.tab_width = tab_width;
+        self
+    }
+
+    // Sets whether error lines and columns are 0- or 1-based (see `LexerOptions::numbering`).
+    pub fn numbering(mut self, numbering: Numbering) -> Self {
+        self.options.numbering = numbering;
+        self
+    }
+
+    // Sets whether near misses of keywords are reported as warnings (see `LexerWarning`).
+    pub fn warn_keyword_typos(mut self, warn: bool) -> Self {
+        self.options.warn_keyword_typos = warn;
+        self
+    }
+
+    // Sets whether unrecognizable characters become `Token::Error`s (see `LexerOptions::error_tokens`).
+    pub fn error_tokens(mut self, enabled: bool) -> Self {
+        self.options.error_tokens = enabled;
+        self
+    }
+
+    // Sets whether integer constants may contain `_` digit separators (see
+    // `LexerOptions::digit_separators`).
+    pub fn digit_separators(mut self, enabled: bool) -> Self {
+        self.options.digit_separators = enabled;
+        self
+    }
+
+    // Sets where `Lexer::tokenize_all_recovering` resumes after an error (see `RecoveryPolicy`).
+    pub fn recovery(mut self, policy: RecoveryPolicy) -> Self {
+        self.options.recovery = policy;
+        self
+    }
+
+    // Sets the longest identifier accepted, in bytes, or `None` for no limit
+    // (see `LexerOptions::max_identifier_len`).
+    pub fn max_identifier_len(mut self, max: Option<usize>) -> Self {
+        self.options.max_identifier_len = max;
+        self
+    }
+
+    // Sets the longest string literal accepted, in bytes, or `None` for no limit
+    // (see `LexerOptions::max_string_len`).
+    pub fn max_string_len(mut self, max: Option<usize>) -> Self {
+        self.options.max_string_len = max;
+        self
+    }
+
+    // Sets how many bytes of input may be lexed, or `None` for no limit
+    // (see `LexerOptions::max_input_bytes`).
+    pub fn max_input_bytes(mut self, max: Option<usize>) -> Self {
+        self.options.max_input_bytes = max;
+        self
+    }
+
+    // Sets how many tokens may be produced, or `None` for no limit
+    // (see `LexerOptions::max_tokens`).
+    pub fn max_tokens(mut self, max: Option<usize>) -> Self {
+        self.options.max_tokens = max;
+        self
+    }
+
+    // Sets whether `Lexer::next_token` steps past an error (see `LexerOptions::resume_after_error`).
+    pub fn resume_after_error(mut self, resume: bool) -> Self {
+        self.options.resume_after_error = resume;
+        self
+    }
+
+    // Sets whether trigraphs like `??<` are translated (see `LexerOptions::translate_trigraphs`).
+    pub fn translate_trigraphs(mut self, translate: bool) -> Self {
+        self.options.translate_trigraphs = translate;
+        self
+    }
+
+    // Sets the function `Lexer::tokenize_all` and `Lexer::tokenize_with_warnings` apply to
+    // their tokens before returning them. Only those two apply it; the other tokenize methods
+    // and `next_token` return the tokens as scanned (see `LexerOptions::post_process`).
+    pub fn post_process(mut self, post_process: fn(Vec<Token>) -> Vec<Token>) -> Self {
+        self.options.post_process = Some(post_process);
+        self
+    }
+
+    // Consumes the builder and creates a `Lexer` for `input` using the configured options.
+    pub fn build(self, input: &str) -> Lexer<'_> {
+        Lexer::with_options(input, self.options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The builder starts from the defaults, so building right away gives a plain lexer.
+    #[test]
+    fn builder_defaults_match_lexer_new() {
+        let source = "int x; // note\n";
+        assert_eq!(
+            LexerBuilder::new().build(source).tokenize_all(),
+            Lexer::new(source).tokenize_all()
+        );
+    }
+
+    // Every setter changes only its own option, and the settings combine.
+    #[test]
+    fn builder_settings_combine() {
+        let tokens = LexerBuilder::new()
+            .keep_comments(true)
+            .emit_newlines(true)
+            .lenient(true)
+            .build("x $ // note\n")
+            .tokenize_all()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Identifier("x".to_string()),
+                Token::Comment("// note".to_string()),
+                Token::Newline,
+            ]
+        );
+    }
+
+    // Replacing the keyword table changes which identifiers are keywords.
+    #[test]
+    fn builder_replaces_the_keyword_table() {
+        let tokens = LexerBuilder::new()
+            .keywords(vec![("fn".to_string(), Token::KwVoid)])
+            .build("fn int")
+            .tokenize_all()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![Token::KwVoid, Token::Identifier("int".to_string())]
+        );
+    }
+
+    // `no_semicolons` is a post-processor dropping every `Semicolon`.
+    fn no_semicolons(tokens: Vec<Token>) -> Vec<Token> {
+        tokens
+            .into_iter()
+            .filter(|token| *token != Token::Semicolon)
+            .collect()
+    }
+
+    // The post-processor runs on `tokenize_all` and `tokenize_with_warnings` only.
+    #[test]
+    fn post_processor_drops_semicolons() {
+        let builder = LexerBuilder::new().post_process(no_semicolons);
+        let source = "int x; return x;";
+        let tokens = builder.clone().build(source).tokenize_all().unwrap();
+        assert!(!tokens.contains(&Token::Semicolon));
+        assert_eq!(tokens.len(), 4);
+        let (tokens, _) = builder
+            .clone()
+            .build(source)
+            .tokenize_with_warnings()
+            .unwrap();
+        assert_eq!(tokens.len(), 4);
+
+        let spanned = builder
+            .clone()
+            .build(source)
+            .tokenize_all_spanned()
+            .unwrap();
+        assert_eq!(spanned.len(), 6);
+        assert_eq!(
+            builder.build("x $").tokenize_all().unwrap_err().code(),
+            "E0001"
+        );
+    }
+}
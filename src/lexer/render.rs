@@ -0,0 +1,265 @@
+// Import `LineIndex`, used to find the line and column of the offending offset.
+use super::line_index::LineIndex;
+
+// Import the `Span` struct, describing the range of text a message is about.
+use super::span::Span;
+
+// `MAX_SNIPPET_WIDTH`: The maximum number of display columns of source text shown in a snippet.
+// Longer lines (e.g. minified or generated code) are cut down to a window around the caret,
+// with `…` marking the parts that were left out.
+const MAX_SNIPPET_WIDTH: usize = 100;
+
+// ANSI escape sequences used by `colorize`. `ERROR_COLOR` (bold red) and `WARNING_COLOR` (bold
+// yellow) are the accent colors of errors and warnings, used for their label and carets.
+pub(crate) const ERROR_COLOR: &str = "\x1b[1;31m";
+pub(crate) const WARNING_COLOR: &str = "\x1b[1;33m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+// `render_snippet` formats a message about the byte range `span` of `source` in the style of rustc:
+//
+//   error: Unexpected character '$'
+//    --> 1:21
+//     |
+//   1 | int main() { return $; }
+//     |                     ^
+//
+// The text covered by `span` is underlined with one `^` per display cell (at least one, so
+// an empty span still gets a caret). A span continuing onto later lines is underlined up to
+// the end of its first line.
+// - `location`: The `(line, column)` shown on the `-->` line and in the gutter. It is passed in,
+//   rather than computed from `span`, so that it matches the numbering (0- or 1-based) of the
+//   location reported elsewhere, e.g. in `LexerError`.
+// - `file`: The name of the file `source` came from, if any. It is shown before the line and
+//   column on the `-->` line, e.g. ` --> main.c:1:21`.
+// - `label`: The severity shown before the message (e.g. "error").
+// - `message`: The description of the problem.
+// - `tab_width`: Tabs in the source line are expanded with spaces to the next multiple of
+//   `tab_width` (instead of being printed as-is), so the caret line, which is made of spaces,
+//   stays aligned with the source line no matter how the terminal renders tabs.
+// An offset at the end of a line or of the input (e.g. "unexpected end of input") puts the
+// caret just after the last character of the line.
+pub(crate) fn render_snippet(
+    source: &str,
+    span: Span,
+    file: Option<&str>,
+    location: (usize, usize),
+    label: &str,
+    message: &str,
+    tab_width: usize,
+) -> String {
+    let index = LineIndex::new(source);
+    let (line, column) = index.line_col(span.start);
+    let (end_line, end_column) = index.line_col(span.end);
+    let line_text = index.line_text(line).unwrap_or("");
+
+    // Expand the line into display cells, remembering which cells the underline starts and
+    // ends at.
+    let mut cells: Vec<char> = Vec::new();
+    let mut caret = None;
+    let mut caret_end = None;
+    for (char_index, ch) in line_text.chars().enumerate() {
+        if char_index + 1 == column as usize {
+            caret = Some(cells.len());
+        }
+        if end_line == line && char_index + 1 == end_column as usize {
+            caret_end = Some(cells.len());
+        }
+        if ch == '\t' {
+            // Pad up to the next tab stop, exactly like `LineIndex::visual_col` counts tabs.
+            let tab_width = tab_width.max(1);
+            cells.extend(std::iter::repeat_n(
+                ' ',
+                tab_width - cells.len() % tab_width,
+            ));
+        } else if ch.is_control() {
+            // Other control characters (e.g. a NUL byte) are shown as a visible placeholder:
+            // printed raw, they could mangle the terminal or shift the carets.
+            cells.push(control_picture(ch));
+        } else {
+            cells.push(ch);
+        }
+    }
+    // If no character of the line is at `column`, the offset is at the end of the line.
+    let caret = caret.unwrap_or(cells.len());
+    // If the span ends at the end of the line, or on a later line, underline to the end.
+    let underline_width = caret_end.unwrap_or(cells.len()).saturating_sub(caret);
+    let (visible_text, caret) = truncate_around(&cells, caret);
+    // The underline may not run past the visible text, which may have been truncated.
+    let visible_width = visible_text.chars().count();
+    let underline_width = underline_width
+        .min(visible_width.saturating_sub(caret))
+        .max(1);
+
+    // The gutter is as wide as the line number, so the `|` separators line up.
+    let (line, column) = location;
+    let gutter = " ".repeat(line.to_string().len());
+    let file_prefix = file.map(|file| format!("{file}:")).unwrap_or_default();
+    format!(
+        "{label}: {message}\n{gutter}--> {file_prefix}{line}:{column}\n{gutter} |\n{line} | {visible_text}\n{gutter} | {caret_padding}{underline}",
+        caret_padding = " ".repeat(caret),
+        underline = "^".repeat(underline_width),
+    )
+}
+
+// `colorize` adds ANSI colors to a snippet produced by `render_snippet`, in the general style of
+// rustc: the label in the `accent` color, the message in bold, the gutter (line numbers, `|`
+// and `-->`) dimmed, and the carets in the `accent` color. Anything after the carets (e.g. a
+// hint) is left plain. Only escape sequences are inserted, so removing them gives back
+// `rendered` exactly.
+pub(crate) fn colorize(rendered: &str, accent: &str) -> String {
+    let mut lines = rendered.split('\n');
+    let mut out = String::new();
+    // The first line is `label: message`.
+    if let Some(header) = lines.next() {
+        match header.split_once(": ") {
+            Some((label, message)) => {
+                out.push_str(&format!("{accent}{label}{RESET}{BOLD}: {message}{RESET}"))
+            }
+            None => out.push_str(header),
+        }
+    }
+    // The other lines start with the gutter, whose width is given by the `-->` line.
+    let rest: Vec<&str> = lines.collect();
+    let gutter_width = rest.first().and_then(|line| line.find("-->")).unwrap_or(0);
+    for (index, line) in rest.iter().enumerate() {
+        out.push('\n');
+        // The `-->` line only has its arrow dimmed; the other lines their `N |` prefix.
+        let gutter_end = if index == 0 {
+            gutter_width + 3
+        } else {
+            gutter_width + 2
+        };
+        let (gutter, text) = line.split_at(gutter_end.min(line.len()));
+        out.push_str(&format!("{DIM}{gutter}{RESET}"));
+        if index + 1 == rest.len() {
+            // The last line holds the carets, after their padding.
+            let carets_start = text.find('^').unwrap_or(text.len());
+            let carets_end = text[carets_start..]
+                .find(|c| c != '^')
+                .map_or(text.len(), |len| carets_start + len);
+            out.push_str(&format!(
+                "{}{accent}{}{RESET}{}",
+                &text[..carets_start],
+                &text[carets_start..carets_end],
+                &text[carets_end..],
+            ));
+        } else {
+            out.push_str(text);
+        }
+    }
+    out
+}
+
+// `control_picture` returns a printable stand-in for the control character `ch`: the matching
+// Unicode "control picture" (e.g. `␀` for NUL) for the C0 controls and DEL, `�` otherwise.
+fn control_picture(ch: char) -> char {
+    match ch as u32 {
+        code @ 0x00..=0x1F => char::from_u32(0x2400 + code).unwrap_or('\u{FFFD}'),
+        0x7F => '\u{2421}',
+        _ => '\u{FFFD}',
+    }
+}
+
+// `truncate_around` shortens `cells` to at most `MAX_SNIPPET_WIDTH` cells, keeping a window
+// centred on `caret` (as far as the line allows). Returns the visible text, with `…` where
+// text was removed, and the position of the caret within it.
+fn truncate_around(cells: &[char], caret: usize) -> (String, usize) {
+    if cells.len() <= MAX_SNIPPET_WIDTH {
+        return (cells.iter().collect(), caret);
+    }
+    let start = caret
+        .saturating_sub(MAX_SNIPPET_WIDTH / 2)
+        .min(cells.len() - MAX_SNIPPET_WIDTH);
+    let end = start + MAX_SNIPPET_WIDTH;
+
+    let mut text = String::new();
+    let mut caret = caret - start;
+    if start > 0 {
+        text.push('…');
+        caret += 1;
+    }
+    text.extend(&cells[start..end]);
+    if end < cells.len() {
+        text.push('…');
+    }
+    (text, caret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `render` renders a snippet for the 1-based `location` of `span` in `source`.
+    fn render(source: &str, span: Span, location: (usize, usize)) -> String {
+        render_snippet(source, span, None, location, "error", "oops", 1)
+    }
+
+    // The offending line is shown with a caret under the offending text.
+    #[test]
+    fn snippet_points_at_the_offending_text() {
+        let source = "int x;\nint main() { return $; }";
+        assert_eq!(
+            render(source, Span::new(27, 28), (2, 21)),
+            "error: oops\n --> 2:21\n  |\n2 | int main() { return $; }\n  |                     ^"
+        );
+    }
+
+    // A span at the end of the input still gets one caret, just after the last character.
+    #[test]
+    fn snippet_at_the_end_of_the_input() {
+        assert_eq!(
+            render("/* x", Span::new(4, 4), (1, 5)),
+            "error: oops\n --> 1:5\n  |\n1 | /* x\n  |     ^"
+        );
+    }
+
+    // A very long line is cut down to a window around the caret.
+    #[test]
+    fn snippet_truncates_long_lines() {
+        let source = format!("{}${}", "a".repeat(300), "b".repeat(300));
+        let rendered = render(&source, Span::new(300, 301), (1, 301));
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert!(lines[3].starts_with("1 | …") && lines[3].ends_with('…'));
+        let caret = lines[4].find('^').unwrap();
+        assert_eq!(lines[3].chars().nth(caret), Some('$'));
+    }
+
+    // Tabs are expanded to spaces, so the caret stays under the offending text.
+    #[test]
+    fn snippet_expands_tabs() {
+        let rendered = render_snippet("\tx $", Span::new(3, 4), None, (1, 7), "error", "oops", 4);
+        assert_eq!(
+            rendered,
+            "error: oops\n --> 1:7\n  |\n1 |     x $\n  |       ^"
+        );
+    }
+
+    // `strip_ansi` removes the escape sequences `colorize` inserts.
+    fn strip_ansi(text: &str) -> String {
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('\x1b') {
+            out.push_str(&rest[..start]);
+            let end = rest[start..].find('m').unwrap();
+            rest = &rest[start + end + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    // Coloring only adds escape sequences: without them, the text is the plain rendering.
+    #[test]
+    fn colored_snippet_has_the_same_content() {
+        let source = "int x;\nint main() { return $; }";
+        let plain = render(source, Span::new(27, 28), (2, 21));
+        let colored = colorize(&plain, ERROR_COLOR);
+        assert_ne!(colored, plain);
+        assert!(colored.starts_with("\x1b[1;31merror\x1b[0m"));
+        assert_eq!(strip_ansi(&colored), plain);
+
+        let with_hint = format!("{} did you mean ' '?", plain);
+        assert_eq!(strip_ansi(&colorize(&with_hint, WARNING_COLOR)), with_hint);
+    }
+}
@@ -0,0 +1,79 @@
+// Import the `Regex` type, which each rule uses to recognize its tokens.
+use regex::Regex;
+
+// Import the `Token` enum, which a rule's constructor function produces.
+use super::token::Token;
+
+// --- Rule Definition ---
+// A `Rule` is a user-registered token pattern, added with `Lexer::add_rule` or
+// `LexerBuilder::rule`. It lets callers teach the lexer new tokens without editing the crate.
+// - `regex`: The pattern recognizing the token. It is matched against the remaining input,
+//   and only a non-empty match that begins exactly at the current position counts.
+//   Anchoring the pattern with `\A` (like the built-in regexes do) avoids needless searching.
+// - `make`: A function turning the matched text into a `Token`. `Token::Custom` is intended
+//   for tokens the built-in variants don't cover, but any variant may be returned.
+//
+// Example (annotations such as `@inline`):
+//   Rule::new(Regex::new(r"\A@[a-zA-Z_]\w*").unwrap(), |text| Token::Custom(text.to_string()))
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub regex: Regex,
+    pub make: fn(&str) -> Token,
+}
+
+impl Rule {
+    // Creates a rule from a compiled regex and a token constructor function.
+    pub fn new(regex: Regex, make: fn(&str) -> Token) -> Self {
+        Rule { regex, make }
+    }
+
+    // `match_len` returns the length (in bytes) of this rule's match at the start of `input`,
+    // or `None` if the rule does not match there. Empty matches are ignored, since a token
+    // that consumes nothing would make the lexer loop forever.
+    pub(crate) fn match_len(&self, input: &str) -> Option<usize> {
+        self.regex
+            .find(input)
+            .filter(|mat| mat.start() == 0 && mat.end() > 0)
+            .map(|mat| mat.end())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{Lexer, LexerErrorKind};
+
+    // `custom` builds the `Token::Custom` for a rule's matched text.
+    fn custom(text: &str) -> Token {
+        Token::Custom(text.to_string())
+    }
+
+    // A registered rule lexes text no built-in token covers.
+    #[test]
+    fn registered_rule_produces_custom_tokens() {
+        let mut lexer = Lexer::new("@inline int");
+        lexer.add_rule(Rule::new(Regex::new(r"\A@[a-zA-Z_]\w*").unwrap(), custom));
+        assert_eq!(
+            lexer.tokenize_all().unwrap(),
+            vec![Token::Custom("@inline".to_string()), Token::KwInt]
+        );
+    }
+
+    // Only a non-empty match at the current position counts.
+    #[test]
+    fn match_len_ignores_later_and_empty_matches() {
+        let rule = Rule::new(Regex::new(r"@|x*").unwrap(), custom);
+        assert_eq!(rule.match_len("@a"), Some(1));
+        assert_eq!(rule.match_len("a@"), None);
+
+        let mut lexer = Lexer::new("$");
+        lexer.add_rule(Rule::new(Regex::new(r"\A\$?").unwrap(), custom));
+        assert_eq!(lexer.tokenize_all().unwrap(), vec![custom("$")]);
+        let mut lexer = Lexer::new("$");
+        lexer.add_rule(Rule::new(Regex::new(r"\Ax*").unwrap(), custom));
+        assert!(matches!(
+            lexer.tokenize_all().unwrap_err().kind(),
+            LexerErrorKind::UnexpectedCharacter { char: '$' }
+        ));
+    }
+}
@@ -0,0 +1,306 @@
+// Import the `Serialize` trait, so the schema can be written as JSON (see `--schema`).
+use serde::Serialize;
+
+// --- Schema Definition ---
+// `Schema` describes the JSON the lexer produces, for tools that want to know every possible
+// token kind and error code up front instead of discovering them in the output:
+// - `tokens`: Every `Token` variant, in declaration order (see `TokenSchema`).
+// - `errors`: Every kind of `LexerError`, in the order of their codes (see `ErrorSchema`).
+// - `error_location`: The fields every serialized error has besides its kind's own fields.
+//
+// The description is static data, written out by hand in `SCHEMA` below. It must be kept in
+// sync with the enums: adding a `Token` variant or a `LexerErrorKind` (or changing how one
+// serializes) means adding or updating its entry here.
+//
+// Shapes are written as JSON templates: `<string>`, `<integer>` and `<boolean>` stand for a
+// value of that type, a key followed by `?` is left out when it has no value, and `...` stands
+// for the `error_location` fields.
+//
+// `#[derive(...)]`:
+// - `Debug`: Allows the schema to be printed with `{:?}` for debugging.
+// - `Serialize`: Serializes as e.g. `{"tokens": [...], "errors": [...], "error_location": "..."}`.
+#[derive(Debug, Serialize)]
+pub struct Schema {
+    pub tokens: &'static [TokenSchema],
+    pub errors: &'static [ErrorSchema],
+    pub error_location: &'static str,
+}
+
+// `TokenSchema` describes one `Token` variant.
+// - `name`: The variant's name, as returned by `Token::name` and used as its JSON key.
+// - `shape`: How a token of this kind is serialized, e.g. `{"Identifier": <string>}`.
+#[derive(Debug, Serialize)]
+pub struct TokenSchema {
+    pub name: &'static str,
+    pub shape: &'static str,
+}
+
+// `ErrorSchema` describes one kind of `LexerError`.
+// - `name`: The kind's snake_case name, used as its JSON key, e.g. `unexpected_character`.
+// - `code`: Its stable error code, as returned by `LexerError::code`, e.g. `E0001`.
+// - `shape`: How an error of this kind is serialized.
+#[derive(Debug, Serialize)]
+pub struct ErrorSchema {
+    pub name: &'static str,
+    pub code: &'static str,
+    pub shape: &'static str,
+}
+
+// `SCHEMA` is the description of the lexer's JSON output, printed by `--schema`.
+pub const SCHEMA: Schema = Schema {
+    tokens: &[
+        TokenSchema {
+            name: "KwInt",
+            shape: r#""KwInt""#,
+        },
+        TokenSchema {
+            name: "KwVoid",
+            shape: r#""KwVoid""#,
+        },
+        TokenSchema {
+            name: "KwReturn",
+            shape: r#""KwReturn""#,
+        },
+        TokenSchema {
+            name: "KwSizeof",
+            shape: r#""KwSizeof""#,
+        },
+        TokenSchema {
+            name: "KwTypedef",
+            shape: r#""KwTypedef""#,
+        },
+        TokenSchema {
+            name: "KwStruct",
+            shape: r#""KwStruct""#,
+        },
+        TokenSchema {
+            name: "KwUnion",
+            shape: r#""KwUnion""#,
+        },
+        TokenSchema {
+            name: "KwEnum",
+            shape: r#""KwEnum""#,
+        },
+        TokenSchema {
+            name: "KwStatic",
+            shape: r#""KwStatic""#,
+        },
+        TokenSchema {
+            name: "KwExtern",
+            shape: r#""KwExtern""#,
+        },
+        TokenSchema {
+            name: "Identifier",
+            shape: r#"{"Identifier": <string>}"#,
+        },
+        TokenSchema {
+            name: "Constant",
+            shape: r#"{"Constant": {"value": <integer>, "raw": <string>}}"#,
+        },
+        TokenSchema {
+            name: "Float",
+            shape: r#"{"Float": <string>}"#,
+        },
+        TokenSchema {
+            name: "StringLiteral",
+            shape: r#"{"StringLiteral": {"value": <string>, "prefix"?: <string>}}"#,
+        },
+        TokenSchema {
+            name: "RawString",
+            shape: r#"{"RawString": <string>}"#,
+        },
+        TokenSchema {
+            name: "CharLiteral",
+            shape: r#"{"CharLiteral": <string>}"#,
+        },
+        TokenSchema {
+            name: "OpenParen",
+            shape: r#""OpenParen""#,
+        },
+        TokenSchema {
+            name: "CloseParen",
+            shape: r#""CloseParen""#,
+        },
+        TokenSchema {
+            name: "OpenBrace",
+            shape: r#""OpenBrace""#,
+        },
+        TokenSchema {
+            name: "CloseBrace",
+            shape: r#""CloseBrace""#,
+        },
+        TokenSchema {
+            name: "Semicolon",
+            shape: r#""Semicolon""#,
+        },
+        TokenSchema {
+            name: "Slash",
+            shape: r#""Slash""#,
+        },
+        TokenSchema {
+            name: "Dot",
+            shape: r#""Dot""#,
+        },
+        TokenSchema {
+            name: "Comment",
+            shape: r#"{"Comment": <string>}"#,
+        },
+        TokenSchema {
+            name: "Newline",
+            shape: r#""Newline""#,
+        },
+        TokenSchema {
+            name: "Directive",
+            shape: r#"{"Directive": <string>}"#,
+        },
+        TokenSchema {
+            name: "Error",
+            shape: r#"{"Error": {"text": <string>}}"#,
+        },
+        TokenSchema {
+            name: "Custom",
+            shape: r#"{"Custom": <string>}"#,
+        },
+    ],
+    errors: &[
+        ErrorSchema {
+            name: "unexpected_character",
+            code: "E0001",
+            shape: r#"{"unexpected_character": {"char": <string>, ...}}"#,
+        },
+        ErrorSchema {
+            name: "invalid_integer",
+            code: "E0002",
+            shape: r#"{"invalid_integer": {"value": <string>, "radix": <integer>, ...}}"#,
+        },
+        ErrorSchema {
+            name: "invalid_number_suffix",
+            code: "E0003",
+            shape: r#"{"invalid_number_suffix": {"number": <string>, "suffix": <string>, "suffix_start": <integer>, ...}}"#,
+        },
+        ErrorSchema {
+            name: "no_match",
+            code: "E0004",
+            shape: r#"{"no_match": {...}}"#,
+        },
+        ErrorSchema {
+            name: "invalid_range",
+            code: "E0005",
+            shape: r#"{"invalid_range": {"range": {"start": <integer>, "end": <integer>}, ...}}"#,
+        },
+        ErrorSchema {
+            name: "unterminated_comment",
+            code: "E0006",
+            shape: r#"{"unterminated_comment": {...}}"#,
+        },
+        ErrorSchema {
+            name: "unterminated_string",
+            code: "E0007",
+            shape: r#"{"unterminated_string": {...}}"#,
+        },
+        ErrorSchema {
+            name: "unterminated_char",
+            code: "E0008",
+            shape: r#"{"unterminated_char": {...}}"#,
+        },
+        ErrorSchema {
+            name: "invalid_numeric_literal",
+            code: "E0009",
+            shape: r#"{"invalid_numeric_literal": {"text": <string>, ...}}"#,
+        },
+        ErrorSchema {
+            name: "disallowed_control_character",
+            code: "E0010",
+            shape: r#"{"disallowed_control_character": {"char": <string>, ...}}"#,
+        },
+        ErrorSchema {
+            name: "identifier_too_long",
+            code: "E0011",
+            shape: r#"{"identifier_too_long": {"len": <integer>, "max": <integer>, ...}}"#,
+        },
+        ErrorSchema {
+            name: "string_too_long",
+            code: "E0012",
+            shape: r#"{"string_too_long": {"len": <integer>, "max": <integer>, ...}}"#,
+        },
+        ErrorSchema {
+            name: "integer_overflow",
+            code: "E0013",
+            shape: r#"{"integer_overflow": {"value": <string>, "radix": <integer>, "bits": <integer>, "max": <integer>, ...}}"#,
+        },
+        ErrorSchema {
+            name: "resource_limit_exceeded",
+            code: "E0014",
+            shape: r#"{"resource_limit_exceeded": {"kind": "input_bytes" | "tokens", "limit": <integer>, ...}}"#,
+        },
+    ],
+    error_location: r#""pos": <integer>, "end": <integer>, "line": <integer>, "column": <integer>, "file"?: <string>, "context": {"text": <string>, "column": <integer>, "truncated"?: <boolean>}"#,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Token;
+
+    // One token of every kind, in declaration order.
+    fn every_token() -> Vec<Token> {
+        let text = || "x".to_string();
+        vec![
+            Token::KwInt,
+            Token::KwVoid,
+            Token::KwReturn,
+            Token::KwSizeof,
+            Token::KwTypedef,
+            Token::KwStruct,
+            Token::KwUnion,
+            Token::KwEnum,
+            Token::KwStatic,
+            Token::KwExtern,
+            Token::Identifier(text()),
+            Token::Constant {
+                value: 1,
+                raw: "1".to_string(),
+            },
+            Token::Float("1.5".to_string()),
+            Token::StringLiteral {
+                value: text(),
+                prefix: None,
+            },
+            Token::RawString(text()),
+            Token::CharLiteral(text()),
+            Token::OpenParen,
+            Token::CloseParen,
+            Token::OpenBrace,
+            Token::CloseBrace,
+            Token::Semicolon,
+            Token::Slash,
+            Token::Dot,
+            Token::Comment(text()),
+            Token::Newline,
+            Token::Directive(text()),
+            Token::Error { text: text() },
+            Token::Custom(text()),
+        ]
+    }
+
+    // The schema lists every token kind, in declaration order (which is also the sort order),
+    // under the name it is serialized with.
+    #[test]
+    fn tokens_are_in_sync_with_token() {
+        let tokens = every_token();
+        assert!(tokens.is_sorted());
+        let names: Vec<&str> = tokens.iter().map(Token::name).collect();
+        let listed: Vec<&str> = SCHEMA.tokens.iter().map(|token| token.name).collect();
+        assert_eq!(listed, names);
+        for (token, schema) in tokens.iter().zip(SCHEMA.tokens) {
+            let shape = match serde_json::to_value(token).unwrap() {
+                serde_json::Value::String(name) => format!("\"{}\"", name),
+                json => {
+                    assert!(json.get(schema.name).is_some(), "{}", json);
+                    format!("{{\"{}\": ", schema.name)
+                }
+            };
+            assert!(schema.shape.starts_with(&shape), "{}", schema.shape);
+        }
+    }
+}
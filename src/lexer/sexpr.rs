@@ -0,0 +1,118 @@
+// Import the `Token` enum, the items being converted.
+use super::token::Token;
+
+// `to_sexpr` writes a token stream as an S-expression, for tools written in Lisp-like
+// languages, e.g. `int main()` becomes:
+//
+//   (tokens (kw-int) (identifier "main") (open-paren) (close-paren))
+//
+// Every token is a parenthesized form named after its variant in kebab-case. Tokens carrying
+// text add it as a quoted string (see `quote`); constants add their value as a number, and
+// floating-point constants their text as written (e.g. `(float 3.14)`).
+// String and character literals add their text without the surrounding quotes, escapes
+// kept as written, e.g. `"a\n"` becomes `(string "a\\n")`. A string's encoding prefix is
+// added as a `(prefix ...)` form, e.g. `L"a"` becomes `(string "a" (prefix "L"))`. A raw
+// string adds its body, e.g. `R"(a\b)"` becomes `(raw-string "a\\b")`.
+pub fn to_sexpr(tokens: &[Token]) -> String {
+    let mut out = String::from("(tokens");
+    for token in tokens {
+        out.push(' ');
+        out.push_str(&token_to_sexpr(token));
+    }
+    out.push(')');
+    out
+}
+
+// `token_to_sexpr` returns the form of a single token.
+fn token_to_sexpr(token: &Token) -> String {
+    match token {
+        Token::KwInt => "(kw-int)".to_string(),
+        Token::KwVoid => "(kw-void)".to_string(),
+        Token::KwReturn => "(kw-return)".to_string(),
+        Token::KwSizeof => "(kw-sizeof)".to_string(),
+        Token::KwTypedef => "(kw-typedef)".to_string(),
+        Token::KwStruct => "(kw-struct)".to_string(),
+        Token::KwUnion => "(kw-union)".to_string(),
+        Token::KwEnum => "(kw-enum)".to_string(),
+        Token::KwStatic => "(kw-static)".to_string(),
+        Token::KwExtern => "(kw-extern)".to_string(),
+        Token::Identifier(name) => format!("(identifier {})", quote(name)),
+        Token::Constant { value, .. } => format!("(constant {})", value),
+        Token::StringLiteral {
+            value,
+            prefix: None,
+        } => format!("(string {})", quote(unquote(value))),
+        Token::StringLiteral {
+            value,
+            prefix: Some(prefix),
+        } => format!(
+            "(string {} (prefix {}))",
+            quote(unquote(value)),
+            quote(prefix)
+        ),
+        Token::RawString(body) => format!("(raw-string {})", quote(body)),
+        Token::CharLiteral(text) => format!("(char {})", quote(unquote(text))),
+        Token::OpenParen => "(open-paren)".to_string(),
+        Token::CloseParen => "(close-paren)".to_string(),
+        Token::OpenBrace => "(open-brace)".to_string(),
+        Token::CloseBrace => "(close-brace)".to_string(),
+        Token::Semicolon => "(semicolon)".to_string(),
+        Token::Slash => "(slash)".to_string(),
+        Token::Comment(text) => format!("(comment {})", quote(text)),
+        Token::Newline => "(newline)".to_string(),
+        Token::Directive(text) => format!("(directive {})", quote(text)),
+        Token::Error { text } => format!("(error {})", quote(text)),
+        Token::Custom(text) => format!("(custom {})", quote(text)),
+        Token::Dot => "(dot)".to_string(),
+        Token::Float(text) => format!("(float {})", text),
+    }
+}
+
+// `quote` writes `text` as a double-quoted string, escaping `"` and `\` with a backslash.
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for ch in text.chars() {
+        if ch == '"' || ch == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
+    }
+    quoted.push('"');
+    quoted
+}
+
+// `unquote` strips the opening and closing quote from the text of a string or character literal.
+fn unquote(text: &str) -> &str {
+    &text[1..text.len() - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    // `to_sexpr` lexes `source` and writes its tokens as an S-expression.
+    fn sexpr(source: &str) -> String {
+        to_sexpr(&Lexer::new(source).tokenize_all().unwrap())
+    }
+
+    // Each token is a form named after its variant.
+    #[test]
+    fn int_main() {
+        assert_eq!(
+            sexpr("int main()"),
+            r#"(tokens (kw-int) (identifier "main") (open-paren) (close-paren))"#
+        );
+        assert_eq!(sexpr(""), "(tokens)");
+    }
+
+    // Values are added as numbers or quoted strings, with escapes kept as written.
+    #[test]
+    fn token_values() {
+        assert_eq!(
+            sexpr(r#"007 3.14 "a\n" L"b" R"(d\e)" 'c'"#),
+            r#"(tokens (constant 7) (float 3.14) (string "a\\n") (string "b" (prefix "L")) (raw-string "d\\e") (char "c"))"#
+        );
+    }
+}
@@ -0,0 +1,158 @@
+// Import the `Serialize` trait from the `serde` crate, so that spans can be
+// included in serialized (e.g., JSON) output alongside the tokens they describe, and
+// `Deserialize`, so that they can be loaded back (e.g. as part of a `LexerError`).
+use serde::{Deserialize, Serialize};
+
+// Import `Range`, the standard `start..end` type that spans convert to and from.
+use std::ops::Range;
+
+// Import the `Token` enum, which `SourcePiece::Token` holds.
+use super::token::Token;
+
+// --- Span Definition ---
+// A `Span` identifies a contiguous range of the source text by byte offsets.
+// - `start`: The byte offset of the first byte in the range.
+// - `end`: The byte offset one past the last byte in the range (exclusive), so
+//   `&input[span.start..span.end]` is exactly the text the span covers.
+//
+// `#[derive(...)]`:
+// - `Clone`, `Copy`: A span is just two integers, so it is cheap to copy around freely.
+// - `Debug`: Allows spans to be printed with `{:?}`.
+// - `PartialEq`, `Eq`: Allows spans to be compared, e.g. in assertions.
+// - `Serialize`: Serializes as an object with `start` and `end` fields, e.g.
+//   `{"start": 4, "end": 8}`. This representation is part of the JSON output format (see
+//   `--spans`), so it is kept stable: a span is never serialized as a `[start, end]` tuple.
+// - `Deserialize`: Reads the same `{"start": ..., "end": ...}` object back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    // Creates a span covering the bytes from `start` (inclusive) to `end` (exclusive).
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    // `len` returns the number of bytes the span covers.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    // `is_empty` returns `true` for a zero-length span, e.g. one marking the end of the input.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    // `contains` returns `true` if the byte `offset` lies inside the span. Since `end` is
+    // exclusive, a span never contains its own `end`, and an empty span contains no offset.
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    // `merge` returns the smallest span covering both `self` and `other`. If the two spans
+    // are not adjacent, the result also covers the gap between them, e.g. merging the spans of
+    // the first and last token of a statement gives the span of the whole statement.
+    pub fn merge(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+// A span can be created from a byte range, e.g. `Span::from(4..8)`.
+impl From<Range<usize>> for Span {
+    fn from(range: Range<usize>) -> Self {
+        Span::new(range.start, range.end)
+    }
+}
+
+// A span can be turned back into a byte range, e.g. for indexing: `&input[Range::from(span)]`.
+impl From<Span> for Range<usize> {
+    fn from(span: Span) -> Self {
+        span.start..span.end
+    }
+}
+
+// --- Source Pieces ---
+// A `SourcePiece` is one part of a lossless tokenization (see `Lexer::tokenize_lossless`):
+// either a token, or a run of trivia (whitespace, skipped comments, a shebang line, or
+// characters skipped in lenient mode) between tokens. The pieces are contiguous and in
+// source order, so slicing the input with their spans and concatenating the slices gives
+// back the input byte for byte.
+// Serializes as e.g. `{"Token": {"token": "KwInt", "span": {"start": 0, "end": 3}}}` or
+// `{"Trivia": {"span": {"start": 3, "end": 4}}}`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum SourcePiece {
+    Token { token: Token, span: Span }, // A token and the text it was produced from.
+    Trivia { span: Span },              // Text between tokens that produced no token.
+}
+
+impl SourcePiece {
+    // `span` returns the range of source text the piece covers.
+    pub fn span(&self) -> Span {
+        match self {
+            SourcePiece::Token { span, .. } | SourcePiece::Trivia { span } => *span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `len` and `is_empty` count bytes, including a zero-length span at the end of the input.
+    #[test]
+    fn span_length() {
+        assert_eq!(Span::new(4, 8).len(), 4);
+        assert!(!Span::new(4, 8).is_empty());
+        assert_eq!(Span::new(8, 8).len(), 0);
+        assert!(Span::new(8, 8).is_empty());
+    }
+
+    // Spans convert to and from `start..end` ranges.
+    #[test]
+    fn span_range_conversions() {
+        let span = Span::from(4..8);
+        assert_eq!(span, Span::new(4, 8));
+        assert_eq!(Range::from(span), 4..8);
+        assert_eq!(&"int main"[Range::from(span)], "main");
+    }
+
+    // A span serializes as an object with `start` and `end`, never as a tuple.
+    #[test]
+    fn span_serializes_as_an_object() {
+        let json = serde_json::to_string(&Span::new(4, 8)).unwrap();
+        assert_eq!(json, r#"{"start":4,"end":8}"#);
+        assert_eq!(
+            serde_json::from_str::<Span>(&json).unwrap(),
+            Span::new(4, 8)
+        );
+    }
+
+    // Merging non-adjacent spans also covers the gap between them.
+    #[test]
+    fn merge_covers_the_gap() {
+        assert_eq!(Span::new(0, 3).merge(Span::new(9, 10)), Span::new(0, 10));
+        assert_eq!(Span::new(9, 10).merge(Span::new(0, 3)), Span::new(0, 10));
+    }
+
+    // Merging overlapping or nested spans gives their union.
+    #[test]
+    fn merge_overlapping_spans() {
+        assert_eq!(Span::new(2, 6).merge(Span::new(4, 9)), Span::new(2, 9));
+        assert_eq!(Span::new(2, 9).merge(Span::new(4, 6)), Span::new(2, 9));
+        assert_eq!(Span::new(2, 4).merge(Span::new(4, 6)), Span::new(2, 6));
+        assert_eq!(Span::new(3, 3).merge(Span::new(3, 3)), Span::new(3, 3));
+    }
+
+    // `start` is inside a span and `end` is not; an empty span contains nothing.
+    #[test]
+    fn contains_at_the_boundaries() {
+        let span = Span::new(4, 8);
+        assert!(!span.contains(3));
+        assert!(span.contains(4));
+        assert!(span.contains(7));
+        assert!(!span.contains(8));
+        assert!(!Span::new(4, 4).contains(4));
+    }
+}
@@ -2,7 +2,21 @@
 // This trait is used to enable the conversion of our `Token` enum
 // into formats like JSON, which is useful for IPC (Inter-Process Communication)
 // or for saving/loading token streams.
+// `Serializer` and `SerializeStructVariant` are used to write `TokenRef`s by hand (see below).
 use serde::Serialize;
+use serde::ser::{SerializeStructVariant, Serializer};
+
+// Import `FromStr`, implemented below so that a string can be parsed as a single token.
+// Import `fmt`, used to implement `Display`, which writes a token in its surface syntax.
+// `HashSet` collects the delimiters a raw string's body rules out (see `Display`).
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+// Import the `Lexer` used to parse a string as a token, and the error returned when that fails.
+// `RAW_STRING_MAX_DELIMITER` bounds the delimiter `Display` picks for a raw string.
+use super::core::{Lexer, RAW_STRING_MAX_DELIMITER};
+use super::error::ParseTokenError;
 
 // Define the `Token` enumeration.
 // An enum is a custom type that can be one of several possible variants.
@@ -19,14 +33,34 @@ use serde::Serialize;
 //   array, and when we retrieve them, we need an owned copy. Variants with owned
 //   data like `Identifier(String)` also benefit from `Clone` if copies are needed.
 // - `Serialize`: Enables this enum to be serialized by `serde` into formats like JSON.
-#[derive(Clone, Debug, PartialEq, Serialize)]
+// - `Eq`, `PartialOrd`, `Ord`: Give tokens a total order, so a `Vec<Token>` can be `sort()`ed,
+//   e.g. to group tokens by kind. The order is the derived one, and is kept stable:
+//   - First by variant, in the order they are declared below: keywords (`KwInt` < `KwVoid` <
+//     ... < `KwExtern`) < `Identifier` < literals (`Constant` < `Float` < `StringLiteral` <
+//     `RawString` < `CharLiteral`) < punctuation (`OpenParen` < ... < `Slash` < `Dot`) <
+//     `Comment` < `Newline` < `Directive` < `Error` < `Custom`. A new variant is declared in
+//     the group it belongs to; the variant indices in `TokenRef`'s `Serialize` impl and the
+//     schema printed by `--schema` (see `SCHEMA` in `schema.rs`) are updated to match.
+//   - Then, within a variant, by its data: strings (identifier names, literal and comment
+//     texts) in byte-wise string order; constants numerically by `value` (so `2` < `10`),
+//     with equal values ordered by their `raw` spelling (`007` < `7`); string literals by
+//     `value` and then `prefix` (no prefix first); floating-point constants by their text
+//     (not numerically, as `Float` holds the spelling only).
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub enum Token {
     // --- Keyword Tokens ---
     // These variants represent reserved keywords in the language.
     // They do not carry any extra data because the token type itself is sufficient information.
-    KwInt,    // Represents the "int" keyword.
-    KwVoid,   // Represents the "void" keyword.
-    KwReturn, // Represents the "return" keyword.
+    KwInt,     // Represents the "int" keyword.
+    KwVoid,    // Represents the "void" keyword.
+    KwReturn,  // Represents the "return" keyword.
+    KwSizeof,  // Represents the "sizeof" keyword, which is used like an operator: `sizeof(int)`.
+    KwTypedef, // Represents the "typedef" keyword.
+    KwStruct,  // Represents the "struct" keyword.
+    KwUnion,   // Represents the "union" keyword.
+    KwEnum,    // Represents the "enum" keyword.
+    KwStatic,  // Represents the "static" keyword.
+    KwExtern,  // Represents the "extern" keyword.
 
     // --- Identifier Token ---
     // Represents a user-defined name (e.g., variable name, function name).
@@ -36,9 +70,52 @@ pub enum Token {
 
     // --- Constant Token ---
     // Represents an integer literal found in the source code.
-    // It holds an `i32` (a 32-bit signed integer) which is the numerical value of the constant.
-    // Example: For `123`, this token would be `Constant(123)`.
-    Constant(i32),
+    // - `value`: An `i32` (a 32-bit signed integer) holding the numerical value of the constant.
+    // - `raw`: The exact spelling of the literal as it appeared in the source. Different
+    //   spellings can share a value (`007` and `7` are both `7`), so formatters and other
+    //   tools that must reproduce the original text should use `raw`, not `value`.
+    // Example: For `007`, this token would be `Constant { value: 7, raw: "007".to_string() }`.
+    // Serialized to JSON as `{"Constant": {"value": 7, "raw": "007"}}`.
+    Constant {
+        value: i32,
+        raw: String,
+    },
+
+    // --- Floating-Point Constant ---
+    // A decimal number with a fractional part, e.g. `3.14`, `5.` or `.5`, holding its text
+    // exactly as written. Unlike `Constant`, it keeps no parsed value (an `f64` could not be
+    // compared for equality or ordered like the other tokens); `as_float` parses it.
+    // Exponents (`1e5`) and suffixes (`1.5f`) are not supported: `1.5f` is an
+    // `InvalidNumberSuffix` error. Serialized to JSON as `{"Float": "3.14"}`.
+    Float(String),
+
+    // --- String and Character Literals ---
+    // A string literal (`"hello\n"`) or character literal (`'a'`), holding its full text
+    // including the quotes. Escape sequences are kept exactly as written (`\n` stays two
+    // characters); a backslash only stops the following character from closing the literal.
+    // A literal must be closed on the line it starts on (see `LexerErrorKind::UnterminatedString`).
+    // A string literal may have an encoding prefix (`L`, `u`, `U` or `u8`) before its opening
+    // quote. It is kept apart in `prefix`, so `value` always starts at the quote: for
+    // `L"wide"`, `value` is `"wide"` (with quotes) and `prefix` is `Some("L")`.
+    // Serialized to JSON as `{"StringLiteral": {"value": "\"wide\"", "prefix": "L"}}`; the
+    // `prefix` key is left out for a plain string.
+    StringLiteral {
+        value: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        prefix: Option<String>,
+    },
+
+    // --- Raw String Literal ---
+    // A C++-style raw string, `R"(...)"` or, with a delimiter, `R"xyz(...)xyz"`, holding its
+    // body: the text between the parentheses, taken verbatim. Backslashes are not escapes and
+    // line breaks are allowed, so `R"(C:\dir)"` is `RawString("C:\\dir".to_string())`.
+    // The body ends at the first `)` followed by the delimiter and `"`; choosing a delimiter
+    // that does not occur in the text lets the body contain `)"`. A raw string that is never
+    // closed is an `UnterminatedString` error. Serialized to JSON as `{"RawString": "..."}`.
+    RawString(String),
+
+    // A character literal, e.g. `'a'`, holding its full text like a string literal (see above).
+    CharLiteral(String),
 
     // --- Punctuation/Symbol Tokens ---
     // These variants represent single characters or sequences of characters
@@ -48,4 +125,922 @@ pub enum Token {
     OpenBrace,  // Represents an opening curly brace: `{`.
     CloseBrace, // Represents a closing curly brace: `}`.
     Semicolon,  // Represents a semicolon: `;`.
+    Slash,      // Represents a slash (division): `/`.
+
+    // --- Member Access ---
+    // A dot, as in the member access `point.x`. A `.` directly followed by a digit starts a
+    // floating-point constant instead (`.5`), except right after an identifier: `a.5` is
+    // `Identifier`, `Dot`, `Constant`, since a number cannot follow a name anyway.
+    Dot,
+
+    // --- Trivia Tokens ---
+    // These are only produced when the lexer is configured to keep them
+    // (see `LexerBuilder::keep_comments` and `LexerBuilder::emit_newlines`).
+    // By default, comments and line breaks are skipped like any other whitespace.
+    Comment(String), // A comment, holding its full text including the `//` or `/* */` delimiters.
+    Newline,         // A line break (`\n`, `\r\n` or a lone `\r`).
+
+    // --- Preprocessor Directive ---
+    // A line starting with `#` (after optional whitespace), e.g. `#include <stdio.h>`, holding
+    // its raw text from the `#` to the end of the line. A backslash at the end of a line
+    // continues the directive onto the next line; the text then includes the backslash and
+    // the line break. The directive is not parsed any further.
+    Directive(String),
+
+    // --- Error Token ---
+    // A run of characters that cannot start any token, e.g. `@@@`. Only produced when the
+    // lexer is configured to report such input in-band (see `LexerBuilder::error_tokens`);
+    // otherwise it is a `LexerErrorKind::UnexpectedCharacter` error. Serializes as
+    // `{"Error": {"text": "@@@"}}`.
+    Error {
+        text: String,
+    },
+
+    // --- Custom Token ---
+    // A token produced by a user-registered `Rule` (see `Lexer::add_rule`).
+    // It holds the matched source text, e.g. `Custom("@inline".to_string())`.
+    Custom(String),
+}
+
+impl Token {
+    // `name` returns the name of the token's variant, e.g. "KwInt" or "Identifier", without
+    // any data it carries. It is the name used for the token in the JSON output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Token::KwInt => "KwInt",
+            Token::KwVoid => "KwVoid",
+            Token::KwReturn => "KwReturn",
+            Token::KwSizeof => "KwSizeof",
+            Token::KwTypedef => "KwTypedef",
+            Token::KwStruct => "KwStruct",
+            Token::KwUnion => "KwUnion",
+            Token::KwEnum => "KwEnum",
+            Token::KwStatic => "KwStatic",
+            Token::KwExtern => "KwExtern",
+            Token::Identifier(_) => "Identifier",
+            Token::Constant { .. } => "Constant",
+            Token::Float(_) => "Float",
+            Token::StringLiteral { .. } => "StringLiteral",
+            Token::RawString(_) => "RawString",
+            Token::CharLiteral(_) => "CharLiteral",
+            Token::OpenParen => "OpenParen",
+            Token::CloseParen => "CloseParen",
+            Token::OpenBrace => "OpenBrace",
+            Token::CloseBrace => "CloseBrace",
+            Token::Semicolon => "Semicolon",
+            Token::Slash => "Slash",
+            Token::Dot => "Dot",
+            Token::Comment(_) => "Comment",
+            Token::Newline => "Newline",
+            Token::Directive(_) => "Directive",
+            Token::Error { .. } => "Error",
+            Token::Custom(_) => "Custom",
+        }
+    }
+
+    // `category` returns the name of the broad kind of token this is, e.g. "keyword" for
+    // `KwInt` or "punctuation" for `Semicolon`. It is used to summarize token streams
+    // (see `--summary`); the names are part of that output, so they are kept stable.
+    pub fn category(&self) -> &'static str {
+        match self {
+            Token::KwInt
+            | Token::KwVoid
+            | Token::KwReturn
+            | Token::KwSizeof
+            | Token::KwTypedef
+            | Token::KwStruct
+            | Token::KwUnion
+            | Token::KwEnum
+            | Token::KwStatic
+            | Token::KwExtern => "keyword",
+            Token::Identifier(_) => "identifier",
+            Token::Constant { .. } | Token::Float(_) => "constant",
+            Token::StringLiteral { .. } | Token::RawString(_) => "string",
+            Token::CharLiteral(_) => "char",
+            Token::OpenParen
+            | Token::CloseParen
+            | Token::OpenBrace
+            | Token::CloseBrace
+            | Token::Semicolon
+            | Token::Slash
+            | Token::Dot => "punctuation",
+            Token::Comment(_) => "comment",
+            Token::Newline => "newline",
+            Token::Directive(_) => "directive",
+            Token::Error { .. } => "error",
+            Token::Custom(_) => "custom",
+        }
+    }
+
+    // Classification predicates, for parsers and highlighters that need to know what kind
+    // of token they have without matching on every variant themselves. They are all answered
+    // by `category`, whose `match` lists every variant (it has no wildcard arm), so a new
+    // variant must be classified there before the crate compiles again and can never be
+    // silently misclassified here.
+
+    // `is_keyword` returns whether the token is a keyword, e.g. `KwInt` or `KwReturn`.
+    pub fn is_keyword(&self) -> bool {
+        self.category() == "keyword"
+    }
+
+    // `is_punctuation` returns whether the token is punctuation: `(`, `)`, `{`, `}`, `;`, `/`
+    // or `.`.
+    pub fn is_punctuation(&self) -> bool {
+        self.category() == "punctuation"
+    }
+
+    // `is_literal` returns whether the token is a literal value: an integer or floating-point
+    // constant, a string literal (raw strings included) or a character literal.
+    pub fn is_literal(&self) -> bool {
+        matches!(self.category(), "constant" | "string" | "char")
+    }
+
+    // `is_identifier` returns whether the token is an identifier. Keywords are not
+    // identifiers, so it is `false` for `KwInt`.
+    pub fn is_identifier(&self) -> bool {
+        self.category() == "identifier"
+    }
+
+    // `as_constant` returns the value of a `Constant` token, e.g. `Some(5)` for `5`,
+    // and `None` for any other token.
+    pub fn as_constant(&self) -> Option<i32> {
+        match self {
+            Token::Constant { value, .. } => Some(*value),
+            _ => None,
+        }
+    }
+
+    // `as_float` returns the value of a `Float` token, e.g. `Some(0.5)` for `.5`,
+    // and `None` for any other token.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Token::Float(text) => text.parse().ok(),
+            _ => None,
+        }
+    }
+
+    // `as_identifier` returns the name of an `Identifier` token, e.g. `Some("main")`,
+    // and `None` for any other token (keywords included).
+    pub fn as_identifier(&self) -> Option<&str> {
+        match self {
+            Token::Identifier(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+// `Display` writes a token the way it is spelled in source code, for user-facing messages
+// (e.g. "expected `;`, found `}`"), where the `Debug` form `Identifier("main")` would be noise:
+// - Keywords and punctuation print their spelling: `int`, `{`, `;`. A `Newline` prints a `\n`.
+// - Identifiers print their name, and constants their spelling as written (`007`), which is
+//   their value as it appeared in the source. A hand-made `Constant` with an empty `raw`
+//   prints its `value`.
+// - Literals, comments, directives, error and custom tokens print their text, e.g. `L"wide"`
+//   (prefix included) or `// note`. A raw string prints as `R"(...)"` around its body, with
+//   a delimiter if the body contains `)"` (see `raw_string_delimiter_for`), e.g. `R"a()")a"`.
+// Parsing the output of `Display` gives back the same token (see `FromStr` below), except for
+// tokens the lexer does not produce from that text by default (comments, newlines, error and
+// custom tokens).
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let spelling = match self {
+            Token::KwInt => "int",
+            Token::KwVoid => "void",
+            Token::KwReturn => "return",
+            Token::KwSizeof => "sizeof",
+            Token::KwTypedef => "typedef",
+            Token::KwStruct => "struct",
+            Token::KwUnion => "union",
+            Token::KwEnum => "enum",
+            Token::KwStatic => "static",
+            Token::KwExtern => "extern",
+            Token::Identifier(name) => name,
+            Token::Constant { value, raw } if raw.is_empty() => return write!(f, "{}", value),
+            Token::Constant { raw, .. } => raw,
+            Token::StringLiteral { value, prefix } => {
+                return write!(f, "{}{}", prefix.as_deref().unwrap_or(""), value);
+            }
+            Token::CharLiteral(text) => text,
+            Token::OpenParen => "(",
+            Token::CloseParen => ")",
+            Token::OpenBrace => "{",
+            Token::CloseBrace => "}",
+            Token::Semicolon => ";",
+            Token::Slash => "/",
+            Token::Comment(text) => text,
+            Token::Newline => "\n",
+            Token::Directive(text) => text,
+            Token::Error { text } => text,
+            Token::Custom(text) => text,
+            Token::Dot => ".",
+            Token::Float(text) => text,
+            Token::RawString(body) => {
+                let delimiter = raw_string_delimiter_for(body);
+                return write!(f, "R\"{}({}){}\"", delimiter, body, delimiter);
+            }
+        };
+        f.write_str(spelling)
+    }
+}
+
+// `raw_string_delimiter_for` returns the delimiter `Display` writes around the raw string
+// `body`: none if the body does not contain `)"`, and otherwise the first of `a`, `b`, ...,
+// `z`, `aa`, `ab`, ... that does not end the body early, i.e. that does not appear in the
+// body between a `)` and a `"`.
+// Each `)` in the body rules out at most one delimiter, so at most one candidate more than
+// there are `)`s is tried. Candidates grow by one letter for every factor of 26, so the
+// delimiter stays within `RAW_STRING_MAX_DELIMITER` characters for any body that fits in
+// memory.
+fn raw_string_delimiter_for(body: &str) -> String {
+    let taken: HashSet<&str> = body
+        .match_indices(')')
+        .filter_map(|(start, _)| {
+            let rest = &body[start + 1..];
+            let len = rest
+                .bytes()
+                .take(RAW_STRING_MAX_DELIMITER + 1)
+                .position(|byte| byte == b'"')?;
+            Some(&rest[..len])
+        })
+        .collect();
+    (0..)
+        .map(|mut n: usize| {
+            // `n` written in bijective base 26 with the digits `a` to `z`: 0 is the empty
+            // delimiter, 1 is `a`, 26 is `z` and 27 is `aa`.
+            let mut delimiter = Vec::new();
+            while n > 0 {
+                n -= 1;
+                delimiter.push(b'a' + (n % 26) as u8);
+                n /= 26;
+            }
+            delimiter.reverse();
+            String::from_utf8(delimiter).unwrap()
+        })
+        .find(|delimiter| !taken.contains(delimiter.as_str()))
+        .unwrap()
+}
+
+// `format_tokens` joins a token stream back into readable text: every token in its surface
+// syntax (see `Display` above), separated by `sep`. For example, with a `sep` of `" "`, the
+// tokens of `int main(void){return 0;}` become `int main ( void ) { return 0 ; }`.
+// The result is meant for reading, not for re-lexing: with an empty `sep`, neighbouring
+// tokens can run together (`int` and `main` become `intmain`).
+pub fn format_tokens(tokens: &[Token], sep: &str) -> String {
+    tokens
+        .iter()
+        .map(Token::to_string)
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+// Parsing a string as a token, e.g. `"int".parse::<Token>()` gives `Ok(Token::KwInt)` and
+// `"42".parse::<Token>()` a `Constant` with value 42. The string is lexed with the default
+// options, so surrounding whitespace and comments are ignored; it must contain exactly one
+// token, otherwise `ParseTokenError::Empty` or `ParseTokenError::MultipleTokens` is returned
+// (e.g. for `"a b"`). Handy for test fixtures and small utilities.
+impl FromStr for Token {
+    type Err = ParseTokenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = Lexer::new(s)
+            .tokenize_all()
+            .map_err(ParseTokenError::Lexer)?;
+        match tokens.len() {
+            0 => Err(ParseTokenError::Empty),
+            1 => Ok(tokens.remove(0)),
+            count => Err(ParseTokenError::MultipleTokens(count)),
+        }
+    }
+}
+
+// --- Borrowed Tokens ---
+// `TokenRef` is a zero-copy counterpart of `Token`, produced by `Lexer::tokenize_all_borrowed`.
+// The tokens that carry source text (identifiers, constants and comments) borrow that text
+// from the input (lifetime `'a`) instead of copying it into a `String`, so lexing a large file
+// does not allocate once per identifier. `into_owned` converts a `TokenRef` into a `Token`.
+// A `TokenRef` serializes exactly like the `Token` it stands for, so borrowed tokens can be
+// written as JSON (or MessagePack) without converting them first.
+//
+// Tokens that carry no source text (keywords, punctuation, newlines), and tokens built by
+// user rules or the keyword table (which may hold any `Token`), are wrapped in `Other`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenRef<'a> {
+    Identifier(&'a str), // An identifier, borrowing its name.
+    Constant {
+        value: i32,
+        raw: &'a str,
+    }, // An integer constant and its original spelling.
+    // A string literal, borrowing its text (from the opening quote) and encoding prefix.
+    StringLiteral {
+        value: &'a str,
+        prefix: Option<&'a str>,
+    },
+    CharLiteral(&'a str), // A character literal, borrowing its full text.
+    Comment(&'a str),     // A kept comment, borrowing its full text.
+    Directive(&'a str),   // A preprocessor directive, borrowing its full text.
+    Error(&'a str),       // An error token, borrowing the invalid text.
+    Float(&'a str),       // A floating-point constant, borrowing its text.
+    RawString(&'a str),   // A raw string literal, borrowing its body.
+    Other(Token),         // Any other token.
+}
+
+impl TokenRef<'_> {
+    // `into_owned` converts the borrowed token into the equivalent owned `Token`,
+    // copying any borrowed text.
+    pub fn into_owned(self) -> Token {
+        match self {
+            TokenRef::Identifier(name) => Token::Identifier(name.to_string()),
+            TokenRef::Constant { value, raw } => Token::Constant {
+                value,
+                raw: raw.to_string(),
+            },
+            TokenRef::StringLiteral { value, prefix } => Token::StringLiteral {
+                value: value.to_string(),
+                prefix: prefix.map(str::to_string),
+            },
+            TokenRef::CharLiteral(text) => Token::CharLiteral(text.to_string()),
+            TokenRef::Comment(text) => Token::Comment(text.to_string()),
+            TokenRef::Directive(text) => Token::Directive(text.to_string()),
+            TokenRef::Error(text) => Token::Error {
+                text: text.to_string(),
+            },
+            TokenRef::Float(text) => Token::Float(text.to_string()),
+            TokenRef::RawString(body) => Token::RawString(body.to_string()),
+            TokenRef::Other(token) => token,
+        }
+    }
+}
+
+// `TokenRef` implements `Serialize` by hand, producing the same output as the derived
+// implementation of `Token`: e.g. `{"Identifier": "main"}` or
+// `{"Constant": {"value": 7, "raw": "007"}}`. A derived implementation would not match, as
+// it would use `TokenRef`'s own variants (`Other` in particular).
+// Serde also passes each variant's index (its position in the declaration of `Token`), which
+// compact formats may write instead of the name. The indices below are those of `Token`, so
+// they have to be updated whenever a variant is added to or moved within `Token` (variants
+// are declared in their group, not at the end); `borrowed_tokens_serialize_like_owned_tokens`
+// checks every one of them against the derived implementation.
+impl Serialize for TokenRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            TokenRef::Identifier(name) => {
+                serializer.serialize_newtype_variant("Token", 10, "Identifier", name)
+            }
+            TokenRef::Constant { value, raw } => {
+                let mut variant =
+                    serializer.serialize_struct_variant("Token", 11, "Constant", 2)?;
+                variant.serialize_field("value", value)?;
+                variant.serialize_field("raw", raw)?;
+                variant.end()
+            }
+            TokenRef::StringLiteral { value, prefix } => {
+                // Like `Token`, the `prefix` field is left out for a plain string.
+                let len = if prefix.is_some() { 2 } else { 1 };
+                let mut variant =
+                    serializer.serialize_struct_variant("Token", 13, "StringLiteral", len)?;
+                variant.serialize_field("value", value)?;
+                match prefix {
+                    Some(prefix) => variant.serialize_field("prefix", prefix)?,
+                    None => variant.skip_field("prefix")?,
+                }
+                variant.end()
+            }
+            TokenRef::CharLiteral(text) => {
+                serializer.serialize_newtype_variant("Token", 15, "CharLiteral", text)
+            }
+            TokenRef::Comment(text) => {
+                serializer.serialize_newtype_variant("Token", 23, "Comment", text)
+            }
+            TokenRef::Directive(text) => {
+                serializer.serialize_newtype_variant("Token", 25, "Directive", text)
+            }
+            TokenRef::Error(text) => {
+                let mut variant = serializer.serialize_struct_variant("Token", 26, "Error", 1)?;
+                variant.serialize_field("text", text)?;
+                variant.end()
+            }
+            TokenRef::Float(text) => {
+                serializer.serialize_newtype_variant("Token", 12, "Float", text)
+            }
+            TokenRef::RawString(body) => {
+                serializer.serialize_newtype_variant("Token", 14, "RawString", body)
+            }
+            TokenRef::Other(token) => token.serialize(serializer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{Lexer, LexerBuilder};
+    use std::fmt;
+
+    // Borrowed identifiers point into the input instead of a copy of it.
+    #[test]
+    fn identifiers_borrow_from_the_input() {
+        let source = String::from("int main() { return count; }");
+        let tokens = Lexer::new(&source).tokenize_all_borrowed().unwrap();
+        let input = source.as_bytes().as_ptr_range();
+        let names: Vec<&str> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                TokenRef::Identifier(name) => Some(*name),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, ["main", "count"]);
+        for name in names {
+            assert!(input.contains(&name.as_ptr()));
+        }
+    }
+
+    // The borrowed tokens are the owned tokens, once converted.
+    #[test]
+    fn borrowed_tokens_match_owned_tokens() {
+        let source = "int x 007; // c\nreturn x;";
+        let borrowed = LexerBuilder::new()
+            .keep_comments(true)
+            .build(source)
+            .tokenize_all_borrowed();
+        let owned = LexerBuilder::new()
+            .keep_comments(true)
+            .build(source)
+            .tokenize_all();
+        assert_eq!(
+            borrowed.map(|tokens| tokens.into_iter().map(TokenRef::into_owned).collect()),
+            Ok(owned.unwrap())
+        );
+    }
+
+    // A string parses as a token only if it holds exactly one.
+    #[test]
+    fn parse_single_tokens() {
+        assert_eq!("int".parse::<Token>(), Ok(Token::KwInt));
+        assert_eq!(
+            " 42 /* answer */".parse::<Token>(),
+            Ok(Token::Constant {
+                value: 42,
+                raw: "42".to_string()
+            })
+        );
+        assert_eq!(
+            "a b".parse::<Token>(),
+            Err(ParseTokenError::MultipleTokens(2))
+        );
+        assert_eq!("".parse::<Token>(), Err(ParseTokenError::Empty));
+        assert_eq!("  // c".parse::<Token>(), Err(ParseTokenError::Empty));
+        assert!(matches!(
+            "$".parse::<Token>(),
+            Err(ParseTokenError::Lexer(_))
+        ));
+    }
+
+    // The value accessors return the payload of their own variant only.
+    #[test]
+    fn constant_and_identifier_accessors() {
+        let five = Token::Constant {
+            value: 5,
+            raw: "5".to_string(),
+        };
+        assert_eq!(five.as_constant(), Some(5));
+        assert_eq!(Token::KwInt.as_constant(), None);
+        assert_eq!(five.as_identifier(), None);
+        assert_eq!(
+            Token::Identifier("main".to_string()).as_identifier(),
+            Some("main")
+        );
+        assert_eq!(Token::KwInt.as_identifier(), None);
+    }
+
+    // Sorting groups tokens by kind (keywords, identifiers, constants, punctuation), then
+    // orders them by name or numeric value.
+    #[test]
+    fn sorting_groups_by_kind() {
+        let mut tokens = Lexer::new("x 10 ; int ( 2 return abc 007 7")
+            .tokenize_all()
+            .unwrap();
+        tokens.sort();
+        let constant = |value: i32, raw: &str| Token::Constant {
+            value,
+            raw: raw.to_string(),
+        };
+        assert_eq!(
+            tokens,
+            vec![
+                Token::KwInt,
+                Token::KwReturn,
+                Token::Identifier("abc".to_string()),
+                Token::Identifier("x".to_string()),
+                constant(2, "2"),
+                constant(7, "007"),
+                constant(7, "7"),
+                constant(10, "10"),
+                Token::OpenParen,
+                Token::Semicolon,
+            ]
+        );
+    }
+
+    // `as_float` parses a `Float`'s text, and is `None` for every other token.
+    #[test]
+    fn float_accessor() {
+        assert_eq!(Token::Float("2.25".to_string()).as_float(), Some(2.25));
+        assert_eq!(Token::Float(".5".to_string()).as_float(), Some(0.5));
+        assert_eq!(Token::Float("5.".to_string()).as_float(), Some(5.0));
+        assert_eq!(Token::Dot.as_float(), None);
+        let five = Token::Constant {
+            value: 5,
+            raw: "5".to_string(),
+        };
+        assert_eq!(five.as_float(), None);
+    }
+
+    // Literals sort between identifiers and punctuation, and the dot with the punctuation.
+    #[test]
+    fn literals_and_dots_sort_with_their_kind() {
+        let mut tokens = LexerBuilder::new()
+            .build(r#"; . 'c' R"(r)" "s" 1.5 2 x int"#)
+            .tokenize_all()
+            .unwrap();
+        tokens.sort();
+        let names: Vec<&str> = tokens.iter().map(Token::name).collect();
+        assert_eq!(
+            names,
+            [
+                "KwInt",
+                "Identifier",
+                "Constant",
+                "Float",
+                "StringLiteral",
+                "RawString",
+                "CharLiteral",
+                "Semicolon",
+                "Dot",
+            ]
+        );
+    }
+
+    // `VariantOf` is a serializer that only looks at enum variants: it stops at the first one
+    // with an `Err` holding the variant's index and name, as passed by the `Serialize` impl.
+    struct VariantOf;
+
+    #[derive(Debug, PartialEq)]
+    struct Variant(u32, &'static str);
+
+    impl fmt::Display for Variant {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "variant {} ({})", self.1, self.0)
+        }
+    }
+
+    impl std::error::Error for Variant {}
+
+    impl serde::ser::Error for Variant {
+        fn custom<T: fmt::Display>(msg: T) -> Self {
+            panic!("{}", msg)
+        }
+    }
+
+    // The serializer methods for anything but an enum variant, none of which a token calls.
+    macro_rules! not_a_variant {
+        ($($method:ident($($arg:ty),*) -> $ret:ty;)*) => {
+            $(fn $method(self, $(_: $arg),*) -> Result<$ret, Variant> {
+                unreachable!()
+            })*
+        };
+    }
+
+    impl serde::Serializer for VariantOf {
+        type Ok = ();
+        type Error = Variant;
+        type SerializeSeq = serde::ser::Impossible<(), Variant>;
+        type SerializeTuple = serde::ser::Impossible<(), Variant>;
+        type SerializeTupleStruct = serde::ser::Impossible<(), Variant>;
+        type SerializeTupleVariant = serde::ser::Impossible<(), Variant>;
+        type SerializeMap = serde::ser::Impossible<(), Variant>;
+        type SerializeStruct = serde::ser::Impossible<(), Variant>;
+        type SerializeStructVariant = serde::ser::Impossible<(), Variant>;
+
+        fn serialize_unit_variant(
+            self,
+            _: &str,
+            index: u32,
+            name: &'static str,
+        ) -> Result<(), Variant> {
+            Err(Variant(index, name))
+        }
+
+        fn serialize_newtype_variant<T: ?Sized + Serialize>(
+            self,
+            _: &str,
+            index: u32,
+            name: &'static str,
+            _: &T,
+        ) -> Result<(), Variant> {
+            Err(Variant(index, name))
+        }
+
+        fn serialize_struct_variant(
+            self,
+            _: &str,
+            index: u32,
+            name: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeStructVariant, Variant> {
+            Err(Variant(index, name))
+        }
+
+        fn serialize_some<T: ?Sized + Serialize>(self, _: &T) -> Result<(), Variant> {
+            unreachable!()
+        }
+
+        fn serialize_newtype_struct<T: ?Sized + Serialize>(
+            self,
+            _: &'static str,
+            _: &T,
+        ) -> Result<(), Variant> {
+            unreachable!()
+        }
+
+        not_a_variant! {
+            serialize_bool(bool) -> ();
+            serialize_i8(i8) -> ();
+            serialize_i16(i16) -> ();
+            serialize_i32(i32) -> ();
+            serialize_i64(i64) -> ();
+            serialize_u8(u8) -> ();
+            serialize_u16(u16) -> ();
+            serialize_u32(u32) -> ();
+            serialize_u64(u64) -> ();
+            serialize_f32(f32) -> ();
+            serialize_f64(f64) -> ();
+            serialize_char(char) -> ();
+            serialize_str(&str) -> ();
+            serialize_bytes(&[u8]) -> ();
+            serialize_none() -> ();
+            serialize_unit() -> ();
+            serialize_unit_struct(&'static str) -> ();
+            serialize_seq(Option<usize>) -> Self::SerializeSeq;
+            serialize_tuple(usize) -> Self::SerializeTuple;
+            serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+            serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+            serialize_map(Option<usize>) -> Self::SerializeMap;
+            serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        }
+    }
+
+    // Borrowed tokens serialize like the owned ones, down to the variant index that compact
+    // formats may write instead of the name.
+    #[test]
+    fn borrowed_tokens_serialize_like_owned_tokens() {
+        let source = "int x 007 1.5 \"s\" L\"w\" R\"(r)\" 'c' . // c\n#define D\n@";
+        let borrowed = LexerBuilder::new()
+            .keep_comments(true)
+            .error_tokens(true)
+            .build(source)
+            .tokenize_all_borrowed()
+            .unwrap();
+        assert_eq!(borrowed.len(), 12);
+        for token in borrowed {
+            let owned = token.clone().into_owned();
+            assert_eq!(
+                serde_json::to_string(&token).unwrap(),
+                serde_json::to_string(&owned).unwrap()
+            );
+            let variant = token.serialize(VariantOf).unwrap_err();
+            assert_eq!(variant, owned.serialize(VariantOf).unwrap_err());
+            assert_eq!(variant.1, owned.name());
+        }
+    }
+
+    // Converting a borrowed token and serializing it gives the same bytes as serializing it
+    // directly, in JSON and in MessagePack (compact and with field names), and the MessagePack
+    // with field names decodes to the same JSON value.
+    #[test]
+    fn borrowed_tokens_round_trip() {
+        let source = "int main 42 \"s\" u8\"t\" 'c' R\"(r)\" .5 . // c\n#include <x>\n@";
+        let borrowed = LexerBuilder::new()
+            .keep_comments(true)
+            .error_tokens(true)
+            .build(source)
+            .tokenize_all_borrowed()
+            .unwrap();
+        for token in borrowed {
+            let owned = token.clone().into_owned();
+            let json = serde_json::to_value(&token).unwrap();
+            assert_eq!(json, serde_json::to_value(&owned).unwrap());
+            let packed = rmp_serde::to_vec(&token).unwrap();
+            assert_eq!(packed, rmp_serde::to_vec(&owned).unwrap());
+            let packed = rmp_serde::to_vec_named(&token).unwrap();
+            assert_eq!(packed, rmp_serde::to_vec_named(&owned).unwrap());
+            let unpacked: serde_json::Value = rmp_serde::from_slice(&packed).unwrap();
+            assert_eq!(unpacked, json);
+        }
+        assert_eq!(TokenRef::Other(Token::Dot).into_owned(), Token::Dot);
+    }
+
+    // `Display` writes each token as it is spelled in the source.
+    #[test]
+    fn display_spellings() {
+        let tokens = LexerBuilder::new()
+            .keep_comments(true)
+            .build("int main 007 { L\"wide\" 'c' 1.5 . // note")
+            .tokenize_all()
+            .unwrap();
+        let spellings: Vec<String> = tokens.iter().map(Token::to_string).collect();
+        assert_eq!(
+            spellings,
+            [
+                "int",
+                "main",
+                "007",
+                "{",
+                "L\"wide\"",
+                "'c'",
+                "1.5",
+                ".",
+                "// note"
+            ]
+        );
+        let hand_made = Token::Constant {
+            value: 7,
+            raw: String::new(),
+        };
+        assert_eq!(hand_made.to_string(), "7");
+        assert_eq!(Token::Newline.to_string(), "\n");
+    }
+
+    // Every kind of token is printed in its surface syntax, hand-built ones included.
+    #[test]
+    fn display_every_kind() {
+        let text = |text: &str| text.to_string();
+        let cases = [
+            (Token::KwInt, "int"),
+            (Token::KwVoid, "void"),
+            (Token::KwReturn, "return"),
+            (Token::KwSizeof, "sizeof"),
+            (Token::KwTypedef, "typedef"),
+            (Token::KwStruct, "struct"),
+            (Token::KwUnion, "union"),
+            (Token::KwEnum, "enum"),
+            (Token::KwStatic, "static"),
+            (Token::KwExtern, "extern"),
+            (Token::Identifier(text("main")), "main"),
+            (
+                Token::Constant {
+                    value: 7,
+                    raw: text("007"),
+                },
+                "007",
+            ),
+            (Token::Float(text("2.50")), "2.50"),
+            (
+                Token::StringLiteral {
+                    value: text("\"s\""),
+                    prefix: None,
+                },
+                "\"s\"",
+            ),
+            (
+                Token::StringLiteral {
+                    value: text("\"w\""),
+                    prefix: Some(text("u8")),
+                },
+                "u8\"w\"",
+            ),
+            (Token::RawString(text("a\\b")), "R\"(a\\b)\""),
+            (Token::CharLiteral(text("'c'")), "'c'"),
+            (Token::OpenParen, "("),
+            (Token::CloseParen, ")"),
+            (Token::OpenBrace, "{"),
+            (Token::CloseBrace, "}"),
+            (Token::Semicolon, ";"),
+            (Token::Slash, "/"),
+            (Token::Dot, "."),
+            (Token::Comment(text("/* c */")), "/* c */"),
+            (Token::Newline, "\n"),
+            (Token::Directive(text("#define D 1")), "#define D 1"),
+            (Token::Error { text: text("@") }, "@"),
+            (Token::Custom(text("<=>")), "<=>"),
+        ];
+        for (token, spelling) in cases {
+            assert_eq!(token.to_string(), spelling, "{:?}", token);
+        }
+    }
+
+    // A raw string gets a delimiter only if its body contains `)"`, and then the first one
+    // that does not occur in the body; either way it parses back to the same token.
+    #[test]
+    fn display_raw_strings() {
+        let raw = |body: &str| Token::RawString(body.to_string());
+        let cases = [
+            (raw("a\\n"), r#"R"(a\n)""#),
+            (raw(")\""), r#"R"a()")a""#),
+            (raw(")\" )a\" )b\""), r#"R"c()" )a" )b")c""#),
+            (raw(") a\""), r#"R"() a")""#),
+        ];
+        for (token, spelling) in cases {
+            assert_eq!(token.to_string(), spelling);
+            assert_eq!(token.to_string().parse::<Token>(), Ok(token));
+        }
+        // With every single letter taken, two letters are used.
+        let body: String = std::iter::once(")\"".to_string())
+            .chain(('a'..='z').map(|letter| format!("){letter}\"")))
+            .collect();
+        let token = raw(&body);
+        assert!(token.to_string().starts_with("R\"aa("));
+        assert_eq!(token.to_string().parse::<Token>(), Ok(token));
+        // Taking every delimiter of `x`s up to the length limit does not run out either.
+        let body: String = (0..=RAW_STRING_MAX_DELIMITER)
+            .map(|len| format!("){}\"", "x".repeat(len)))
+            .collect();
+        let token = raw(&body);
+        assert!(token.to_string().starts_with("R\"a("));
+        assert_eq!(token.to_string().parse::<Token>(), Ok(token));
+    }
+
+    // `format_tokens` joins the spellings with the separator, which may be empty.
+    #[test]
+    fn format_tokens_joins_spellings() {
+        let tokens = Lexer::new("int main(void){return 0;}")
+            .tokenize_all()
+            .unwrap();
+        assert_eq!(
+            format_tokens(&tokens, " "),
+            "int main ( void ) { return 0 ; }"
+        );
+        assert_eq!(format_tokens(&tokens, ""), "intmain(void){return0;}");
+        assert_eq!(format_tokens(&[], " "), "");
+    }
+
+    // Every variant has its category, and the predicates follow from it.
+    #[test]
+    fn categories_and_predicates() {
+        let text = || "x".to_string();
+        let cases = [
+            (Token::KwInt, "keyword"),
+            (Token::KwVoid, "keyword"),
+            (Token::KwReturn, "keyword"),
+            (Token::KwSizeof, "keyword"),
+            (Token::KwTypedef, "keyword"),
+            (Token::KwStruct, "keyword"),
+            (Token::KwUnion, "keyword"),
+            (Token::KwEnum, "keyword"),
+            (Token::KwStatic, "keyword"),
+            (Token::KwExtern, "keyword"),
+            (Token::Identifier(text()), "identifier"),
+            (
+                Token::Constant {
+                    value: 1,
+                    raw: "1".to_string(),
+                },
+                "constant",
+            ),
+            (Token::Float("1.5".to_string()), "constant"),
+            (
+                Token::StringLiteral {
+                    value: "\"s\"".to_string(),
+                    prefix: None,
+                },
+                "string",
+            ),
+            (Token::RawString(text()), "string"),
+            (Token::CharLiteral("'c'".to_string()), "char"),
+            (Token::OpenParen, "punctuation"),
+            (Token::CloseParen, "punctuation"),
+            (Token::OpenBrace, "punctuation"),
+            (Token::CloseBrace, "punctuation"),
+            (Token::Semicolon, "punctuation"),
+            (Token::Slash, "punctuation"),
+            (Token::Dot, "punctuation"),
+            (Token::Comment(text()), "comment"),
+            (Token::Newline, "newline"),
+            (Token::Directive(text()), "directive"),
+            (Token::Error { text: text() }, "error"),
+            (Token::Custom(text()), "custom"),
+        ];
+        for (token, category) in cases {
+            assert_eq!(token.category(), category, "{:?}", token);
+            assert_eq!(token.is_keyword(), category == "keyword", "{:?}", token);
+            assert_eq!(
+                token.is_punctuation(),
+                category == "punctuation",
+                "{:?}",
+                token
+            );
+            assert_eq!(
+                token.is_literal(),
+                matches!(category, "constant" | "string" | "char"),
+                "{:?}",
+                token
+            );
+            assert_eq!(
+                token.is_identifier(),
+                category == "identifier",
+                "{:?}",
+                token
+            );
+        }
+    }
 }
@@ -0,0 +1,321 @@
+// Import the `fmt` module, used to implement `Display` for `LexerWarning`.
+use std::fmt;
+
+// Import the `Serialize` trait, so warnings can be written out (e.g. as JSON) like errors.
+use serde::Serialize;
+
+// Import the snippet renderer and default tab width shared with `LexerError::render`.
+use super::line_index::DEFAULT_TAB_WIDTH;
+use super::render::{WARNING_COLOR, colorize, render_snippet};
+
+// Import the `Span` struct, used to describe the byte range a warning covers.
+use super::span::Span;
+
+// --- Lexer Warnings ---
+// `LexerWarning` describes something suspicious in the input that is nevertheless valid,
+// Unlike `LexerError`s, warnings never stop the lexer; they are collected
+// while tokenizing and returned next to the tokens (see `Lexer::tokenize_with_warnings`).
+//
+// Warnings mirror `LexerError`: every variant carries the same location fields (`pos`,
+// `end`, `line`, `column` and `file`, with the same meaning), serializes with a snake_case
+// name, and offers the same `message`, `span`, `render` and `Display` formatting.
+//
+// `#[derive(...)]`:
+// - `Clone`: Allows warnings to be copied, e.g. to report them in several places.
+// - `Debug`: Allows warnings to be printed with `{:?}` for debugging.
+// - `PartialEq`: Allows comparing warnings, e.g. in assertions.
+// - `Serialize`: Enables serialization of warnings into formats like JSON.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum LexerWarning {
+    // An identifier that is a single edit away from a keyword, e.g. `retrun` or `viod`.
+    // It is lexed as an identifier as usual, but the author most likely meant the keyword.
+    // Only produced when enabled with `LexerBuilder::warn_keyword_typos`.
+    #[serde(rename = "possible_keyword_typo")]
+    PossibleKeywordTypo {
+        found: String,      // The identifier as written, e.g. "retrun".
+        suggestion: String, // The keyword it resembles, e.g. "return".
+        pos: usize,         // The byte offset where the identifier starts.
+        end: usize,         // The byte offset just after the identifier.
+        line: usize,        // The line number of `pos`.
+        column: usize,      // The column number of `pos`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<String>, // The name of the file, if the input has one.
+    },
+
+    // A constant with a leading zero, e.g. `007`. It is lexed as the decimal constant 7, but
+    // in C the same spelling is an octal literal, so the author may expect a different value.
+    #[serde(rename = "leading_zero")]
+    LeadingZero {
+        constant: String, // The constant as written, e.g. "007".
+        pos: usize,       // The byte offset where the constant starts.
+        end: usize,       // The byte offset just after the constant.
+        line: usize,      // The line number of `pos`.
+        column: usize,    // The column number of `pos`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<String>, // The name of the file, if the input has one.
+    },
+
+    // A `/*` inside a block comment, e.g. `/* a /* b */`. Block comments do not nest, so the
+    // comment ends at the first `*/`; a `*/` meant to close the outer comment would be left
+    // over. The warning covers the inner `/*`.
+    #[serde(rename = "nested_comment_opener")]
+    NestedCommentOpener {
+        pos: usize,    // The byte offset of the inner `/*`.
+        end: usize,    // The byte offset just after it.
+        line: usize,   // The line number of `pos`.
+        column: usize, // The column number of `pos`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<String>, // The name of the file, if the input has one.
+    },
+}
+
+// --- Helper Methods ---
+impl LexerWarning {
+    // `message` returns the description of the warning without its location, e.g.
+    // "Identifier 'retrun' looks like a typo; did you mean the keyword 'return'?".
+    pub fn message(&self) -> String {
+        match self {
+            LexerWarning::PossibleKeywordTypo {
+                found, suggestion, ..
+            } => format!(
+                "Identifier '{}' looks like a typo; did you mean the keyword '{}'?",
+                found, suggestion
+            ),
+            LexerWarning::LeadingZero { constant, .. } => format!(
+                "Constant '{}' has a leading zero; it is decimal here, but octal in C",
+                constant
+            ),
+            LexerWarning::NestedCommentOpener { .. } => {
+                "'/*' inside a block comment; block comments do not nest".to_string()
+            }
+        }
+    }
+
+    // `location` returns the `(pos, line, column)` shared by every variant.
+    pub(crate) fn location(&self) -> (usize, usize, usize) {
+        match self {
+            LexerWarning::PossibleKeywordTypo {
+                pos, line, column, ..
+            }
+            | LexerWarning::LeadingZero {
+                pos, line, column, ..
+            }
+            | LexerWarning::NestedCommentOpener {
+                pos, line, column, ..
+            } => (*pos, *line, *column),
+        }
+    }
+
+    // `span` returns the byte range of the text the warning is about.
+    pub fn span(&self) -> Span {
+        match self {
+            LexerWarning::PossibleKeywordTypo { pos, end, .. }
+            | LexerWarning::LeadingZero { pos, end, .. }
+            | LexerWarning::NestedCommentOpener { pos, end, .. } => Span::new(*pos, *end),
+        }
+    }
+
+    // `file` returns the name of the file the warning occurred in, or `None` for unnamed input.
+    pub fn file(&self) -> Option<&str> {
+        match self {
+            LexerWarning::PossibleKeywordTypo { file, .. }
+            | LexerWarning::LeadingZero { file, .. }
+            | LexerWarning::NestedCommentOpener { file, .. } => file.as_deref(),
+        }
+    }
+
+    // `to_gcc_string` formats the warning on a single line, like `LexerError::to_gcc_string`:
+    //
+    //   main.c:1:5: warning: Identifier 'retrun' looks like a typo; did you mean the keyword 'return'?
+    pub fn to_gcc_string(&self) -> String {
+        let (_, line, column) = self.location();
+        format!(
+            "{}:{}:{}: warning: {}",
+            self.file().unwrap_or("<input>"),
+            line,
+            column,
+            self.message()
+        )
+    }
+
+    // `render` formats the warning like `LexerError::render` does, labelled as a warning:
+    //
+    //   warning: Identifier 'retrun' looks like a typo; did you mean the keyword 'return'?
+    //    --> 1:5
+    //     |
+    //   1 | int retrun 0;
+    //     |     ^^^^^^
+    pub fn render(&self, source: &str) -> String {
+        self.render_with_tab_width(source, DEFAULT_TAB_WIDTH)
+    }
+
+    // `render_with_tab_width` works like `render`, with tabs expanded to `tab_width` columns.
+    pub fn render_with_tab_width(&self, source: &str, tab_width: usize) -> String {
+        let (_, line, column) = self.location();
+        render_snippet(
+            source,
+            self.span(),
+            self.file(),
+            (line, column),
+            "warning",
+            &self.message(),
+            tab_width,
+        )
+    }
+
+    // `render_colored` works like `LexerError::render_colored`, with the `warning` label and
+    // the carets in yellow.
+    pub fn render_colored(&self, source: &str, tab_width: usize) -> String {
+        colorize(
+            &self.render_with_tab_width(source, tab_width),
+            WARNING_COLOR,
+        )
+    }
+}
+
+// `Display` prints the message followed by the location, in the same format as `LexerError`,
+// e.g. "Identifier 'viod' looks like a typo; did you mean the keyword 'void'? at line 1,
+// column 1 (bytes 0..4)".
+impl fmt::Display for LexerWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (_, line, column) = self.location();
+        let span = self.span();
+        write!(f, "{}", self.message())?;
+        if let Some(file) = self.file() {
+            write!(f, " in {}", file)?;
+        }
+        write!(
+            f,
+            " at line {}, column {} (bytes {}..{})",
+            line, column, span.start, span.end
+        )
+    }
+}
+
+// `is_one_edit_apart` returns `true` if `a` can be turned into `b` with exactly one edit:
+// inserting, deleting or replacing a single character, or swapping two adjacent characters.
+// Swaps count as a single edit because they are the most common typo (`retrun`, `viod`).
+// Identical strings are zero edits apart and return `false`.
+pub(crate) fn is_one_edit_apart(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Skip the common prefix; the single edit must happen right where the strings diverge.
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    match (a.len(), b.len()) {
+        (0, 0) => false,
+        // Replacing the first differing character, or swapping it with the next one.
+        (la, lb) if la == lb => {
+            a[1..] == b[1..] || (la >= 2 && a[0] == b[1] && a[1] == b[0] && a[2..] == b[2..])
+        }
+        // Deleting the first differing character from the longer string.
+        (la, lb) if la == lb + 1 => a[1..] == *b,
+        (la, lb) if la + 1 == lb => *a == b[1..],
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{Lexer, LexerBuilder};
+
+    // `warnings` lexes `input` with keyword typo warnings enabled.
+    fn warnings(input: &str) -> Vec<LexerWarning> {
+        let mut lexer = LexerBuilder::new().warn_keyword_typos(true).build(input);
+        lexer.tokenize_with_warnings().unwrap().1
+    }
+
+    // Swaps, replacements, insertions and deletions are each a single edit.
+    #[test]
+    fn one_edit_apart() {
+        assert!(is_one_edit_apart("retrun", "return"));
+        assert!(is_one_edit_apart("viod", "void"));
+        assert!(is_one_edit_apart("imt", "int"));
+        assert!(is_one_edit_apart("in", "int"));
+        assert!(is_one_edit_apart("intt", "int"));
+        assert!(!is_one_edit_apart("int", "int"));
+        assert!(!is_one_edit_apart("rtuern", "return"));
+        assert!(!is_one_edit_apart("main", "int"));
+    }
+
+    // `retrun` suggests `return`; the keyword itself produces no warning.
+    #[test]
+    fn typos_suggest_the_keyword() {
+        assert_eq!(
+            warnings("retrun 0;"),
+            vec![LexerWarning::PossibleKeywordTypo {
+                found: "retrun".to_string(),
+                suggestion: "return".to_string(),
+                pos: 0,
+                end: 6,
+                line: 1,
+                column: 1,
+                file: None,
+            }]
+        );
+        assert!(warnings("return 0;").is_empty());
+    }
+
+    // Typo warnings are opt-in.
+    #[test]
+    fn typo_warnings_are_off_by_default() {
+        let mut lexer = LexerBuilder::new().build("retrun 0;");
+        assert!(lexer.tokenize_with_warnings().unwrap().1.is_empty());
+    }
+
+    // Clean input produces no warnings at all.
+    #[test]
+    fn clean_input_has_no_warnings() {
+        let mut lexer = Lexer::new("int main() { return 0; }");
+        let (tokens, warnings) = lexer.tokenize_with_warnings().unwrap();
+        assert_eq!(tokens.len(), 9);
+        assert!(warnings.is_empty());
+    }
+
+    // Warnings are displayed and serialized like errors.
+    #[test]
+    fn warning_display_and_json() {
+        let mut lexer = LexerBuilder::new().file_name("a.c").build("x 007;");
+        let (_, warnings) = lexer.tokenize_with_warnings().unwrap();
+        assert_eq!(
+            warnings[0].to_string(),
+            "Constant '007' has a leading zero; it is decimal here, but octal in C in a.c at line 1, column 3 (bytes 2..5)"
+        );
+        assert_eq!(
+            serde_json::to_string(&warnings[0]).unwrap(),
+            r#"{"leading_zero":{"constant":"007","pos":2,"end":5,"line":1,"column":3,"file":"a.c"}}"#
+        );
+    }
+
+    // Errors still stop lexing, even with warnings collected before them.
+    #[test]
+    fn errors_still_stop_tokenize_with_warnings() {
+        let mut lexer = Lexer::new("x 007 $");
+        assert!(lexer.tokenize_with_warnings().is_err());
+    }
+
+    // A `/*` inside a block comment is reported at the inner `/*`.
+    #[test]
+    fn nested_comment_opener() {
+        assert_eq!(
+            warnings("x\n/* a /* b */ y"),
+            vec![LexerWarning::NestedCommentOpener {
+                pos: 7,
+                end: 9,
+                line: 2,
+                column: 6,
+                file: None,
+            }]
+        );
+    }
+
+    // Plain and empty block comments, and `/*` in a line comment, are not nested openers.
+    #[test]
+    fn comments_without_nested_openers() {
+        assert!(warnings("/* a * / b */ x").is_empty());
+        assert!(warnings("/**/ x").is_empty());
+        assert!(warnings("/***/ x").is_empty());
+        assert!(warnings("x // a /* b\ny").is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+//! `obv_lexer` is a lexer for a subset of C: it turns source code into a list of tokens
+//! (keywords, identifiers, constants, literals and punctuation), or a `LexerError` pointing
+//! at the first thing it cannot lex.
+//!
+//! The quickest way in is [`tokenize`]:
+//!
+//! ```
+//! use obv_lexer::{Token, tokenize};
+//!
+//! let tokens = tokenize("int main(void) { return 2; }").unwrap();
+//! assert_eq!(tokens[0], Token::KwInt);
+//! assert_eq!(tokens[1], Token::Identifier("main".to_string()));
+//! assert_eq!(tokens[7].as_constant(), Some(2));
+//! ```
+//!
+//! Errors carry their location and a stable code:
+//!
+//! ```
+//! use obv_lexer::{LexerErrorKind, tokenize};
+//!
+//! let error = tokenize("int x = 1;").unwrap_err();
+//! assert_eq!(error.kind(), &LexerErrorKind::UnexpectedCharacter { char: '=' });
+//! assert_eq!((error.line(), error.column()), (1, 7));
+//! assert_eq!(error.code(), "E0001");
+//! ```
+//!
+//! For anything beyond the defaults, create a [`Lexer`], usually through a
+//! [`LexerBuilder`]:
+//!
+//! ```
+//! use obv_lexer::{LexerBuilder, Token};
+//!
+//! let mut lexer = LexerBuilder::new().keep_comments(true).build("return; // done");
+//! let tokens = lexer.tokenize_all().unwrap();
+//! assert_eq!(tokens.last(), Some(&Token::Comment("// done".to_string())));
+//! ```
+
+// --- 1. Declare the `lexer` module ---
+// The whole lexer lives in `src/lexer/` (see `src/lexer/mod.rs`). The module is public, so
+// its complete API can be reached as `obv_lexer::lexer::...`, e.g.
+// `obv_lexer::lexer::LineIndex` or `obv_lexer::lexer::to_html`.
+pub mod lexer;
+
+// --- 2. Re-export the essentials ---
+// The types almost every user of the library needs are also re-exported at the crate root,
+// so `use obv_lexer::Token;` works as well as `use obv_lexer::lexer::Token;`. Both paths name
+// the same items.
+pub use lexer::{
+    Lexer, LexerBuilder, LexerError, LexerErrorKind, LexerOptions, Span, Token, TokenRef,
+};
+
+// --- 3. Convenience entry point ---
+// `tokenize` lexes `source` with the default options and returns all of its tokens, or the
+// first error. It is a shortcut for `Lexer::new(source).tokenize_all()`.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, LexerError> {
+    Lexer::new(source).tokenize_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `tokenize` gives the same tokens, or the same error, as a default `Lexer`.
+    #[test]
+    fn tokenize_matches_a_default_lexer() {
+        for source in [
+            "int main(void) { return 2; }",
+            "",
+            "// only a comment",
+            "x $ y",
+        ] {
+            assert_eq!(tokenize(source), Lexer::new(source).tokenize_all());
+        }
+        assert_eq!(
+            tokenize("return x;"),
+            Ok(vec![
+                Token::KwReturn,
+                Token::Identifier("x".to_string()),
+                Token::Semicolon
+            ])
+        );
+    }
+
+    // The root re-exports name the same items as the paths under `lexer`.
+    #[test]
+    fn root_reexports_are_the_lexer_items() {
+        let token: lexer::Token = Token::KwInt;
+        let error: lexer::LexerError = tokenize("$").unwrap_err();
+        assert_eq!(token, lexer::Token::KwInt);
+        assert_eq!(
+            error.kind(),
+            &lexer::LexerErrorKind::UnexpectedCharacter { char: '$' }
+        );
+    }
+}
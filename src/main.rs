@@ -1,18 +1,20 @@
-// --- 1. Declare the `lexer` module ---
-// The `mod lexer;` statement tells the Rust compiler to look for and include
-// the `lexer` module. Based on Rust's module discovery rules, it will find
-// `src/lexer/mod.rs` (because a directory `src/lexer/` exists) and treat
-// that file as the root of the `lexer` module.
-mod lexer;
+// --- 1. The `obv_lexer` library ---
+// The lexer itself is the `obv_lexer` library crate (see `src/lib.rs`), which Cargo builds
+// from the same package. This binary is a thin command-line front end to it: it reads the
+// input, runs the lexer and prints the result. It uses the library like any other crate would,
+// through its public API only.
 
 // --- 2. Import necessary items ---
 // `use` statements bring specific items from modules into the current scope,
 // allowing them to be used without full qualification.
 
-// Import `Lexer`, `Token`, and `LexerError` from our `lexer` module.
+// Import `Lexer`, `Token`, `LexerError` and friends from the library's `lexer` module.
 // These were re-exported in `src/lexer/mod.rs`, making them directly
 // accessible under the `lexer` namespace.
-use lexer::{Lexer, LexerError, Token};
+use obv_lexer::lexer::{
+    IndexBase, Lexer, LexerBuilder, LexerError, LexerWarning, LineIndex, LineTokens, Numbering,
+    SCHEMA, Span, Token, group_by_line, to_html, to_sexpr,
+};
 
 // Import from the standard library (`std`):
 // `env` module for interacting with the execution environment,
@@ -21,16 +23,27 @@ use lexer::{Lexer, LexerError, Token};
 // `io` module for input/output operations.
 // `self` imports the `io` module itself (for `io::Result`, `io::stderr`).
 // `Write` trait is imported for methods like `write_all` on `stderr`.
+// `time` module for measuring how long lexing takes (`Instant`, `Duration`), used by `--stats`.
+// `path` and `sync::mpsc` are used by `--watch`, to find the watched file's directory and to
+// receive file system events from the watcher thread.
 use std::{
+    collections::BTreeMap,
     env, fs,
-    io::{self, Write},
+    io::{self, BufRead, BufWriter, IsTerminal, Write},
+    path::Path,
+    sync::mpsc,
+    time::{Duration, Instant},
 };
 
 // Import from third-party crates (defined in `Cargo.toml`):
-use serde::Serialize; // The `Serialize` trait from `serde` is needed for the
-                      // `CompilerOutput` enum to derive it, enabling JSON serialization.
-use serde_json; // The `serde_json` crate provides functions for serializing
-                // Rust data structures to JSON strings and vice-versa.
+// The `Serialize` trait from `serde` is needed for the `CompilerOutput` enum
+// to derive it, enabling JSON serialization.
+use serde::Serialize;
+// The `serde_json` crate (used below via its full path, e.g. `serde_json::to_string_pretty`)
+// provides functions for serializing Rust data structures to JSON strings and vice-versa.
+// The `notify` crate reports changes to files, used by `--watch` to re-lex a modified file.
+// The `rmp_serde` crate (used via its full path) serializes to MessagePack, for `--format msgpack`.
+use notify::{RecursiveMode, Watcher};
 
 // --- 3. Define `CompilerOutput` Enum ---
 // This enum is a utility for structuring the program's final output,
@@ -39,18 +52,755 @@ use serde_json; // The `serde_json` crate provides functions for serializing
 //
 // `#[derive(serde::Serialize)]`: This attribute automatically generates the code
 // needed to serialize `CompilerOutput` instances into formats supported by `serde` (like JSON).
+//
+// The enum is serialized "externally tagged" (serde's default): the variant name is the
+// single key of an object holding the variant's data, so every output is either
+// `{"Success": [...tokens...]}` or `{"Error": [...errors...]}`. Consumers can tell the two
+// apart by the key alone, without inspecting the shape of the data (which `#[serde(untagged)]`
+// would require, and which would become ambiguous once both sides are arrays).
 #[derive(Serialize)]
-// `#[serde(untagged)]`: This is a `serde` attribute that affects how enums are serialized.
-// "Untagged" means that when serializing, `serde` will not add an extra field to the JSON
-// to indicate which variant of the enum it is. Instead, it will try to serialize the
-// data *inside* the variant directly.
-// - If `CompilerOutput::Success(tokens)`, the JSON will be an array `[...]` (the tokens).
-// - If `CompilerOutput::Error(error)`, the JSON will be an object `{...}` (the error details).
-// This relies on the serialized forms of `Vec<Token>` and `LexerError` being distinct.
-// #[serde(untagged)]
 enum CompilerOutput {
     Success(Vec<Token>), // Variant for successful lexing, holding the vector of tokens.
-    Error(LexerError),   // Variant for a lexing error, holding the `LexerError` instance.
+    // Variant for successful lexing in `--spans` mode, holding each token with its byte range.
+    // `#[serde(rename = "Success")]` keeps the same top-level key as the plain token list,
+    // so consumers only need to handle the different shape of the array elements.
+    #[serde(rename = "Success")]
+    SpannedSuccess(Vec<SpannedToken>),
+    // Variant for successful lexing in `--by-line` mode, holding the tokens grouped per line.
+    #[serde(rename = "Success")]
+    LineSuccess(Vec<LineTokens>),
+    // Variant for failed lexing, holding the `LexerError`s that occurred, in source order.
+    // The lexer currently stops at the first error, so there is exactly one, but the errors
+    // are always serialized as an array so consumers are ready for lexers that recover and
+    // report several. Each error is serialized with its stable error code (see
+    // `LexerError::code`).
+    Error(Vec<LexerError>),
+}
+
+// --- 3a. Define `OutputDocument` ---
+// The complete JSON document written to the output: the `CompilerOutput`, plus the optional
+// line-starts table requested with `--lines`, the token summary requested with `--summary`,
+// and the warnings collected while lexing.
+// `#[serde(flatten)]` inlines the `"Success"`/`"Error"` key of `output` into the document, so
+// without `--lines` the document is exactly the serialized `CompilerOutput`. With `--lines`,
+// a `"lines"` key holding the byte offset at which each source line starts is added next to it,
+// e.g. `{"Success": [...], "lines": [0, 10, 27]}`. `--summary` likewise adds a `"summary"` key,
+// and any warnings (see `LexerWarning`) are listed under a `"warnings"` key, so that they are
+// kept apart from the `"Success"`/`"Error"` result. Without warnings, the key is left out.
+#[derive(Serialize)]
+struct OutputDocument {
+    #[serde(flatten)]
+    output: CompilerOutput,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lines: Option<Vec<usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<BTreeMap<&'static str, usize>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<LexerWarning>,
+}
+
+// --- 3b. Define `SpannedToken` ---
+// The element type of the `--spans` output: a token together with the byte range it came from.
+// `#[serde(flatten)]` inlines the span's `start` and `end` fields next to `token`, producing
+// e.g. `{"token": {"Identifier": "main"}, "start": 4, "end": 8}`.
+#[derive(Serialize)]
+struct SpannedToken {
+    token: Token,
+    #[serde(flatten)]
+    span: Span,
+}
+
+// --- 3c. Define `CliOptions` ---
+// The parsed command-line arguments.
+// - `file_path`: The source file to lex. `None` means the built-in example program is used.
+// - `spans`: Set by `--spans`. Emits each token with its byte range instead of a bare token list.
+//   This changes the shape of the JSON output, which is why it is opt-in.
+// - `output_path`: Set by `--output <path>`. The JSON is written to this file (created or
+//   truncated) instead of being printed to `stdout`.
+// - `ndjson`: Set by `--ndjson`. Streams one compact JSON value per token per line instead of
+//   building a single JSON document, so the tokens never have to be held in memory together.
+// - `by_line`: Set by `--by-line`. Groups the tokens by the source line they start on, as an
+//   array of `{"line": N, "tokens": [...]}` objects (see `group_by_line`).
+// - `lines`: Set by `--lines`. Adds the line-starts table to the JSON document (see
+//   `OutputDocument`), so consumers can map byte offsets to lines without the source file.
+// - `stats`: Set by `--stats`. Prints timing and throughput figures for lexing to `stderr`.
+// - `table`: Set by `--table`. Prints an aligned, human-readable table of the tokens instead
+//   of JSON (see `format_table`).
+// - `tokens_only`: Set by `--tokens-only`. Prints the `Debug` form of each token on its own
+//   line (e.g. `KwInt`), for grepping, instead of JSON.
+// - `format`: Set by `--format <json|sexpr|html|msgpack>`. `sexpr` prints the tokens as an
+//   S-expression (see `lexer::to_sexpr`), `html` the highlighted source (see
+//   `lexer::to_html`) and `msgpack` the tokens as binary MessagePack instead of JSON.
+// - `summary`: Set by `--summary`. Adds the number of tokens of each category to the JSON
+//   document (see `summarize`).
+// - `tab_width`: Set by `--tab-width <n>`. The tab width used for the columns of errors and
+//   warnings (see `LexerOptions::tab_width`) and for expanding tabs in rendered snippets.
+// - `repl`: Set by `--repl`. Reads lines from `stdin` and prints the tokens of each one (see
+//   `run_repl`) instead of lexing a file.
+// - `watch`: Set by `--watch`. Lexes the file, then keeps watching it and prints a fresh JSON
+//   document each time it is modified (see `run_watch`), until the process is killed.
+// - `max_bytes`: Set by `--max-bytes <n>`. A source file larger than this many bytes is
+//   refused instead of read (see `read_source`). `None` (the default) means no limit.
+// - `max_tokens`: Set by `--max-tokens <n>`. Lexing stops with an error once the input has
+//   more than this many tokens (see `LexerOptions::max_tokens`). `None` (the default) means
+//   no limit.
+// - `quiet`: Set by `--quiet`. Suppresses the informational messages on `stderr` (the echo of
+//   the source code and its banners); lexing errors and failures are still reported.
+// - `error_format`: Set by `--error-format <human|gcc>`. How lexing errors are shown on
+//   `stderr`; the JSON output is the same either way.
+// - `numbering`: Set by `--line-base <0|1>` and `--column-base <0|1>`. Whether the lines and
+//   columns of errors (in the JSON and on `stderr`) are 0- or 1-based. Both default to 1.
+// - `schema`: Set by `--schema`. Prints the description of the JSON output (see
+//   `lexer::SCHEMA`) and exits, without lexing anything.
+struct CliOptions {
+    file_path: Option<String>,
+    spans: bool,
+    output_path: Option<String>,
+    ndjson: bool,
+    by_line: bool,
+    lines: bool,
+    stats: bool,
+    summary: bool,
+    table: bool,
+    tokens_only: bool,
+    format: OutputFormat,
+    quiet: bool,
+    tab_width: usize,
+    repl: bool,
+    watch: bool,
+    max_bytes: Option<u64>,
+    max_tokens: Option<usize>,
+    error_format: ErrorFormat,
+    numbering: Numbering,
+    schema: bool,
+}
+
+// `ErrorFormat` selects how lexing errors are printed on `stderr`.
+// - `Human`: rustc-style, with the source line and carets under the error (see `LexerError::render`).
+//   With `color`, the snippet is colored with ANSI escape codes (see `LexerError::render_colored`).
+//   `--error-format human` leaves `color` off; `main` turns it on when `stderr` can show colors
+//   (see `stderr_supports_color`).
+// - `Gcc`: a single `file:line:col: error: message [code]` line (see `LexerError::to_gcc_string`),
+//   which editors and build tools can parse to jump to the error.
+#[derive(Clone, Copy, PartialEq)]
+enum ErrorFormat {
+    Human { color: bool },
+    Gcc,
+}
+
+// `stderr_supports_color` decides whether diagnostics on `stderr` are colored: only when it is a
+// terminal (not a file or a pipe), and the user has not opted out by setting the `NO_COLOR`
+// environment variable to a non-empty value (see https://no-color.org).
+fn stderr_supports_color() -> bool {
+    io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+// `OutputFormat` selects the format of the token output on `stdout` (or the `--output` file).
+// - `Json`: The JSON document described in the README (the default).
+// - `Sexpr`: A single S-expression, e.g. `(tokens (kw-int) (identifier "main") ...)`.
+// - `Html`: The source as syntax-highlighted HTML, e.g. `<span class="kw">int</span> ...`.
+// - `Msgpack`: The token list as MessagePack, a compact binary encoding of the same data as
+//   the JSON `"Success"` array (tokens are encoded through their `Serialize` implementation).
+#[derive(Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Json,
+    Sexpr,
+    Html,
+    Msgpack,
+}
+
+impl OutputFormat {
+    // `flag` returns how the format is selected on the command line, for error messages,
+    // or `None` for the default JSON format.
+    fn flag(self) -> Option<&'static str> {
+        match self {
+            OutputFormat::Json => None,
+            OutputFormat::Sexpr => Some("--format sexpr"),
+            OutputFormat::Html => Some("--format html"),
+            OutputFormat::Msgpack => Some("--format msgpack"),
+        }
+    }
+}
+
+// `format_error` formats `error` for `stderr` in the selected `format`.
+// `source` is the input the error was produced from, needed for the human format's snippet,
+// in which tabs are expanded to `tab_width` columns.
+fn format_error(error: &LexerError, source: &str, format: ErrorFormat, tab_width: usize) -> String {
+    match format {
+        ErrorFormat::Human { color: false } => error.render_with_tab_width(source, tab_width),
+        ErrorFormat::Human { color: true } => error.render_colored(source, tab_width),
+        ErrorFormat::Gcc => error.to_gcc_string(),
+    }
+}
+
+// `format_warning` formats `warning` for `stderr` in the selected `format`, like `format_error`.
+fn format_warning(
+    warning: &LexerWarning,
+    source: &str,
+    format: ErrorFormat,
+    tab_width: usize,
+) -> String {
+    match format {
+        ErrorFormat::Human { color: false } => warning.render_with_tab_width(source, tab_width),
+        ErrorFormat::Human { color: true } => warning.render_colored(source, tab_width),
+        ErrorFormat::Gcc => warning.to_gcc_string(),
+    }
+}
+
+// `USAGE` is the one-line summary of the command-line syntax printed after argument errors.
+const USAGE: &str = "Usage: obv_lexer [--spans | --ndjson | --by-line | --table | --tokens-only | --format <json|sexpr|html|msgpack>] [--lines] [--stats] [--summary] [--quiet] [--repl | --watch] [--tab-width <n>] [--max-bytes <n>] [--max-tokens <n>] [--output <path>] [--error-format <human|gcc>] [--line-base <0|1>] [--column-base <0|1>] [--schema] [path_to_source_file.c]";
+
+// `NDJSON_FLUSH_INTERVAL` is how many NDJSON lines are written between explicit flushes.
+// Flushing periodically lets a consumer reading the stream see tokens as they are produced,
+// without paying for a system call on every single line.
+const NDJSON_FLUSH_INTERVAL: usize = 1024;
+
+// `WATCH_DEBOUNCE` is how long `--watch` waits for the file system to go quiet before
+// re-lexing. Saving a file often produces a burst of events (truncate, write, rename, ...);
+// they are all handled by a single re-lex once no new event has arrived for this long.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+// `parse_args` turns the raw command-line arguments (without the program name) into `CliOptions`.
+// Arguments starting with `--` are flags; the first other argument is the source file path.
+// An unknown flag or a second file path is reported as an error message.
+// Flags that take a value (like `--output <path>`) consume the argument that follows them.
+fn parse_args(args: &[String]) -> Result<CliOptions, String> {
+    let mut options = CliOptions {
+        file_path: None,
+        spans: false,
+        output_path: None,
+        ndjson: false,
+        by_line: false,
+        lines: false,
+        stats: false,
+        summary: false,
+        table: false,
+        tokens_only: false,
+        format: OutputFormat::Json,
+        quiet: false,
+        tab_width: 1,
+        repl: false,
+        watch: false,
+        max_bytes: None,
+        max_tokens: None,
+        error_format: ErrorFormat::Human { color: false },
+        numbering: Numbering::ONE_BASED,
+        schema: false,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--spans" => options.spans = true,
+            "--ndjson" => options.ndjson = true,
+            "--by-line" => options.by_line = true,
+            "--lines" => options.lines = true,
+            "--stats" => options.stats = true,
+            "--summary" => options.summary = true,
+            "--table" => options.table = true,
+            "--tokens-only" => options.tokens_only = true,
+            "--quiet" => options.quiet = true,
+            "--repl" => options.repl = true,
+            "--watch" => options.watch = true,
+            "--schema" => options.schema = true,
+            "--output" => {
+                let path = args.next().ok_or("Flag '--output' requires a file path")?;
+                options.output_path = Some(path.clone());
+            }
+            "--error-format" => {
+                options.error_format = match args.next().map(String::as_str) {
+                    Some("human") => ErrorFormat::Human { color: false },
+                    Some("gcc") => ErrorFormat::Gcc,
+                    Some(other) => {
+                        return Err(format!(
+                            "Flag '--error-format' expects 'human' or 'gcc', got '{}'",
+                            other
+                        ));
+                    }
+                    None => return Err("Flag '--error-format' requires a value".to_string()),
+                }
+            }
+            "--format" => {
+                options.format = match args.next().map(String::as_str) {
+                    Some("json") => OutputFormat::Json,
+                    Some("sexpr") => OutputFormat::Sexpr,
+                    Some("html") => OutputFormat::Html,
+                    Some("msgpack") => OutputFormat::Msgpack,
+                    Some(other) => {
+                        return Err(format!(
+                            "Flag '--format' expects 'json', 'sexpr', 'html' or 'msgpack', got '{}'",
+                            other
+                        ));
+                    }
+                    None => return Err("Flag '--format' requires a value".to_string()),
+                }
+            }
+            "--tab-width" => {
+                let value = args.next().ok_or("Flag '--tab-width' requires a number")?;
+                options.tab_width = match value.parse() {
+                    Ok(width) if width > 0 => width,
+                    _ => {
+                        return Err(format!(
+                            "Flag '--tab-width' expects a positive number, got '{}'",
+                            value
+                        ));
+                    }
+                };
+            }
+            "--max-bytes" => {
+                let value = args.next().ok_or("Flag '--max-bytes' requires a number")?;
+                let max_bytes = value.parse().map_err(|_| {
+                    format!(
+                        "Flag '--max-bytes' expects a number of bytes, got '{}'",
+                        value
+                    )
+                })?;
+                options.max_bytes = Some(max_bytes);
+            }
+            "--max-tokens" => {
+                let value = args.next().ok_or("Flag '--max-tokens' requires a number")?;
+                let max_tokens = value.parse().map_err(|_| {
+                    format!(
+                        "Flag '--max-tokens' expects a number of tokens, got '{}'",
+                        value
+                    )
+                })?;
+                options.max_tokens = Some(max_tokens);
+            }
+            "--line-base" => options.numbering.line = parse_base(arg, args.next())?,
+            "--column-base" => options.numbering.column = parse_base(arg, args.next())?,
+            flag if flag.starts_with("--") => return Err(format!("Unknown flag '{}'", flag)),
+            path => {
+                if options.file_path.is_some() {
+                    return Err(format!("Unexpected extra argument '{}'", path));
+                }
+                options.file_path = Some(path.to_string());
+            }
+        }
+    }
+    // `--spans`, `--ndjson`, `--by-line`, `--table`, `--tokens-only` and the non-JSON
+    // `--format`s each select a different output shape.
+    let format_flag = options.format.flag();
+    let shapes = [
+        ("--spans", options.spans),
+        ("--ndjson", options.ndjson),
+        ("--by-line", options.by_line),
+        ("--table", options.table),
+        ("--tokens-only", options.tokens_only),
+        (format_flag.unwrap_or("--format"), format_flag.is_some()),
+    ];
+    let selected: Vec<&str> = shapes
+        .iter()
+        .filter(|(_, set)| *set)
+        .map(|(flag, _)| *flag)
+        .collect();
+    if let [first, second, ..] = selected[..] {
+        return Err(format!(
+            "Flags '{}' and '{}' cannot be combined",
+            first, second
+        ));
+    }
+    // In NDJSON mode, lexing and writing are interleaved, so there is no lexing time to report.
+    if options.stats && options.ndjson {
+        return Err("Flags '--stats' and '--ndjson' cannot be combined".to_string());
+    }
+    // NDJSON has no document to add the line-starts table to.
+    if options.lines && options.ndjson {
+        return Err("Flags '--lines' and '--ndjson' cannot be combined".to_string());
+    }
+    // Nor to add the summary to; the tokens are never all known at once either.
+    if options.summary && options.ndjson {
+        return Err("Flags '--summary' and '--ndjson' cannot be combined".to_string());
+    }
+    // The table, the token list, the S-expression, the HTML and the MessagePack are not JSON,
+    // so there is no document to add keys to.
+    let text_output = if options.table {
+        Some("--table")
+    } else if options.tokens_only {
+        Some("--tokens-only")
+    } else {
+        format_flag
+    };
+    // The REPL reads `stdin` and prints one line of output per input line, so it takes no file
+    // and no flags that shape or redirect the output of a whole file.
+    if options.repl {
+        let conflicts = [
+            ("--spans", options.spans),
+            ("--ndjson", options.ndjson),
+            ("--by-line", options.by_line),
+            ("--table", options.table),
+            ("--tokens-only", options.tokens_only),
+            (format_flag.unwrap_or("--format"), format_flag.is_some()),
+            ("--lines", options.lines),
+            ("--summary", options.summary),
+            ("--stats", options.stats),
+            ("--output", options.output_path.is_some()),
+        ];
+        if let Some((flag, _)) = conflicts.iter().find(|(_, set)| *set) {
+            return Err(format!("Flags '{}' and '--repl' cannot be combined", flag));
+        }
+        if let Some(path) = &options.file_path {
+            return Err(format!(
+                "Flag '--repl' takes no source file, got '{}'",
+                path
+            ));
+        }
+    }
+    // Watch mode prints a JSON document to `stdout` after every change, so it needs a file to
+    // watch and only supports the plain JSON output (optionally with `--lines`/`--summary`).
+    if options.watch {
+        let conflicts = [
+            ("--repl", options.repl),
+            ("--spans", options.spans),
+            ("--ndjson", options.ndjson),
+            ("--by-line", options.by_line),
+            ("--table", options.table),
+            ("--tokens-only", options.tokens_only),
+            (format_flag.unwrap_or("--format"), format_flag.is_some()),
+            ("--stats", options.stats),
+            ("--output", options.output_path.is_some()),
+        ];
+        if let Some((flag, _)) = conflicts.iter().find(|(_, set)| *set) {
+            return Err(format!("Flags '{}' and '--watch' cannot be combined", flag));
+        }
+        if options.file_path.is_none() {
+            return Err("Flag '--watch' requires a source file".to_string());
+        }
+    }
+    if let Some(text_flag) = text_output {
+        for (flag, set) in [("--lines", options.lines), ("--summary", options.summary)] {
+            if set {
+                return Err(format!(
+                    "Flags '{}' and '{}' cannot be combined",
+                    flag, text_flag
+                ));
+            }
+        }
+    }
+    Ok(options)
+}
+
+// `parse_base` reads the value of a `--line-base` or `--column-base` flag, which must be
+// `0` or `1`. `flag` is only used in the error message.
+fn parse_base(flag: &str, value: Option<&String>) -> Result<IndexBase, String> {
+    match value.map(String::as_str) {
+        Some("0") => Ok(IndexBase::Zero),
+        Some("1") => Ok(IndexBase::One),
+        Some(other) => Err(format!("Flag '{}' expects 0 or 1, got '{}'", flag, other)),
+        None => Err(format!("Flag '{}' requires a value (0 or 1)", flag)),
+    }
+}
+
+// `write_ndjson` streams the lexer's tokens to `writer` as NDJSON (newline-delimited JSON):
+// each token is serialized compactly on its own line, e.g. `{"Identifier":"main"}`.
+// Tokens are pulled one at a time from `Lexer::tokens`, so they are never all in memory.
+// If lexing fails, a final `{"Error": ...}` line describing the error is written.
+// Returns `Ok(true)` if the whole input was lexed, `Ok(false)` if a lexing error occurred,
+// or an `io::Error` if writing failed.
+// The error is also shown on `stderr`, formatted as selected by `error_format` (with tabs
+// `tab_width` columns wide).
+fn write_ndjson<W: Write>(
+    lexer: &mut Lexer,
+    source: &str,
+    error_format: ErrorFormat,
+    tab_width: usize,
+    writer: &mut W,
+) -> io::Result<bool> {
+    let mut succeeded = true;
+    for (index, result) in lexer.tokens().enumerate() {
+        let line = match result {
+            Ok(token) => serde_json::to_string(&token),
+            Err(e) => {
+                succeeded = false;
+                eprintln!("{}", format_error(&e, source, error_format, tab_width));
+                serde_json::to_string(&CompilerOutput::Error(vec![e]))
+            }
+        }
+        // Serialization failures are turned into `io::Error`s so `?` can propagate them.
+        .map_err(io::Error::other)?;
+        writeln!(writer, "{}", line)?;
+        if (index + 1) % NDJSON_FLUSH_INTERVAL == 0 {
+            writer.flush()?;
+        }
+    }
+    // Warnings are only shown on `stderr`; the NDJSON stream holds tokens and errors only.
+    for warning in lexer.warnings() {
+        eprintln!(
+            "{}",
+            format_warning(warning, source, error_format, tab_width)
+        );
+    }
+    writer.flush()?;
+    Ok(succeeded)
+}
+
+// `summarize` counts the tokens of each category (see `Token::category`), e.g.
+// `{"identifier": 1, "keyword": 1, "punctuation": 4}` for `int main() {}`. Categories without
+// any tokens are left out. A `BTreeMap` keeps the keys sorted, so the JSON is deterministic.
+fn summarize<'t>(tokens: impl IntoIterator<Item = &'t Token>) -> BTreeMap<&'static str, usize> {
+    tokens
+        .into_iter()
+        .fold(BTreeMap::new(), |mut counts, token| {
+            *counts.entry(token.category()).or_insert(0) += 1;
+            counts
+        })
+}
+
+// `run_repl` is the interactive mode selected with `--repl`. It reads `input` line by line
+// until EOF, lexes each line on its own with a fresh `Lexer`, and writes its tokens to `output`
+// as a compact JSON array on one line, e.g. `["KwInt",{"Identifier":"x"},"Semicolon"]`.
+// A lexing error is shown on `stderr` (formatted as selected by `--error-format`) and the loop
+// moves on to the next line. Unless `--quiet` is given, a `> ` prompt is shown on `stderr`
+// before each line, so `stdout` holds nothing but the token arrays.
+fn run_repl<R: BufRead, W: Write>(input: R, output: &mut W, cli: &CliOptions) -> io::Result<()> {
+    let prompt = || {
+        if !cli.quiet {
+            eprint!("> ");
+        }
+    };
+    prompt();
+    for line in input.lines() {
+        let line = line?;
+        let result = LexerBuilder::new()
+            .file_name("<repl>")
+            .numbering(cli.numbering)
+            .tab_width(cli.tab_width)
+            .max_tokens(cli.max_tokens)
+            .build(&line)
+            .tokenize_all();
+        match result {
+            Ok(tokens) => {
+                let json = serde_json::to_string(&tokens).map_err(io::Error::other)?;
+                writeln!(output, "{}", json)?;
+                output.flush()?;
+            }
+            Err(e) => eprintln!(
+                "{}",
+                format_error(&e, &line, cli.error_format, cli.tab_width)
+            ),
+        }
+        prompt();
+    }
+    Ok(())
+}
+
+// `read_source` reads the source file at `path`. With a `max_bytes` limit, the file's size is
+// checked first (from its metadata, without reading it), and a larger file is refused with an
+// `io::ErrorKind::FileTooLarge` error, so that lexing a huge file by accident cannot exhaust
+// the memory.
+fn read_source(path: &str, max_bytes: Option<u64>) -> io::Result<String> {
+    if let Some(max_bytes) = max_bytes {
+        let size = fs::metadata(path)?.len();
+        if size > max_bytes {
+            return Err(io::Error::new(
+                io::ErrorKind::FileTooLarge,
+                format!(
+                    "'{}' is {} bytes, more than the --max-bytes limit of {}",
+                    path, size, max_bytes
+                ),
+            ));
+        }
+    }
+    fs::read_to_string(path)
+}
+
+// `lex_and_print` is the step `--watch` repeats on every change: it reads the file at `path`,
+// lexes it and writes the JSON document (with the `--lines` and `--summary` additions, if
+// requested) to `output`, followed by a newline. Lexing errors and warnings are also shown on
+// `stderr`. A file that cannot be read (e.g. because an editor is in the middle of replacing
+// it) is reported on `stderr` and skipped, so that watching can continue.
+fn lex_and_print<W: Write>(path: &str, cli: &CliOptions, output: &mut W) -> io::Result<()> {
+    let source = match read_source(path, cli.max_bytes) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error: Failed to read '{}': {}", path, e);
+            return Ok(());
+        }
+    };
+    let mut lexer = LexerBuilder::new()
+        .file_name(path)
+        .numbering(cli.numbering)
+        .tab_width(cli.tab_width)
+        .max_tokens(cli.max_tokens)
+        .build(&source);
+    let result = lexer.tokenize_all();
+    let warnings = lexer.warnings().to_vec();
+    for warning in &warnings {
+        eprintln!(
+            "{}",
+            format_warning(warning, &source, cli.error_format, cli.tab_width)
+        );
+    }
+    let (output_value, summary) = match result {
+        Ok(tokens) => {
+            let summary = cli.summary.then(|| summarize(&tokens));
+            (CompilerOutput::Success(tokens), summary)
+        }
+        Err(e) => {
+            eprintln!(
+                "{}",
+                format_error(&e, &source, cli.error_format, cli.tab_width)
+            );
+            (CompilerOutput::Error(vec![e]), None)
+        }
+    };
+    let document = OutputDocument {
+        output: output_value,
+        lines: cli
+            .lines
+            .then(|| LineIndex::new(&source).line_starts().to_vec()),
+        summary,
+        warnings,
+    };
+    let json = serde_json::to_string_pretty(&document).map_err(io::Error::other)?;
+    writeln!(output, "{}", json)?;
+    output.flush()
+}
+
+// `run_watch` is the live-feedback mode selected with `--watch`. It lexes the file at `path`
+// once, then watches it and calls `lex_and_print` again after each modification, writing to
+// `stdout`. Rapid successive events are debounced (see `WATCH_DEBOUNCE`). It only returns if
+// the watcher fails; otherwise it runs until the process is killed.
+// The file's directory is watched rather than the file itself, because many editors save by
+// writing a new file and renaming it over the old one, which would end a watch on the file.
+fn run_watch(path: &str, cli: &CliOptions) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    lex_and_print(path, cli, &mut stdout)?;
+
+    let file = Path::new(path);
+    let directory = match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    // The watcher runs on its own thread and sends each event through the channel.
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+    watcher
+        .watch(directory, RecursiveMode::NonRecursive)
+        .map_err(io::Error::other)?;
+    if !cli.quiet {
+        eprintln!("--- Watching '{}' for changes (Ctrl+C to stop) ---", path);
+    }
+
+    for event in &receiver {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("Error: Failed to watch '{}': {}", path, e);
+                continue;
+            }
+        };
+        // Only changes to the watched file matter; its directory may hold other files.
+        let changed = (event.kind.is_create() || event.kind.is_modify())
+            && event
+                .paths
+                .iter()
+                .any(|changed| changed.file_name() == file.file_name());
+        if !changed {
+            continue;
+        }
+        // Debounce: let the burst of events from a single save die down first.
+        while receiver.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+        if !cli.quiet {
+            eprintln!("--- '{}' changed, lexing again ---", path);
+        }
+        lex_and_print(path, cli, &mut stdout)?;
+    }
+    Ok(())
+}
+
+// `format_table` formats spanned tokens as a table with one row per token, e.g.:
+//
+//   #  Kind        Value  Position
+//   0  KwInt       int    1:1
+//   1  Identifier  main   1:5
+//   2  OpenParen   (      1:9
+//
+// - `Value`: The token's source text, e.g. the identifier's name or the punctuation glyph.
+//   Line breaks and other control characters (e.g. in a `Newline` token or a block comment)
+//   are escaped like in a Rust string (`\n`), so every token stays on its own row.
+// - `Position`: The `line:column` the token starts at, numbered as selected by `numbering`,
+//   with tabs advancing the column to the next multiple of `tab_width` (like in errors).
+// Every column is padded to its widest cell, so the columns line up.
+fn format_table(
+    tokens: &[(Token, Span)],
+    source: &str,
+    numbering: Numbering,
+    tab_width: usize,
+) -> String {
+    let index = LineIndex::with_tab_width(source, tab_width);
+    let header = ["#", "Kind", "Value", "Position"].map(String::from);
+    let rows: Vec<[String; 4]> = tokens
+        .iter()
+        .enumerate()
+        .map(|(i, (token, span))| {
+            let (line, column) = index.visual_col(span.start);
+            let (line, column) = numbering.apply((line as usize, column as usize));
+            [
+                i.to_string(),
+                token.name().to_string(),
+                source[span.start..span.end]
+                    .chars()
+                    .map(|c| {
+                        if c.is_control() {
+                            c.escape_debug().to_string()
+                        } else {
+                            c.to_string()
+                        }
+                    })
+                    .collect(),
+                format!("{}:{}", line, column),
+            ]
+        })
+        .collect();
+    let mut widths = [0; 4];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    std::iter::once(&header)
+        .chain(&rows)
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            // The last column is not padded, so lines have no trailing spaces.
+            format!("{}\n", cells.join("  ").trim_end())
+        })
+        .collect()
+}
+
+// `format_stats` formats the `--stats` report for lexing `byte_count` bytes into
+// `token_count` tokens in `elapsed` time, e.g.:
+//
+//   --- Stats ---
+//   Time: 0.250 ms
+//   Tokens: 9
+//   Bytes: 25
+//   Throughput: 100000 bytes/s, 36000 tokens/s
+//
+// If `elapsed` is too short to be measured (zero), the rates are reported as `n/a`
+// instead of dividing by zero.
+fn format_stats(elapsed: Duration, token_count: usize, byte_count: usize) -> String {
+    let seconds = elapsed.as_secs_f64();
+    let throughput = if seconds > 0.0 {
+        format!(
+            "{:.0} bytes/s, {:.0} tokens/s",
+            byte_count as f64 / seconds,
+            token_count as f64 / seconds
+        )
+    } else {
+        "n/a".to_string()
+    };
+    format!(
+        "--- Stats ---\nTime: {:.3} ms\nTokens: {}\nBytes: {}\nThroughput: {}",
+        seconds * 1000.0,
+        token_count,
+        byte_count,
+        throughput
+    )
 }
 
 // `main` is the entry point function for the Rust application.
@@ -64,61 +814,321 @@ fn main() -> io::Result<()> {
     // `.collect()`: Collects the arguments from the iterator into a `Vec<String>`.
     let args: Vec<String> = env::args().collect();
 
+    // Parse everything after the program name (`args[0]`) into `CliOptions`.
+    // Invalid arguments are reported on `stderr` and end the program with a failure exit code.
+    let mut cli = match parse_args(&args[1..]) {
+        Ok(cli) => cli,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+    // Human-readable diagnostics are colored when shown in a terminal.
+    if let ErrorFormat::Human { color } = &mut cli.error_format {
+        *color = stderr_supports_color();
+    }
+
+    // With `--schema`, the description of the JSON output is printed instead of lexing anything.
+    // Like `--version` in other tools, it ignores the other arguments and reads no file.
+    if cli.schema {
+        let schema = serde_json::to_string_pretty(&SCHEMA).map_err(io::Error::other)?;
+        println!("{}", schema);
+        return Ok(());
+    }
+
+    // In `--repl` mode, lines are read and lexed interactively until `stdin` is closed.
+    if cli.repl {
+        return run_repl(io::stdin().lock(), &mut io::stdout().lock(), &cli);
+    }
+
+    // In `--watch` mode, the file is lexed again every time it changes, until the process is killed.
+    if cli.watch
+        && let Some(file_path) = &cli.file_path
+    {
+        return run_watch(file_path, &cli);
+    }
+
     // `source_code`: This variable will hold the source code string to be lexed.
-    let source_code = if args.len() > 1 {
-        // If there is more than one argument, it means a file path was likely provided
-        // as the second argument (`args[1]`).
-        // Get a reference to the file path string.
-        let file_path = &args[1];
+    let source_code = if let Some(file_path) = &cli.file_path {
+        // A file path was provided on the command line.
         // `fs::read_to_string(file_path)`: Attempts to read the entire content of the
         // specified file into a `String`. This operation can fail (e.g., file not found,
         // no permission), so it returns an `io::Result<String>`.
         // The `?` operator is used here: if `read_to_string` returns an `Err(io_error)`,
         // the `?` operator will immediately return that `Err(io_error)` from the `main` function.
         // If it's `Ok(content)`, `content` is assigned to `source_code`.
-        fs::read_to_string(file_path)?
+        // A file over the `--max-bytes` limit is refused with a message instead.
+        match read_source(file_path, cli.max_bytes) {
+            Ok(source) => source,
+            Err(e) if e.kind() == io::ErrorKind::FileTooLarge => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            Err(e) => return Err(e),
+        }
     } else {
         // If no file path argument is provided, use a default hardcoded string for demonstration.
         // `eprintln!`: Prints to standard error (`stderr`). This is good for informational
         // messages or errors that shouldn't be part of the primary output (which goes to `stdout`).
-        eprintln!("No source file provided. Use default example code.");
+        if !cli.quiet {
+            eprintln!("No source file provided. Use default example code.");
+        }
         "int main () { return 0; }".to_string() // Convert `&str` to `String`
     };
-    // Print the source code being processed to `stderr` for user visibility.
-    eprintln!("--- Source Code ---");
-    eprintln!("{}", source_code);
-    eprintln!("-------------------");
+    // Print the source code being processed to `stderr` for user visibility, unless `--quiet`.
+    if !cli.quiet {
+        eprintln!("--- Source Code ---");
+        eprintln!("{}", source_code);
+        eprintln!("-------------------");
+    }
 
     // --- 5. Instantiate and Run the Lexer ---
     // Create a new `Lexer` instance, passing a reference to the `source_code`.
     // `lexer_instance` needs to be mutable (`mut`) because `tokenize_all` (which calls
     // `next_token_internal`) modifies the lexer's internal `position`.
-    let mut lexer_instance = Lexer::new(&source_code);
+    // The lexer is given the name of the input, so that errors (both the JSON output and the
+    // rendered message on `stderr`) say which file they came from. The built-in example
+    // program has no file, so it is called `<example>`.
+    // It also uses the line/column numbering, tab width and token limit selected on the
+    // command line.
+    // For `--format html`, comments are kept as tokens, so they can be highlighted too.
+    let source_name = cli.file_path.as_deref().unwrap_or("<example>");
+    let mut lexer_instance = LexerBuilder::new()
+        .file_name(source_name)
+        .numbering(cli.numbering)
+        .tab_width(cli.tab_width)
+        .max_tokens(cli.max_tokens)
+        .keep_comments(cli.format == OutputFormat::Html)
+        .build(&source_code);
+
+    // In `--ndjson` mode, the tokens are streamed straight to the output as they are produced,
+    // and the program exits right away; the rest of `main` builds a single JSON document instead.
+    // The output is wrapped in a `BufWriter` so that each small line doesn't become its own write.
+    if cli.ndjson {
+        let result = match &cli.output_path {
+            Some(output_path) => fs::File::create(output_path).and_then(|file| {
+                write_ndjson(
+                    &mut lexer_instance,
+                    &source_code,
+                    cli.error_format,
+                    cli.tab_width,
+                    &mut BufWriter::new(file),
+                )
+            }),
+            None => write_ndjson(
+                &mut lexer_instance,
+                &source_code,
+                cli.error_format,
+                cli.tab_width,
+                &mut BufWriter::new(io::stdout().lock()),
+            ),
+        };
+        match result {
+            Ok(true) => return Ok(()),
+            // A lexing error was already written as the last line; only the exit code remains.
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("Error: Failed to write NDJSON output: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // In `--table` and `--tokens-only` mode and with a `--format` other than `json`, the tokens
+    // are printed as a table, a bare list, an S-expression, highlighted HTML or MessagePack
+    // instead of JSON. A lexing error is only shown on `stderr`, as there is no JSON document
+    // to put it in; with `--tokens-only` it is just the one-line message (its `Display`).
+    if cli.table || cli.tokens_only || cli.format != OutputFormat::Json {
+        let started = Instant::now();
+        let result = lexer_instance.tokenize_all_spanned();
+        let elapsed = started.elapsed();
+        let tokens = match result {
+            Ok(tokens) => tokens,
+            Err(e) if cli.tokens_only => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    format_error(&e, &source_code, cli.error_format, cli.tab_width)
+                );
+                std::process::exit(1);
+            }
+        };
+        if cli.stats {
+            eprintln!("{}", format_stats(elapsed, tokens.len(), source_code.len()));
+        }
+        for warning in lexer_instance.warnings() {
+            eprintln!(
+                "{}",
+                format_warning(warning, &source_code, cli.error_format, cli.tab_width)
+            );
+        }
+        // The output is built as bytes, since MessagePack is binary rather than text.
+        let bytes = if cli.table {
+            format_table(&tokens, &source_code, cli.numbering, cli.tab_width).into_bytes()
+        } else if cli.tokens_only {
+            let lines: Vec<String> = tokens
+                .iter()
+                .map(|(token, _)| format!("{:?}\n", token))
+                .collect();
+            lines.concat().into_bytes()
+        } else if cli.format == OutputFormat::Html {
+            format!("{}\n", to_html(&source_code, &tokens)).into_bytes()
+        } else {
+            let tokens: Vec<Token> = tokens.into_iter().map(|(token, _)| token).collect();
+            if cli.format == OutputFormat::Msgpack {
+                // `to_vec_named` encodes struct variants (like `Constant`) as maps with field
+                // names, matching the JSON output, rather than as bare arrays.
+                match rmp_serde::to_vec_named(&tokens) {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        eprintln!(
+                            "Internal Error: Failed to serialize lexer output to MessagePack: {}",
+                            e
+                        );
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                format!("{}\n", to_sexpr(&tokens)).into_bytes()
+            }
+        };
+        let written = match &cli.output_path {
+            Some(output_path) => fs::write(output_path, bytes),
+            None => {
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(&bytes).and_then(|()| stdout.flush())
+            }
+        };
+        if let Err(e) = written {
+            let target = cli.output_path.as_deref().unwrap_or("stdout");
+            eprintln!("Error: Failed to write output to '{}': {}", target, e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
     // Call `tokenize_all()` on the lexer instance. This attempts to convert the
     // entire `source_code` into a sequence of tokens.
     // `match lexer_instance.tokenize_all()`: Handle the `Result` returned by `tokenize_all`.
-    let output = match lexer_instance.tokenize_all() {
-        // If `tokenize_all` returns `Ok(tokens)`, lexing was successful.
-        // Wrap the `tokens` vector in the `CompilerOutput::Success` variant.
-        Ok(tokens) => CompilerOutput::Success(tokens),
-        // If `tokenize_all` returns `Err(e)`, a lexing error occurred.
-        // Wrap the `LexerError` instance `e` in the `CompilerOutput::Error` variant.
-        Err(e) => CompilerOutput::Error(e),
+    // In `--spans` mode, `tokenize_all_spanned` is used instead so that each token keeps its span.
+    // The call is timed for `--stats`; building the output afterwards is not included.
+    let started = Instant::now();
+    let elapsed;
+    let output = if cli.by_line {
+        // `--by-line` needs the spans to know which line each token starts on.
+        let result = lexer_instance.tokenize_all_spanned();
+        elapsed = started.elapsed();
+        match result {
+            Ok(tokens) => {
+                let index = LineIndex::new(&source_code);
+                let mut groups = group_by_line(tokens, &index);
+                // Line numbers follow `--line-base`, like those in errors.
+                for group in &mut groups {
+                    group.line = cli.numbering.apply((group.line, 1)).0;
+                }
+                CompilerOutput::LineSuccess(groups)
+            }
+            Err(e) => CompilerOutput::Error(vec![e]),
+        }
+    } else if cli.spans {
+        let result = lexer_instance.tokenize_all_spanned();
+        elapsed = started.elapsed();
+        match result {
+            Ok(tokens) => CompilerOutput::SpannedSuccess(
+                tokens
+                    .into_iter()
+                    .map(|(token, span)| SpannedToken { token, span })
+                    .collect(),
+            ),
+            Err(e) => CompilerOutput::Error(vec![e]),
+        }
+    } else {
+        let result = lexer_instance.tokenize_all();
+        elapsed = started.elapsed();
+        match result {
+            // If `tokenize_all` returns `Ok(tokens)`, lexing was successful.
+            // Wrap the `tokens` vector in the `CompilerOutput::Success` variant.
+            Ok(tokens) => CompilerOutput::Success(tokens),
+            // If `tokenize_all` returns `Err(e)`, a lexing error occurred.
+            // Wrap the `LexerError` instance `e` in the `CompilerOutput::Error` variant.
+            Err(e) => CompilerOutput::Error(vec![e]),
+        }
     };
 
-    // --- 6. Serialize Output to JSON and Print to Standard Output (`stdout`) ---
+    // With `--stats`, report the timing on `stderr`, so the JSON output is unaffected.
+    // Stats are only reported for successful runs: a failed run stops at the error, so its
+    // figures would not describe the whole input.
+    if cli.stats {
+        match &output {
+            CompilerOutput::Success(tokens) => {
+                eprintln!("{}", format_stats(elapsed, tokens.len(), source_code.len()))
+            }
+            CompilerOutput::SpannedSuccess(tokens) => {
+                eprintln!("{}", format_stats(elapsed, tokens.len(), source_code.len()))
+            }
+            CompilerOutput::LineSuccess(groups) => {
+                let token_count = groups.iter().map(|group| group.tokens.len()).sum();
+                eprintln!("{}", format_stats(elapsed, token_count, source_code.len()))
+            }
+            CompilerOutput::Error(_) => {}
+        }
+    }
+
+    // --- 6. Serialize Output to JSON and Write it to `stdout` (or the `--output` file) ---
     // `serde_json::to_string_pretty(&output)`: Attempts to serialize the `output`
     // (which is a `CompilerOutput` enum instance) into a JSON string.
     // `to_string_pretty` formats the JSON with indentation for human readability.
     // This operation can also fail (though rarely, e.g., if a type cannot be serialized),
     // so it returns a `Result<String, serde_json::Error>`.
-    match serde_json::to_string_pretty(&output) {
+    // With `--lines`, the line-starts table is added next to the output (see `OutputDocument`).
+    // With `--summary`, so are the token counts; a failed run has no tokens to summarize.
+    let summary = match &output {
+        _ if !cli.summary => None,
+        CompilerOutput::Success(tokens) => Some(summarize(tokens)),
+        CompilerOutput::SpannedSuccess(tokens) => {
+            Some(summarize(tokens.iter().map(|spanned| &spanned.token)))
+        }
+        CompilerOutput::LineSuccess(groups) => {
+            Some(summarize(groups.iter().flat_map(|group| &group.tokens)))
+        }
+        CompilerOutput::Error(_) => None,
+    };
+    // Warnings are shown on `stderr` too, in the selected `--error-format`.
+    let warnings = lexer_instance.warnings().to_vec();
+    for warning in &warnings {
+        eprintln!(
+            "{}",
+            format_warning(warning, &source_code, cli.error_format, cli.tab_width)
+        );
+    }
+    let document = OutputDocument {
+        output,
+        lines: cli
+            .lines
+            .then(|| LineIndex::new(&source_code).line_starts().to_vec()),
+        summary,
+        warnings,
+    };
+    match serde_json::to_string_pretty(&document) {
         // If serialization is successful (`Ok(json_string)`):
         Ok(json_string) => {
-            // `println!("{}", json_string)`: Print the resulting JSON string to standard output.
-            // This is the primary way this lexer communicates its results to other tools or scripts.
-            println!("{}", json_string);
+            if let Some(output_path) = &cli.output_path {
+                // With `--output`, write the JSON to the requested file instead.
+                // `fs::write` creates the file if it does not exist and truncates it if it does.
+                // Failing to write the results is fatal: report it and exit with a failure code.
+                if let Err(e) = fs::write(output_path, format!("{}\n", json_string)) {
+                    eprintln!("Error: Failed to write output to '{}': {}", output_path, e);
+                    std::process::exit(1);
+                }
+            } else {
+                // `println!("{}", json_string)`: Print the resulting JSON string to standard output.
+                // This is the primary way this lexer communicates its results to other tools or scripts.
+                println!("{}", json_string);
+            }
         }
         // If JSON serialization itself fails (`Err(e)`):
         Err(e) => {
@@ -145,9 +1155,17 @@ fn main() -> io::Result<()> {
     // Even if JSON serialization was successful, we need to set the program's exit code
     // to reflect whether the *lexing* of the source code was successful.
     // This is important for scripting and build tools that check exit codes.
-    if let CompilerOutput::Error(_) = output {
-        // If the `output` was the `Error` variant (meaning a `LexerError` occurred),
-        // exit the program with a status code of 1 to indicate failure.
+    if let CompilerOutput::Error(errors) = document.output {
+        // If the `output` was the `Error` variant (meaning `LexerError`s occurred),
+        // also show them to the user on `stderr` (by default in human-readable form, with the
+        // offending source line and a caret under each error; see `--error-format`), then exit
+        // the program with a status code of 1 to indicate failure.
+        for e in &errors {
+            eprintln!(
+                "{}",
+                format_error(e, &source_code, cli.error_format, cli.tab_width)
+            );
+        }
         std::process::exit(1);
     }
     // If the program reaches this point, it means:
@@ -158,3 +1176,108 @@ fn main() -> io::Result<()> {
     // Returning `Ok(())` from `main` results in an exit code of 0 (success).
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The stats report the figures and the rates derived from them.
+    #[test]
+    fn stats_report_rates() {
+        assert_eq!(
+            format_stats(Duration::from_millis(500), 9, 25),
+            "--- Stats ---\nTime: 500.000 ms\nTokens: 9\nBytes: 25\nThroughput: 50 bytes/s, 18 tokens/s"
+        );
+    }
+
+    // An unmeasurably short run has no rates rather than dividing by zero.
+    #[test]
+    fn stats_without_elapsed_time() {
+        assert!(format_stats(Duration::ZERO, 9, 25).ends_with("Throughput: n/a"));
+    }
+
+    // The summary of `int main() {}` counts each category of token.
+    #[test]
+    fn summary_counts_categories() {
+        let tokens = Lexer::new("int main() {}").tokenize_all().unwrap();
+        let summary = summarize(&tokens);
+        assert_eq!(
+            summary.into_iter().collect::<Vec<_>>(),
+            [("identifier", 1), ("keyword", 1), ("punctuation", 4)]
+        );
+    }
+
+    // The table has a header and one aligned row per token, with control characters escaped.
+    #[test]
+    fn table_columns_line_up() {
+        let source = "int\tmain\n";
+        let mut lexer = LexerBuilder::new().emit_newlines(true).build(source);
+        let tokens = lexer.tokenize_all_spanned().unwrap();
+        assert_eq!(
+            format_table(&tokens, source, Numbering::default(), 4),
+            "#  Kind        Value  Position\n\
+             0  KwInt       int    1:1\n\
+             1  Identifier  main   1:5\n\
+             2  Newline     \\n     1:9\n"
+        );
+    }
+
+    // `cli` parses `args` as command-line arguments.
+    fn cli(args: &[&str]) -> CliOptions {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_args(&args).unwrap()
+    }
+
+    // The REPL lexes each line on its own and keeps going after an error.
+    #[test]
+    fn repl_lexes_line_by_line() {
+        let input = "int x;\n$\n\nreturn 0;\n";
+        let mut output = Vec::new();
+        run_repl(input.as_bytes(), &mut output, &cli(&["--quiet", "--repl"])).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[\"KwInt\",{\"Identifier\":\"x\"},\"Semicolon\"]\n\
+             []\n\
+             [\"KwReturn\",{\"Constant\":{\"value\":0,\"raw\":\"0\"}},\"Semicolon\"]\n"
+        );
+    }
+
+    // Each call of the watcher's step prints the document for the file's current content.
+    #[test]
+    fn watch_step_prints_the_current_content() {
+        let path = std::env::temp_dir().join(format!("obv_lexer_watch_{}.c", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        let options = cli(&["--quiet", "--lines", "--watch", path_str]);
+        let mut output = Vec::new();
+        fs::write(&path, "int x;\n").unwrap();
+        lex_and_print(path_str, &options, &mut output).unwrap();
+        fs::write(&path, "return\n").unwrap();
+        lex_and_print(path_str, &options, &mut output).unwrap();
+        fs::remove_file(&path).unwrap();
+        // A file that cannot be read is skipped.
+        lex_and_print(path_str, &options, &mut output).unwrap();
+        let first = "{\n  \"Success\": [\n    \"KwInt\",\n    {\n      \"Identifier\": \"x\"\n    },\n    \"Semicolon\"\n  ],\n  \"lines\": [\n    0,\n    7\n  ]\n}\n";
+        let second =
+            "{\n  \"Success\": [\n    \"KwReturn\"\n  ],\n  \"lines\": [\n    0,\n    7\n  ]\n}\n";
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("{}{}", first, second)
+        );
+    }
+
+    // Several errors are serialized as one array, each with its code, in source order.
+    #[test]
+    fn errors_serialize_as_an_array() {
+        let (_, errors) = Lexer::new("$ x @").tokenize_all_recovering();
+        let json = serde_json::to_string(&CompilerOutput::Error(errors)).unwrap();
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"Error":["#,
+                r#"{"code":"E0001","unexpected_character":{"char":"$","pos":0,"end":1,"line":1,"column":1,"context":{"text":"$ x @","column":0}}},"#,
+                r#"{"code":"E0001","unexpected_character":{"char":"@","pos":4,"end":5,"line":1,"column":5,"context":{"text":"$ x @","column":4}}}"#,
+                r#"]}"#
+            )
+        );
+    }
+}
@@ -0,0 +1,102 @@
+// Allocation tests for the zero-copy API. A counting global allocator records how many
+// allocations the current thread makes, so a test can check that lexing does not allocate
+// once per token. Counts are kept per thread, so tests running in parallel don't mix up
+// each other's allocations.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use obv_lexer::{Lexer, LexerBuilder};
+
+// `CountingAllocator` forwards to the system allocator, counting every allocation made by
+// the current thread in `ALLOCATIONS`.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // `try_with` fails instead of panicking while the thread is being torn down.
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// `allocations` returns how many allocations `f` made on the current thread.
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+// `borrowed_allocations` counts the allocations of `tokenize_all_borrowed` on `n`
+// statements of the form `int name_<i>;`, each holding a distinct identifier.
+fn borrowed_allocations(n: usize) -> usize {
+    let source: String = (0..n).map(|i| format!("int name_{i};\n")).collect();
+    allocations(|| {
+        let tokens = Lexer::new(&source).tokenize_all_borrowed().unwrap();
+        assert_eq!(tokens.len(), 3 * n);
+    })
+}
+
+// The number of allocations does not grow with the number of identifiers: only the token
+// vector itself is (re)allocated, a logarithmic number of times.
+#[test]
+fn borrowed_tokens_do_not_allocate_per_identifier() {
+    // Warm up any lazily built state (the regexes and their caches) first.
+    borrowed_allocations(1);
+    let small = borrowed_allocations(100);
+    let large = borrowed_allocations(10_000);
+    assert!(
+        large <= small + 10,
+        "{small} allocations for 100 identifiers, {large} for 10000"
+    );
+}
+
+// For comparison, the owned tokens allocate at least one `String` per identifier.
+#[test]
+fn owned_tokens_allocate_per_identifier() {
+    let source: String = (0..1_000).map(|i| format!("int name_{i};\n")).collect();
+    Lexer::new("int x;").tokenize_all().unwrap();
+    let count = allocations(|| {
+        Lexer::new(&source).tokenize_all().unwrap();
+    });
+    assert!(
+        count >= 1_000,
+        "only {count} allocations for 1000 identifiers"
+    );
+}
+
+// Borrowed tokens are serialized straight from the input: writing them as JSON into a buffer
+// that is already large enough allocates nothing per token, without converting them to
+// owned `Token`s first.
+#[test]
+fn borrowed_tokens_serialize_without_allocating() {
+    let source: String = (0..1_000)
+        .map(|i| format!("int name_{i}; return {i}; // note {i}\n"))
+        .collect();
+    let tokens = LexerBuilder::new()
+        .keep_comments(true)
+        .build(&source)
+        .tokenize_all_borrowed()
+        .unwrap();
+    let mut json = Vec::with_capacity(256 * tokens.len());
+    let count = allocations(|| {
+        serde_json::to_writer(&mut json, &tokens).unwrap();
+    });
+    assert!(
+        count <= 10,
+        "{count} allocations for {} tokens",
+        tokens.len()
+    );
+    assert!(json.starts_with(br#"["KwInt",{"Identifier":"name_0"},"#));
+}
@@ -0,0 +1,220 @@
+// Integration tests for the command-line front end. Each test runs the compiled `obv_lexer`
+// binary on a source file from `tests/golden/` and compares what it prints with a checked-in
+// golden file next to it, so any change to the output format shows up as a failing test.
+// To update the golden files after an intended change, run the tests with `UPDATE_GOLDEN=1`.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+// `run` runs the binary with `args` from the crate root, so that the file paths passed to it
+// (and reported in its errors) are the same on every machine.
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_obv_lexer"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run the obv_lexer binary")
+}
+
+// `assert_golden` compares `actual` with the golden file `tests/golden/<name>`.
+fn assert_golden(name: &str, actual: &[u8]) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+    }
+    let expected = fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    assert_eq!(
+        String::from_utf8_lossy(actual),
+        String::from_utf8_lossy(&expected),
+        "output differs from {}",
+        path.display()
+    );
+}
+
+// `--spans` pairs every token with its byte range.
+#[test]
+fn spans() {
+    let output = run(&["--quiet", "--spans", "tests/golden/sample.c"]);
+    assert!(output.status.success());
+    assert_golden("spans.json", &output.stdout);
+}
+
+// `--output` writes the JSON document to a file instead of `stdout`.
+#[test]
+fn output_file() {
+    let path = std::env::temp_dir().join(format!("obv_lexer_output_{}.json", std::process::id()));
+    let output = run(&[
+        "--quiet",
+        "--output",
+        path.to_str().unwrap(),
+        "tests/golden/sample.c",
+    ]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    let written = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_golden("output.json", &written);
+}
+
+// `--ndjson` writes one compact JSON value per line.
+#[test]
+fn ndjson() {
+    let output = run(&["--quiet", "--ndjson", "tests/golden/sample.c"]);
+    assert!(output.status.success());
+    assert_golden("ndjson.jsonl", &output.stdout);
+}
+
+// A lexing error ends the NDJSON stream with an error line and a failure exit code.
+#[test]
+fn ndjson_error() {
+    let output = run(&["--quiet", "--ndjson", "tests/golden/error.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_golden("ndjson_error.jsonl", &output.stdout);
+}
+
+// `--stats` reports on `stderr`, leaving the JSON on `stdout` unchanged.
+#[test]
+fn stats() {
+    let output = run(&["--quiet", "--stats", "tests/golden/sample.c"]);
+    assert!(output.status.success());
+    assert_golden("output.json", &output.stdout);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("--- Stats ---\nTime: "));
+    assert!(stderr.contains("\nTokens: 9\nBytes: 27\n"));
+}
+
+// `--error-format gcc` prints errors as single `file:line:col: error: message [code]` lines.
+#[test]
+fn gcc_error_format() {
+    let output = run(&["--quiet", "--error-format", "gcc", "tests/golden/error.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "tests/golden/error.c:2:10: error: Unexpected character '$' [E0001]\n"
+    );
+}
+
+// `--lines` adds the byte offset at which each line starts, counting a CRLF as one line break
+// and ending without an entry past the last line when the file has no trailing newline.
+#[test]
+fn lines() {
+    let output = run(&["--quiet", "--lines", "tests/golden/crlf.c"]);
+    assert!(output.status.success());
+    assert_golden("lines.json", &output.stdout);
+}
+
+// `--by-line` groups the tokens by the line they start on, leaving out lines that hold only
+// comments or whitespace.
+#[test]
+fn by_line() {
+    let output = run(&["--quiet", "--by-line", "tests/golden/comments.c"]);
+    assert!(output.status.success());
+    assert_golden("by_line.json", &output.stdout);
+}
+
+// `--summary` adds the number of tokens of each category.
+#[test]
+fn summary() {
+    let output = run(&["--quiet", "--summary", "tests/golden/sample.c"]);
+    assert!(output.status.success());
+    assert_golden("summary.json", &output.stdout);
+}
+
+// `--quiet` drops the source echo and banners from `stderr`, but still reports errors.
+#[test]
+fn quiet() {
+    let loud = run(&["tests/golden/error.c"]);
+    let stderr = String::from_utf8_lossy(&loud.stderr);
+    assert!(stderr.starts_with("--- Source Code ---\n"));
+
+    let quiet = run(&["--quiet", "tests/golden/error.c"]);
+    assert_eq!(quiet.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&quiet.stderr);
+    assert!(!stderr.contains("---"));
+    assert!(stderr.starts_with("error[E0001]: Unexpected character '$'\n"));
+    assert_eq!(quiet.stdout, loud.stdout);
+}
+
+// Warnings are listed under their own `warnings` key, next to the tokens.
+#[test]
+fn warnings() {
+    let output = run(&["--quiet", "tests/golden/warnings.c"]);
+    assert!(output.status.success());
+    assert_golden("warnings.json", &output.stdout);
+}
+
+// A failed run prints its errors as an array under `Error`, and exits with status 1.
+#[test]
+fn error() {
+    let output = run(&["--quiet", "tests/golden/error.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_golden("error.json", &output.stdout);
+}
+
+// `--format msgpack` writes the tokens as MessagePack, which decodes to the same tokens as
+// the JSON output.
+#[test]
+fn msgpack() {
+    let output = run(&["--quiet", "--format", "msgpack", "tests/golden/sample.c"]);
+    assert!(output.status.success());
+    let decoded: serde_json::Value = rmp_serde::from_slice(&output.stdout).unwrap();
+    let json = run(&["--quiet", "tests/golden/sample.c"]);
+    let json: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+    assert_eq!(decoded, json["Success"]);
+}
+
+// `--max-bytes` refuses a larger file before reading it, and lexes one within the limit.
+#[test]
+fn max_bytes() {
+    let over = run(&["--quiet", "--max-bytes", "26", "tests/golden/sample.c"]);
+    assert_eq!(over.status.code(), Some(1));
+    assert!(over.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8_lossy(&over.stderr),
+        "Error: 'tests/golden/sample.c' is 27 bytes, more than the --max-bytes limit of 26\n"
+    );
+
+    let within = run(&["--quiet", "--max-bytes", "27", "tests/golden/sample.c"]);
+    assert!(within.status.success());
+    assert_golden("output.json", &within.stdout);
+}
+
+// `--tokens-only` prints the `Debug` form of each token on its own line, and only the error
+// message on failure.
+#[test]
+fn tokens_only() {
+    let output = run(&["--quiet", "--tokens-only", "tests/golden/int_x.c"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        ["KwInt", "Identifier(\"x\")", "Semicolon"]
+    );
+
+    let output = run(&["--quiet", "--tokens-only", "tests/golden/error.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[E0001] Unexpected character '$' in tests/golden/error.c at line 2, column 10 (bytes 22..23)\n"
+    );
+}
+
+// `--schema` prints a JSON description of every token and error kind, without reading the
+// source file.
+#[test]
+fn schema() {
+    let output = run(&["--schema", "tests/golden/does_not_exist.c"]);
+    assert!(output.status.success());
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(schema["tokens"][0]["name"], "KwInt");
+    let errors = schema["errors"].as_array().unwrap();
+    assert!(
+        errors
+            .iter()
+            .any(|error| error["name"] == "unexpected_character" && error["code"] == "E0001")
+    );
+}
@@ -0,0 +1,7 @@
+// A sample with comments and blank lines.
+int main() {
+
+  /* a multi-line
+     comment */
+  return 0; // done
+}
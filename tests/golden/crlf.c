@@ -0,0 +1,4 @@
+int x;
+int y;
+
+return
\ No newline at end of file
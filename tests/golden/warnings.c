@@ -0,0 +1 @@
+int x /* a /* b */ 007;